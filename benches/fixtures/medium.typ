@@ -0,0 +1,73 @@
+#set page(paper: "a4", margin: (x: 2cm, y: 2.5cm), numbering: "1")
+#set text(font: "Linux Libertine", size: 11pt, lang: "en")
+#set heading(numbering: "1.1")
+#set par(justify: true, leading: 0.65em)
+
+#let accent = rgb("#1f6feb")
+#let todo(body) = box(fill: yellow, inset: 3pt, radius: 2pt)[*TODO:* #body]
+#let theorem(title: none, body) = block(
+  width: 100%, inset: 8pt, stroke: (left: 2pt + accent),
+)[
+  #if title != none [*#title.* ]
+  #body
+]
+
+= Background
+
+Formatting documents by hand is tedious, and when a document is maintained by
+several people the style quickly drifts apart. This section recalls the
+notions used in the rest of the text, see @results for the measurements.
+
+== Definitions
+
+- A *node* is an element of the syntax tree.
+- A *leaf* is a node without children.
+  - Leaves carry the text of the document.
+  - Inner nodes only group them.
++ First we parse.
++ Then we format.
+
+#theorem(title: "Idempotence")[
+  Formatting an already formatted document leaves it unchanged:
+  $ f(f(x)) = f(x) quad forall x in cal(D) $
+]
+
+#todo[find a better example]
+
+== Code
+
+#let fib(n) = {
+  if n <= 1 {
+    return n
+  }
+  let (a, b) = (0, 1)
+  for _ in range(n - 1) {
+    (a, b) = (b, a + b)
+  }
+  b
+}
+
+The first terms are #range(10).map(fib).map(str).join(", ").
+
+#figure(
+  table(
+    columns: (auto, 1fr, 1fr),
+    align: (left, center, right),
+    [*Name*], [*Value*], [*Unit*],
+    [length], [12.5], [cm],
+    [weight], [3.2], [kg],
+  ),
+  caption: [Some measurements.],
+) <results>
+
+```rust
+fn main() {
+    println!("raw blocks are copied as is");
+}
+```
+
+$ sum_(i=1)^n i = (n (n + 1)) / 2 $
+
+#for (key, value) in (a: 1, b: 2, c: 3) [
+  / #key: #value
+]
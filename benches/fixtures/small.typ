@@ -0,0 +1,9 @@
+#set page(paper: "a4", margin: (x: 2cm, y: 2.5cm))
+#set text(font: "Linux Libertine", size: 11pt)
+
+= Introduction
+
+This is a *short* note with some _emphasis_ and a bit of math $a^2 + b^2 = c^2$.
+
+#let greet(name) = [Hello #name!]
+#greet("world")
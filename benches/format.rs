@@ -0,0 +1,78 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use typstfmt::{format, Config};
+
+const SMALL: &str = include_str!("fixtures/small.typ");
+const MEDIUM: &str = include_str!("fixtures/medium.typ");
+/// a real document, from the tests of Pygments, see `fixtures/README.md`.
+const PYGMENTS: &str = include_str!("fixtures/pygments.typ");
+
+fn bench_format(c: &mut Criterion) {
+    // a thesis sized document, built from the medium one to keep the fixtures small.
+    let large = MEDIUM.repeat(50);
+    let mut group = c.benchmark_group("format");
+    let inputs = [
+        ("small", SMALL),
+        ("medium", MEDIUM),
+        ("large", &large),
+        ("pygments", PYGMENTS),
+    ];
+    for (name, input) in inputs {
+        group.bench_with_input(BenchmarkId::from_parameter(name), input, |b, input| {
+            b.iter(|| format(black_box(input), Config::default()))
+        });
+    }
+    group.finish();
+}
+
+/// every node is formatted again by each of its parents, the time grows with the
+/// depth of the tree.
+fn bench_nesting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nesting");
+    for depth in [10, 50, 200] {
+        let blocks = format!("{}text{}", "#[".repeat(depth), "]".repeat(depth));
+        let arrays = format!("#let a = {}1{}", "(".repeat(depth), ",)".repeat(depth));
+        for (name, input) in [("blocks", blocks), ("arrays", arrays)] {
+            group.bench_with_input(BenchmarkId::new(name, depth), &input, |b, input| {
+                b.iter(|| format(black_box(input), Config::default()))
+            });
+        }
+    }
+    group.finish();
+}
+
+/// the `*.typ` files of the directory `TYPSTFMT_BENCH_CORPUS` as one batch, a
+/// checkout of real projects or packages, `typst/packages` for instance.
+fn bench_corpus(c: &mut Criterion) {
+    let Some(dir) = std::env::var_os("TYPSTFMT_BENCH_CORPUS") else {
+        return;
+    };
+    let mut files = vec![];
+    let mut dirs = vec![std::path::PathBuf::from(dir)];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "typ") {
+                files.extend(std::fs::read_to_string(&path));
+            }
+        }
+    }
+    let bytes: usize = files.iter().map(String::len).sum();
+    let mut group = c.benchmark_group("corpus");
+    group.throughput(criterion::Throughput::Bytes(bytes as u64));
+    group.sample_size(10);
+    group.bench_function(format!("{} files", files.len()), |b| {
+        b.iter(|| {
+            for file in &files {
+                black_box(format(black_box(file), Config::default()));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_format, bench_nesting, bench_corpus);
+criterion_main!(benches);
@@ -0,0 +1,570 @@
+use super::*;
+use crate::sorting::{self, Entry};
+
+/// Written in a trailing comment on the first line of a group (a dictionary, the
+/// arguments of a call, a run of `let`), asks for the group to be aligned.
+pub(crate) const ALIGN_MARKER: &str = "fmt: align";
+
+fn is_marker(node: &LinkedNode) -> bool {
+    node.kind() == LineComment && node.text().contains(ALIGN_MARKER)
+}
+
+/// true if one of the children is the alignment marker, directives
+/// disable alignment.
+pub(crate) fn has_marker(parent: &LinkedNode) -> bool {
+    parent.children().any(|c| is_marker(&c))
+        && !parent.children().any(|c| utils::directive(&c).is_some())
+}
+
+/// the marker on the line of the opening parenthesis of a collection, if any.
+pub(crate) fn collection_marker<'a>(parent: &LinkedNode<'a>) -> Option<LinkedNode<'a>> {
+    if !has_marker(parent) {
+        return None;
+    }
+    parent
+        .children()
+        .take_while(|c| !(c.kind() == Space && c.text().contains('\n')))
+        .find(is_marker)
+}
+
+/// with `align_table_cells`, formats the arguments of a `table` or `grid` call
+/// like [format_aligned] without a marker. `None` if the number of columns isn't
+/// known or a cell can't be aligned.
+pub(crate) fn format_table(
+    parent: &LinkedNode,
+    children: &[String],
+    ctx: &mut Ctx,
+) -> Option<String> {
+    let columns = table_columns(parent)?;
+    let (entries, _, _) = sorting::entries(parent, children);
+    column_widths(&entries, columns)?;
+    Some(format_aligned(parent, children, None, ctx))
+}
+
+/// formats a collection with one entry per line, the values after the keys are
+/// aligned, so are the cells of `table` and `grid` when the number of columns is known.
+///
+/// The marker is put right after the opening parenthesis.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_aligned(
+    parent: &LinkedNode,
+    children: &[String],
+    marker: Option<&LinkedNode>,
+    ctx: &mut Ctx,
+) -> String {
+    ctx.stats.applied("alignment");
+    let marker = marker.map(|marker| &children[marker.index()]);
+    let (mut entries, dangling, after) = sorting::entries(parent, children);
+    for entry in entries.iter_mut().filter(|_| marker.is_some()) {
+        if entry.trailing.as_ref() == marker {
+            entry.trailing = None;
+            break;
+        }
+        if let Some(i) = entry.leading.iter().position(|c| Some(c) == marker) {
+            entry.leading.remove(i);
+            break;
+        }
+    }
+
+    let widths = table_columns(parent).and_then(|columns| column_widths(&entries, columns));
+    let key_width = entries
+        .iter()
+        .filter_map(|e| e.key_text.as_ref())
+        .map(|key| utils::width(key))
+        .max()
+        .unwrap_or(0);
+
+    let mut res = String::new();
+    match marker {
+        Some(marker) => {
+            ctx.push_raw_in("( ", &mut res);
+            ctx.push_raw_in(marker, &mut res);
+            ctx.push_raw_in("\n", &mut res);
+        }
+        None => ctx.push_raw_in("(\n", &mut res),
+    }
+    let mut column = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        if let Some(widths) = widths.as_ref().filter(|_| is_cell(entry)) {
+            if column == 0 {
+                ctx.push_raw_in(&ctx.get_indent(), &mut res);
+            }
+            ctx.push_raw_in(&entry.text, &mut res);
+            ctx.push_raw_in(",", &mut res);
+            let row_ends = column + 1 == widths.len() || !entries.get(i + 1).is_some_and(is_cell);
+            if row_ends {
+                ctx.push_raw_in("\n", &mut res);
+                column = 0;
+            } else {
+                let pad = widths[column] - utils::width(&entry.text);
+                ctx.push_raw_in(&(utils::padding(pad) + " "), &mut res);
+                column += 1;
+            }
+            continue;
+        }
+        for comment in &entry.leading {
+            ctx.push_raw_in(&ctx.get_indent(), &mut res);
+            ctx.push_raw_in(comment, &mut res);
+            ctx.push_raw_in("\n", &mut res);
+        }
+        ctx.push_raw_in(&ctx.get_indent(), &mut res);
+        ctx.push_raw_indent(&aligned(entry, key_width), &mut res);
+        ctx.push_raw_in(",", &mut res);
+        if let Some(comment) = &entry.trailing {
+            let gap = if comment.starts_with("//") {
+                ctx.comment_gap()
+            } else {
+                " ".to_string()
+            };
+            ctx.push_raw_in(&gap, &mut res);
+            ctx.push_raw_in(comment, &mut res);
+        }
+        ctx.push_raw_in("\n", &mut res);
+    }
+    for comment in &dangling {
+        ctx.push_raw_in(&ctx.get_indent(), &mut res);
+        ctx.push_raw_in(comment, &mut res);
+        ctx.push_raw_in("\n", &mut res);
+    }
+    ctx.push_raw_in(")", &mut res);
+    for s in &after {
+        ctx.push_raw_in(s, &mut res);
+    }
+    res
+}
+
+/// A line of a collection aligned by hand: its cells, each with the separator
+/// following it if any, and the comment ending the line.
+#[derive(Default)]
+struct Row<'a> {
+    cells: Vec<(LinkedNode<'a>, Option<LinkedNode<'a>>)>,
+    comment: Option<LinkedNode<'a>>,
+}
+
+impl Row<'_> {
+    /// the columns of the cells after the first in the source.
+    fn starts(&self) -> Vec<usize> {
+        self.cells[1..]
+            .iter()
+            .map(|(c, _)| source_column(c))
+            .collect()
+    }
+
+    /// the columns of the separators in the source.
+    fn separators(&self) -> Vec<usize> {
+        let separators = self.cells.iter().filter_map(|(_, s)| s.as_ref());
+        separators.map(source_column).collect()
+    }
+}
+
+/// true if the rows with several cells have them at the same columns, by
+/// `columns` of each row.
+fn aligned_rows(rows: &[Row], columns: impl Fn(&Row) -> Vec<usize>) -> bool {
+    let rows: Vec<_> = rows.iter().filter(|row| row.cells.len() >= 2).collect();
+    rows.len() >= 2 && rows.windows(2).all(|w| columns(w[0]) == columns(w[1]))
+}
+
+/// true if `node` is a row of a matrix, `1, 0` in `mat(1, 0; 0, 1)`.
+fn is_matrix_row(node: &LinkedNode) -> bool {
+    node.kind() == Array
+        && node
+            .parent()
+            .is_some_and(|p| p.kind() == Args && p.children().any(|c| c.kind() == Semicolon))
+}
+
+/// the rows of the source of a collection, those of a matrix are its arrays.
+/// `None` if a cell is on several lines or a block comment is in the way.
+fn rows<'a>(parent: &LinkedNode<'a>) -> Option<Vec<Row<'a>>> {
+    let mut rows = vec![];
+    let mut row = Row::default();
+    for child in parent.children() {
+        match child.kind() {
+            LeftParen | RightParen => {}
+            Space if child.text().contains('\n') => {
+                if !row.cells.is_empty() || row.comment.is_some() {
+                    rows.push(std::mem::take(&mut row));
+                }
+            }
+            Space => {}
+            Comma | Semicolon => {
+                let (_, separator) = row.cells.last_mut()?;
+                if separator.is_some() {
+                    return None;
+                }
+                *separator = Some(child);
+            }
+            LineComment => row.comment = Some(child),
+            BlockComment => return None,
+            _ if child.clone().into_text().contains('\n') => return None,
+            _ if is_matrix_row(&child) => {
+                for cell in child.children() {
+                    match cell.kind() {
+                        Space => {}
+                        Comma => {
+                            let (_, separator) = row.cells.last_mut()?;
+                            *separator = Some(cell);
+                        }
+                        LineComment | BlockComment => return None,
+                        _ => row.cells.push((cell, None)),
+                    }
+                }
+            }
+            _ => row.cells.push((child, None)),
+        }
+    }
+    if !row.cells.is_empty() || row.comment.is_some() {
+        rows.push(row);
+    }
+    Some(rows)
+}
+
+/// the column `node` starts at in the source.
+fn source_column(node: &LinkedNode) -> usize {
+    utils::width(&utils::line_before(node))
+}
+
+/// with `preserve_alignment`, true if the source of the collection is aligned in
+/// columns by hand, like the rows of a matrix: on each line the cells after the
+/// first start at the same columns, or the separators are at the same columns for
+/// cells aligned to the right, and spaces were added to get there.
+pub(crate) fn is_hand_aligned(parent: &LinkedNode) -> bool {
+    let text = parent.get().clone().into_text();
+    if !text.contains('\n') || utils::contains_directive(parent) {
+        return false;
+    }
+    let Some(rows) = rows(parent) else {
+        return false;
+    };
+    let padded = rows.iter().any(|row| {
+        row.cells.windows(2).any(|w| match &w[0].1 {
+            Some(separator) => source_column(&w[1].0) > source_column(separator) + 2,
+            None => false,
+        })
+    });
+    padded && (aligned_rows(&rows, Row::starts) || aligned_rows(&rows, Row::separators))
+}
+
+/// with `preserve_alignment`, leaves the formatted cells of a matrix row for the
+/// matrix to align them, see [format_hand_aligned].
+pub(crate) fn keep_matrix_row(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) {
+    if is_matrix_row(parent) && parent.parent().is_some_and(|p| is_hand_aligned(&p)) {
+        ctx.laid_out_again
+            .insert((Array, parent.range()), children.to_vec());
+    }
+}
+
+/// a hand aligned collection with its cells formatted, on the lines they are in
+/// the source, indented relative to the line it starts on like the other
+/// formatted nodes.
+///
+/// The spaces of the source are kept if no cell changed, else the cells are
+/// aligned again: a column is as wide as its widest cell, the cells start at the
+/// same column or end at the same column if the source aligned their separators.
+pub(crate) fn format_hand_aligned(
+    parent: &LinkedNode,
+    children: &[String],
+    ctx: &mut Ctx,
+) -> String {
+    ctx.stats.applied("preserve_alignment");
+    let rows = rows(parent).unwrap_or_default();
+    // the formatted cells of each row.
+    let mut cells: Vec<Vec<String>> = vec![];
+    for row in &rows {
+        let mut texts = vec![];
+        for (cell, _) in &row.cells {
+            let formatted = match cell.parent() {
+                Some(array) if array.kind() == Array && is_matrix_row(&array) => ctx
+                    .laid_out_again
+                    .get(&(Array, array.range()))
+                    .map(|inner| inner[cell.index()].clone()),
+                _ => Some(children[cell.index()].clone()),
+            };
+            texts.push(formatted.unwrap_or_else(|| cell.clone().into_text().to_string()));
+        }
+        cells.push(texts);
+    }
+    for child in parent.children().filter(is_matrix_row) {
+        ctx.laid_out_again.remove(&(Array, child.range()));
+    }
+    let unchanged = rows.iter().zip(&cells).all(|(row, texts)| {
+        row.cells
+            .iter()
+            .zip(texts)
+            .all(|((cell, _), text)| cell.clone().into_text().as_str() == text)
+    });
+    let right = !aligned_rows(&rows, Row::starts);
+    let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = (0..columns)
+        .map(|k| {
+            cells
+                .iter()
+                .filter_map(|texts| texts.get(k))
+                .map(|text| utils::width(text))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = utils::line_before(parent);
+    let base = &line[..line.len() - line.trim_start().len()];
+    let mut res = String::new();
+    let mut rows = rows.iter().zip(&cells).peekable();
+    for child in parent.children() {
+        match child.kind() {
+            LeftParen | RightParen => ctx.push_raw_in(child.text(), &mut res),
+            Space if child.text().contains('\n') => {
+                let indent = child.text().rsplit('\n').next().unwrap_or_default();
+                ctx.push_raw_in("\n", &mut res);
+                ctx.push_raw_in(indent.strip_prefix(base).unwrap_or(indent), &mut res);
+            }
+            // a row is pushed at its first cell, or its comment if it has none.
+            _ => {
+                let starts_row = rows.peek().is_some_and(|(row, _)| {
+                    let first = row.cells.first().map(|(c, _)| c).or(row.comment.as_ref());
+                    first.is_some_and(|first| first.offset() == child.offset())
+                });
+                if let Some((row, texts)) = rows.next_if(|_| starts_row) {
+                    let spacing = Spacing {
+                        widths: &widths,
+                        unchanged,
+                        right,
+                    };
+                    push_row(row, texts, children, spacing, ctx, &mut res);
+                }
+            }
+        }
+    }
+    res
+}
+
+/// how [format_hand_aligned] spaces the cells of a row.
+#[derive(Clone, Copy)]
+struct Spacing<'a> {
+    /// the width of the widest cell of each column.
+    widths: &'a [usize],
+    /// true if the spaces of the source are kept.
+    unchanged: bool,
+    /// true if the cells end at the same columns.
+    right: bool,
+}
+
+/// a row of [format_hand_aligned], its comment included.
+fn push_row(
+    row: &Row,
+    texts: &[String],
+    children: &[String],
+    spacing: Spacing,
+    ctx: &mut Ctx,
+    res: &mut String,
+) {
+    let widths = spacing.widths;
+    for (k, ((cell, separator), text)) in row.cells.iter().zip(texts).enumerate() {
+        if k > 0 {
+            let gap = if spacing.unchanged {
+                space_before(cell)
+            } else if spacing.right {
+                utils::padding(widths[k] - utils::width(text)) + " "
+            } else {
+                utils::padding(widths[k - 1] - utils::width(&texts[k - 1])) + " "
+            };
+            ctx.push_raw_in(&gap, res);
+        }
+        ctx.push_raw_in(text, res);
+        if let Some(separator) = separator {
+            ctx.push_raw_in(separator.text(), res);
+        }
+    }
+    let Some(comment) = &row.comment else {
+        return;
+    };
+    if !row.cells.is_empty() {
+        let gap = match space_before(comment) {
+            space if spacing.unchanged && !space.is_empty() => space,
+            _ => ctx.comment_gap(),
+        };
+        ctx.push_raw_in(&gap, res);
+    }
+    ctx.push_raw_in(&children[comment.index()], res);
+}
+
+/// the spaces before `node` in the source.
+fn space_before(node: &LinkedNode) -> String {
+    let before = node.index().checked_sub(1);
+    match before.and_then(|i| node.parent()?.children().nth(i)) {
+        Some(space) if space.kind() == Space => space.text().to_string(),
+        _ => String::new(),
+    }
+}
+
+/// `key: value` with the value starting at the column after the longest key.
+fn aligned(entry: &Entry, key_width: usize) -> String {
+    let Some(key) = &entry.key_text else {
+        return entry.text.clone();
+    };
+    match entry
+        .text
+        .strip_prefix(key.as_str())
+        .and_then(|rest| rest.strip_prefix(':'))
+    {
+        Some(value) => format!(
+            "{key}:{}{}",
+            utils::padding(key_width - utils::width(key)) + " ",
+            value.trim_start()
+        ),
+        None => entry.text.clone(),
+    }
+}
+
+fn is_cell(entry: &Entry) -> bool {
+    entry.key.is_none() && entry.kind != Spread
+}
+
+/// the number of columns of a `table` or `grid` call, from its `columns` argument.
+fn table_columns(parent: &LinkedNode) -> Option<usize> {
+    let call = parent.parent().filter(|p| p.kind() == FuncCall)?;
+    if !matches!(call.children().next()?.text().as_str(), "table" | "grid") {
+        return None;
+    }
+    let columns = parent.children().find(|c| {
+        c.kind() == Named
+            && c.children()
+                .next()
+                .is_some_and(|name| name.text() == "columns")
+    })?;
+    let value = columns.children().last()?;
+    match value.kind() {
+        Int => value.text().parse().ok(),
+        Array => Some(
+            value
+                .children()
+                .filter(|c| {
+                    !matches!(
+                        c.kind(),
+                        LeftParen | RightParen | Comma | Space | LineComment | BlockComment
+                    )
+                })
+                .count(),
+        ),
+        _ => None,
+    }
+    .filter(|columns| *columns > 0)
+}
+
+/// the width of each column, a column starts again after anything that isn't a
+/// cell. Cells with comments or on several lines can't be aligned.
+fn column_widths(entries: &[Entry], columns: usize) -> Option<Vec<usize>> {
+    let mut widths = vec![0; columns];
+    let mut column = 0;
+    for entry in entries {
+        if !is_cell(entry) {
+            column = 0;
+            continue;
+        }
+        if !entry.leading.is_empty() || entry.trailing.is_some() || entry.text.contains('\n') {
+            return None;
+        }
+        widths[column] = widths[column].max(utils::width(&entry.text));
+        column = (column + 1) % columns;
+    }
+    Some(widths)
+}
+
+/// the runs of `let` bindings of `parent` to align, each starting at the binding
+/// followed by the marker and going on with the bindings of the next lines.
+fn let_runs<'a>(parent: &LinkedNode<'a>) -> Vec<Vec<LinkedNode<'a>>> {
+    let children: Vec<LinkedNode> = parent.children().collect();
+    let kind = |i: usize| children.get(i).map(|c| c.kind());
+    let on_one_line = |i: usize| kind(i) == Some(Space) && !children[i].text().contains('\n');
+    // the index after the binding at `i` and the comment on its line, if any.
+    let line_end = |mut i: usize| {
+        i += 1;
+        if on_one_line(i) && kind(i + 1) == Some(LineComment) {
+            i += 2;
+        }
+        i
+    };
+    let mut runs = vec![];
+    let mut i = 0;
+    while i < children.len() {
+        let marked = kind(i) == Some(LetBinding)
+            && on_one_line(i + 1)
+            && children.get(i + 2).is_some_and(is_marker);
+        if !marked {
+            i += 1;
+            continue;
+        }
+        let mut run = vec![children[i].clone()];
+        i = line_end(i);
+        loop {
+            let next_line = kind(i) == Some(Space) && children[i].text().matches('\n').count() == 1;
+            let hash = usize::from(kind(i + 1) == Some(Hash));
+            if !(next_line && kind(i + 1 + hash) == Some(LetBinding)) {
+                break;
+            }
+            run.push(children[i + 1 + hash].clone());
+            i = line_end(i + 1 + hash);
+        }
+        runs.push(run);
+    }
+    runs
+}
+
+/// keeps the formatted binding split at its `=` for [align_let_runs], when its
+/// parent asks for alignment.
+pub(crate) fn keep_let_binding(parent: &LinkedNode, res: &str, eq: usize, ctx: &mut Ctx) {
+    if parent.parent().is_some_and(|p| has_marker(&p)) {
+        let (name, value) = res.split_at(eq);
+        ctx.laid_out_again.insert(
+            (LetBinding, parent.range()),
+            vec![name.to_string(), value.to_string()],
+        );
+    }
+}
+
+/// aligns the `=` of the runs of `let` bindings of `parent`, `res` being the
+/// formatted `parent`. The bindings were split at their `=` by [keep_let_binding].
+pub(crate) fn align_let_runs(parent: &LinkedNode, res: &str, ctx: &mut Ctx) -> String {
+    let mut lines: Vec<String> = res.split('\n').map(str::to_string).collect();
+    let mut line = 0;
+    for run in let_runs(parent) {
+        // the line of each binding and the byte its `=` is at.
+        let mut eqs = vec![];
+        for binding in run {
+            let Some(parts) = ctx.laid_out_again.remove(&(LetBinding, binding.range())) else {
+                break;
+            };
+            let first = parts.concat();
+            let first = first.split('\n').next().unwrap_or_default();
+            let code = |l: &str| l.trim_start().trim_start_matches('#').to_string();
+            let Some(found) = lines[line..]
+                .iter()
+                .position(|l| code(l).starts_with(first))
+            else {
+                break;
+            };
+            line += found;
+            if parts[0].contains('\n') {
+                break;
+            }
+            let start = lines[line].len() - code(&lines[line]).len();
+            eqs.push((line, start + parts[0].len()));
+            line += 1;
+            if parts[1].contains('\n') {
+                break;
+            }
+        }
+        let width = eqs
+            .iter()
+            .map(|&(l, eq)| utils::width(&lines[l][..eq]))
+            .max()
+            .unwrap_or(0);
+        for (l, eq) in eqs {
+            let padding = utils::padding(width - utils::width(&lines[l][..eq]));
+            lines[l].insert_str(eq, &padding);
+        }
+    }
+    for child in parent.children().filter(|c| c.kind() == LetBinding) {
+        ctx.laid_out_again.remove(&(LetBinding, child.range()));
+    }
+    lines.join("\n")
+}
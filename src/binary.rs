@@ -1,8 +1,8 @@
 use super::*;
+use crate::doc::{self, Doc};
 
-/// only format tight cause it would not be supported to format breaking in code blocks
-///
-/// it could be supported in parenthesized.
+/// formats tight, unless the line gets too long: then the whole chain of
+/// operations is broken before each operator, see [chain_doc].
 #[instrument(skip_all, ret)]
 pub(crate) fn format_bin_left_assoc(
     parent: &LinkedNode,
@@ -10,46 +10,107 @@ pub(crate) fn format_bin_left_assoc(
     ctx: &mut Ctx,
 ) -> String {
     let res = format_bin_left_assoc_tight(parent, children, ctx);
-
-    if parent.kind() == Parenthesized
-        && (
-            crate::utils::max_line_length(&res) >= ctx.config.max_line_length
-            // || parent
-            // .children()
-            // .any(|x| x.kind() == LineComment || x.kind() == BlockComment)
-        )
-    {
-        return format_bin_left_assoc_breaking(parent, children, ctx);
+    // `a + b` in `a + b + c` is broken with its parent.
+    if parent.parent_kind() == Some(Binary) && parent.index() == 0 {
+        ctx.laid_out_again
+            .insert((Binary, parent.range()), children.to_vec());
+        return res;
+    }
+    let mut items = vec![];
+    chain(parent, children, ctx, &mut items);
+    // the operations inside the chain included, a line comment can't be put on one line.
+    let has_comment = items
+        .iter()
+        .any(|(kind, _)| matches!(kind, LineComment | BlockComment));
+    let is_assignment = parent
+        .children()
+        .any(|c| matches!(c.kind(), Eq | PlusEq | HyphEq | StarEq | SlashEq));
+    if has_comment || is_assignment || ctx.off {
+        return res;
+    }
+    let (res, broken) = doc::render_in_place(&chain_doc(parent, &items), ctx);
+    if broken {
+        ctx.stats.applied("binary_breaking");
     }
     res
 }
 
-/// not integrated and never used for now
-#[instrument(skip_all)]
-pub(crate) fn format_bin_left_assoc_breaking(
-    parent: &LinkedNode,
-    children: &[String],
-    ctx: &mut Ctx,
-) -> String {
-    let mut res = String::new();
-    for (s, node) in children.iter().zip(parent.children()) {
-        match node.kind() {
-            _ if ctx.off => res.push_str(node.text()),
-            x if BinOp::from_kind(x).is_some() => {
-                ctx.push_in("\n", &mut res);
-                ctx.push_raw_indent(s, &mut res);
+/// the chain `parent` ends as a group, broken before each operator, the operands
+/// after the first indented.
+///
+/// A line break only continues an expression inside parentheses, the chain is
+/// wrapped in some when it breaks and they are missing, in a code block for
+/// instance.
+fn chain_doc(parent: &LinkedNode, items: &[(SyntaxKind, String)]) -> Doc {
+    let mut docs = vec![];
+    let mut after_not = false;
+    for (i, (kind, s)) in items.iter().enumerate() {
+        match kind {
+            Not => {
+                docs.push(Doc::Line);
+                docs.push(Doc::text(s));
+                after_not = true;
             }
-            LineComment | BlockComment => {
-                ctx.push_raw_indent(s, &mut res);
-                ctx.push_in("\n", &mut res);
+            x if BinOp::from_kind(*x).is_some() => {
+                docs.push(if after_not { Doc::text(" ") } else { Doc::Line });
+                docs.push(Doc::text(s));
+                after_not = false;
             }
-            Space => {}
             _ => {
-                ctx.push_in(s, &mut res);
+                if i > 0 {
+                    docs.push(Doc::text(" "));
+                }
+                docs.push(Doc::text(s));
             }
         }
     }
-    res
+    if !breaking_needs_parens(parent) {
+        return Doc::group(vec![Doc::Nest(docs)]);
+    }
+    Doc::group(vec![
+        Doc::IfBreak("(".to_string()),
+        Doc::Nest(docs),
+        Doc::IfBreak(")".to_string()),
+    ])
+}
+
+/// the formatted operands and operators of the chain of left associative
+/// operations ending at `node`, `a`, `+`, `b`, `-`, `c` for `a + b - c`, from the
+/// `children` of `node` and those its inner operations left in
+/// [Ctx::laid_out_again].
+fn chain(
+    node: &LinkedNode,
+    children: &[String],
+    ctx: &mut Ctx,
+    items: &mut Vec<(SyntaxKind, String)>,
+) {
+    for (s, child) in children.iter().zip(node.children()) {
+        match child.kind() {
+            Space => {}
+            Binary if child.index() == 0 => {
+                match ctx.laid_out_again.remove(&(Binary, child.range())) {
+                    Some(inner) => chain(&child, &inner, ctx, items),
+                    // formatted as written, in a `typstfmt::off` region for instance.
+                    None => items.push((Binary, s.clone())),
+                }
+            }
+            kind => items.push((kind, s.clone())),
+        }
+    }
+}
+
+/// true if the nearest enclosing delimiters of `node` aren't parentheses, a line
+/// break would end the expression.
+fn breaking_needs_parens(node: &LinkedNode) -> bool {
+    let mut ancestor = node.parent();
+    while let Some(node) = ancestor {
+        match node.kind() {
+            Parenthesized | Args | Array | Dict | Params | Destructuring => return false,
+            Code | CodeBlock | ContentBlock | Markup | Math | Equation => return true,
+            _ => ancestor = node.parent(),
+        }
+    }
+    true
 }
 
 #[instrument(skip_all)]
@@ -75,7 +136,9 @@ pub(crate) fn format_bin_left_assoc_tight(
             }
             Space => {}
             LineComment => {
-                ctx.push_in(" ", &mut res);
+                let trimmed = res.trim_end_matches(' ').len();
+                res.truncate(trimmed);
+                ctx.push_raw_in(&ctx.comment_gap(), &mut res);
                 ctx.push_raw_in(s, &mut res);
                 ctx.push_in("\n", &mut res);
                 ctx.push_raw_in(&ctx.get_indent(), &mut res);
@@ -0,0 +1,98 @@
+use super::*;
+
+/// formats a field access or a call like [format_default], unless it ends a chain
+/// of at least two accesses, `data.filter(..).map(..)`, that gets too long: then
+/// the chain is broken before each `.` by [format_chain_breaking].
+#[instrument(skip_all, ret)]
+pub(crate) fn format_chain(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    let res = format_default(parent, children, ctx);
+    // `data.filter(..)` in `data.filter(..).map(..)` is broken with its parent.
+    if matches!(parent.parent_kind(), Some(FieldAccess | FuncCall)) && parent.index() == 0 {
+        ctx.laid_out_again
+            .insert((parent.kind(), parent.range()), children.to_vec());
+        return res;
+    }
+    let mut items = vec![];
+    let linked = links(parent, children, ctx, &mut items);
+    if ctx.off
+        || utils::block_type(parent) == Btype::Math
+        || ctx.width_in_place(&res) < ctx.config.max_line_length
+    {
+        return res;
+    }
+    if !linked || items.iter().filter(|(kind, _)| *kind == Dot).count() < 2 {
+        return res;
+    }
+    format_chain_breaking(parent, &items, ctx)
+}
+
+/// breaks before each `.` of the chain, the accesses are indented.
+///
+/// In markup a line break would end the embedded expression, the chain is then
+/// wrapped in parentheses.
+#[instrument(skip_all)]
+fn format_chain_breaking(
+    parent: &LinkedNode,
+    items: &[(SyntaxKind, String)],
+    ctx: &mut Ctx,
+) -> String {
+    ctx.stats.applied("chain_breaking");
+    let mut res = String::new();
+    for (kind, s) in items {
+        match kind {
+            Dot => {
+                ctx.push_raw_in("\n", &mut res);
+                ctx.push_raw_in(&ctx.get_indent(), &mut res);
+                ctx.push_raw_in(s, &mut res);
+            }
+            _ => ctx.push_raw_indent(s, &mut res),
+        }
+    }
+    if in_markup(parent) {
+        res = format!("({res})");
+    }
+    res
+}
+
+/// the formatted links of the chain ending at `node`: `data`, `.`, `filter`,
+/// `(..)`, `.`, `map`, `(..)` for `data.filter(..).map(..)`, from the `children`
+/// of `node` and those its inner accesses left in [Ctx::laid_out_again]. False if
+/// a comment or a line break is in the way.
+fn links(
+    node: &LinkedNode,
+    children: &[String],
+    ctx: &mut Ctx,
+    items: &mut Vec<(SyntaxKind, String)>,
+) -> bool {
+    let mut linked = true;
+    for (s, child) in children.iter().zip(node.children()) {
+        match child.kind() {
+            Space if !child.text().contains('\n') => {}
+            Space | LineComment | BlockComment => linked = false,
+            kind @ (FieldAccess | FuncCall) if child.index() == 0 => {
+                match ctx.laid_out_again.remove(&(kind, child.range())) {
+                    Some(inner) => linked &= links(&child, &inner, ctx, items),
+                    // formatted as written, in a `typstfmt::off` region for instance.
+                    None => items.push((kind, s.clone())),
+                }
+            }
+            kind => items.push((kind, s.clone())),
+        }
+    }
+    linked
+}
+
+/// true if the nearest enclosing block of `node` is markup rather than code.
+fn in_markup(node: &LinkedNode) -> bool {
+    let mut ancestor = node.parent();
+    while let Some(node) = ancestor {
+        match node.kind() {
+            Parenthesized | Args | Array | Dict | Params | Destructuring | Code | CodeBlock => {
+                return false
+            }
+            Markup | ContentBlock => return true,
+            _ => ancestor = node.parent(),
+        }
+    }
+    true
+}
@@ -10,9 +10,18 @@ pub(crate) fn format_code_blocks(
     ctx: &mut Ctx,
 ) -> String {
     let children_contains_lines = children.iter().any(|c| c.contains('\n'));
-    let parent_is_loop = [Some(ForLoop), Some(WhileLoop)].contains(&parent.parent_kind());
+    // short loop bodies stay inline only when inline code blocks are allowed.
+    let parent_is_loop = [Some(ForLoop), Some(WhileLoop)].contains(&parent.parent_kind())
+        && ctx.config.max_inline_code_block_width == 0;
     let code = utils::find_child(parent, &|x| x.kind() == Code).unwrap();
 
+    if ctx.config.unwrap_embedded_blocks {
+        if let Some(expr) = unwrappable(parent, &code, children) {
+            ctx.stats.applied("unwrap_embedded_block");
+            return expr;
+        }
+    }
+
     if parent.children().any(|c| c.kind() == LineComment) {
         return format_code_blocks_breaking(parent, children, ctx);
     }
@@ -21,6 +30,30 @@ pub(crate) fn format_code_blocks(
         return format_code_blocks_tight(parent, children, ctx);
     }
 
+    if ctx.config.expand_blocks {
+        return format_code_blocks_breaking(parent, children, ctx);
+    }
+    let single_statement = code
+        .children()
+        .filter(|c| !matches!(c.kind(), Space | Semicolon))
+        .count()
+        == 1;
+    if ctx.config.collapse_single_statement_blocks && single_statement && !children_contains_lines {
+        let res = format_code_blocks_tight(parent, children, ctx);
+        if ctx.width_in_place(&res) < ctx.config.max_line_length {
+            ctx.stats.applied("collapse_single_statement_blocks");
+            return res;
+        }
+    }
+
+    if ctx.config.mode == Mode::Conservative && !children_contains_lines {
+        return if utils::source_is_multiline(parent) {
+            format_code_blocks_breaking(parent, children, ctx)
+        } else {
+            format_code_blocks_tight(parent, children, ctx)
+        };
+    }
+
     if children_contains_lines || parent_is_loop {
         debug!("format breaking cause: children contains breakline: {children_contains_lines}");
         debug!("or because parent is loop: {parent_is_loop}");
@@ -28,9 +61,11 @@ pub(crate) fn format_code_blocks(
     }
 
     let res = format_code_blocks_tight(parent, children, ctx);
-    let max_line_length = utils::max_line_length(&res);
+    let max_line_length = ctx.width_in_place(&res);
 
-    if max_line_length >= ctx.config.max_line_length {
+    if max_line_length >= ctx.config.max_line_length
+        || (ctx.config.mode == Mode::Minimal && utils::source_is_multiline(parent))
+    {
         debug!(
             "format breaking cause max_line_length ({}) above limit",
             max_line_length
@@ -41,6 +76,34 @@ pub(crate) fn format_code_blocks(
     res
 }
 
+/// the formatted expression of `#{expr}` if `#expr` means the same thing: the
+/// expression is a call, a field access or a variable and what follows in the
+/// markup can't extend it (`#{f}(x)` isn't `#f(x)`) nor be swallowed (`#{a};`).
+fn unwrappable(parent: &LinkedNode, code: &LinkedNode, children: &[String]) -> Option<String> {
+    if parent.parent_kind() != Some(Markup) || parent.prev_sibling_kind() != Some(Hash) {
+        return None;
+    }
+    let mut exprs = code.children().filter(|c| c.kind() != Space);
+    let expr = exprs.next()?;
+    if exprs.next().is_some() || !matches!(expr.kind(), Ident | FuncCall | FieldAccess) {
+        return None;
+    }
+    let markup = parent.parent()?;
+    let next = markup
+        .children()
+        .nth(parent.index() + 1)
+        .and_then(|next| next.get().clone().into_text().chars().next());
+    if next.is_some_and(|c| c.is_alphanumeric() || "_-.([;".contains(c)) {
+        return None;
+    }
+    let index = parent.children().position(|c| c.kind() == Code)?;
+    let formatted = children[index].trim();
+    // `#(a)(b)` would start an embedded parenthesized expression.
+    formatted
+        .starts_with(|c: char| c.is_alphabetic() || c == '_')
+        .then(|| formatted.to_string())
+}
+
 #[instrument(skip_all)]
 pub(crate) fn format_code_blocks_tight(
     parent: &LinkedNode,
@@ -63,10 +126,14 @@ pub(crate) fn format_code_blocks_tight(
                 }
                 debug!("leftbrace formatted not empty!");
                 res.push_str(s);
-                res.push(' ');
+                if ctx.config.space_inside_braces {
+                    res.push(' ');
+                }
             }
             RightBrace => {
-                res.push(' ');
+                if ctx.config.space_inside_braces {
+                    res.push(' ');
+                }
                 res.push_str(s);
             }
             Space => {}
@@ -84,6 +151,7 @@ pub(crate) fn format_code_blocks_breaking(
     children: &[String],
     ctx: &mut Ctx,
 ) -> String {
+    ctx.stats.applied("code_block_breaking");
     let mut res = String::new();
     for (s, node) in children.iter().zip(parent.children()) {
         match node.kind() {
@@ -116,7 +184,8 @@ pub(crate) fn format_code_blocks_breaking(
                         Some(space) if space.kind() == Space && space.text().contains('\n') => {
                             res.push('\n');
                             res.push_str(&ctx.get_indent());
-                            res.push_str(s.trim_start());
+                            // the lines of a block comment turned into line comments.
+                            ctx.push_raw_indent(s.trim_start(), &mut res);
 
                             if !utils::next_is_ignoring(&node, RightBrace, &[Space]) {
                                 ctx.push_in("\n", &mut res);
@@ -125,8 +194,12 @@ pub(crate) fn format_code_blocks_breaking(
                             }
                         }
                         _ => {
-                            res.push(' ');
-                            res.push_str(s);
+                            if node.kind() == LineComment {
+                                res.push_str(&ctx.comment_gap());
+                            } else {
+                                res.push(' ');
+                            }
+                            ctx.push_raw_indent(s, &mut res);
                             if !utils::next_is_ignoring(&node, RightBrace, &[Space]) {
                                 ctx.push_in("\n", &mut res);
                                 ctx.consec_new_line = 2;
@@ -150,5 +223,121 @@ pub(crate) fn format_code_blocks_breaking(
         }
     }
     ctx.lost_context();
+    if ctx.config.trim_block_blank_lines && !utils::contains_directive(parent) {
+        return utils::trim_blank_edge_lines(&res, '{', '}');
+    }
+    res
+}
+
+/// formats the statements inside a code block.
+/// - removes a redundant semicolon ending the block.
+/// - puts each statement on its own line, a semicolon separating statements
+/// is followed by a newline (or a single space before a comment).
+/// - unless the block was written on one line and stays under
+/// `max_inline_code_block_width`, then statements are separated by `; `.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_code(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    let budget = ctx.config.max_inline_code_block_width;
+    let written_inline = !utils::source_is_multiline(parent)
+        && !children.iter().any(|c| c.contains('\n'))
+        && !parent.children().any(|c| c.kind() == LineComment);
+    if budget > 0 && written_inline {
+        let res = format_code_statements(parent, children, ctx, true);
+        // the braces and the spaces around the code.
+        if utils::max_line_length(&res) + 4 <= budget {
+            return res;
+        }
+    }
+    let res = format_code_statements(parent, children, ctx, false);
+    if align::has_marker(parent) {
+        return align::align_let_runs(parent, &res, ctx);
+    }
+    res
+}
+
+fn format_code_statements(
+    parent: &LinkedNode,
+    children: &[String],
+    ctx: &mut Ctx,
+    one_line: bool,
+) -> String {
+    let mut res = String::new();
+    let mut skip_space = false;
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            _ if ctx.off => res.push_str(&deep_no_format(&node)),
+            LineComment | BlockComment => {
+                let buf = format_comment_handling_disable(&node, &[], ctx);
+                ctx.push_raw_in(&buf, &mut res);
+            }
+            Semicolon
+                if get_next_ignoring(&node, &[Space, LineComment, BlockComment]).is_none() => {}
+            Semicolon => {
+                let space =
+                    utils::next_sibling_or_trivia(&node).filter(|next| next.kind() == Space);
+                let newline_follows = space.as_ref().is_some_and(|x| x.text().contains('\n'));
+                let comment_follows = next_is_ignoring(&node, LineComment, &[Space])
+                    || next_is_ignoring(&node, BlockComment, &[Space]);
+                // the line break separates the statements already.
+                let redundant =
+                    ctx.config.remove_line_end_semicolons && !one_line && !comment_follows;
+                if redundant {
+                    ctx.stats.applied("remove_line_end_semicolons");
+                } else {
+                    ctx.push_raw_in(s, &mut res);
+                }
+                if comment_follows || one_line {
+                    if space.is_none() {
+                        ctx.push_raw_in(" ", &mut res);
+                    }
+                } else if !newline_follows {
+                    // one statement per line.
+                    ctx.push_raw_in("\n", &mut res);
+                    skip_space = space.is_some();
+                }
+            }
+            Space if skip_space => skip_space = false,
+            // no space before a semicolon.
+            Space
+                if !s.contains('\n')
+                    && utils::next_sibling_or_trivia(&node)
+                        .is_some_and(|n| n.kind() == Semicolon) => {}
+            _ => ctx.push_raw_in(s, &mut res),
+        }
+    }
     res
 }
+
+/// formats `return expr`, if the line is too long the expression is put on its own
+/// indented line, wrapped in parentheses when it couldn't be broken otherwise.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_return(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    let mut res = String::new();
+    let mut expr = None;
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            _ if ctx.off => res.push_str(&deep_no_format(&node)),
+            Return => ctx.push_raw_in(s, &mut res),
+            Space => {}
+            _ => {
+                ctx.push_raw_in(" ", &mut res);
+                ctx.push_raw_in(s, &mut res);
+                expr = Some((s, node.kind()));
+            }
+        }
+    }
+
+    match expr {
+        Some((s, Binary | Unary | FieldAccess))
+            if !ctx.off && utils::max_line_length(&res) >= ctx.config.max_line_length =>
+        {
+            let mut res = String::new();
+            ctx.push_raw_in("return (\n", &mut res);
+            ctx.push_raw_in(&ctx.get_indent(), &mut res);
+            ctx.push_raw_indent(s, &mut res);
+            ctx.push_raw_in("\n)", &mut res);
+            res
+        }
+        _ => res,
+    }
+}
@@ -1,34 +1,985 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
 use serde::Deserialize;
 use serde::Serialize;
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(default)]
-#[serde(deny_unknown_fields)]
-pub struct Config {
-    pub indent_space: usize,
-    pub max_line_length: usize,
-    /// If enabled, when breaking arguments, it will try to keep more on one line.
-    pub experimental_args_breaking_consecutive: bool,
-    pub line_wrap: bool,
-}
-
-impl Default for Config {
-    fn default() -> Self {
+use tracing::warn;
+
+use crate::{editorconfig, Names, VersionReq};
+
+/// Describes a key of the [Config], generated from the field declaration.
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigOption {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub ty: &'static str,
+    pub allowed: &'static str,
+    /// The JSON schema of the values, see [Config::json_schema].
+    pub schema: &'static str,
+}
+
+/// Types that can be used as a [Config] value.
+pub(crate) trait ConfigValue {
+    /// A human readable description of what is accepted.
+    const ALLOWED: &'static str;
+    /// The JSON schema of the accepted values.
+    const SCHEMA: &'static str;
+}
+
+impl ConfigValue for bool {
+    const ALLOWED: &'static str = "true, false";
+    const SCHEMA: &'static str = r#"{"type": "boolean"}"#;
+}
+
+impl ConfigValue for usize {
+    const ALLOWED: &'static str = "any non-negative integer";
+    const SCHEMA: &'static str = r#"{"type": "integer", "minimum": 0}"#;
+}
+
+impl<T: ConfigValue> ConfigValue for Option<T> {
+    const ALLOWED: &'static str = T::ALLOWED;
+    const SCHEMA: &'static str = T::SCHEMA;
+}
+
+impl ConfigValue for Names {
+    const ALLOWED: &'static str = "a list of names like [\"pagebreak\", \"v\"]";
+    const SCHEMA: &'static str = r#"{"type": "array", "items": {"type": "string"}}"#;
+}
+
+impl ConfigValue for VersionReq {
+    const ALLOWED: &'static str =
+        "a version requirement like \"^0.3\", \"~0.2.9\", \"=0.3.0\" or \">=0.3\"";
+    const SCHEMA: &'static str = r#"{"type": "string"}"#;
+}
+
+impl ConfigValue for VersionMismatch {
+    const ALLOWED: &'static str = "\"error\", \"warn\"";
+    const SCHEMA: &'static str = r#"{"enum": ["error", "warn"]}"#;
+}
+
+impl ConfigValue for IndentStyle {
+    const ALLOWED: &'static str = "\"spaces\", \"tabs\"";
+    const SCHEMA: &'static str = r#"{"enum": ["spaces", "tabs"]}"#;
+}
+
+impl ConfigValue for LineEnding {
+    const ALLOWED: &'static str = "\"auto\", \"lf\", \"crlf\"";
+    const SCHEMA: &'static str = r#"{"enum": ["auto", "lf", "crlf"]}"#;
+}
+
+impl ConfigValue for OnError {
+    const ALLOWED: &'static str = "\"format-valid-regions\", \"passthrough\", \"abort\"";
+    const SCHEMA: &'static str = r#"{"enum": ["format-valid-regions", "passthrough", "abort"]}"#;
+}
+
+impl ConfigValue for Wrap {
+    const ALLOWED: &'static str = "true, false, \"width\", \"semantic\"";
+    const SCHEMA: &'static str = r#"{"enum": [true, false, "width", "semantic"]}"#;
+}
+
+impl ConfigValue for TypstVersion {
+    const ALLOWED: &'static str = "\"0.10\", \"0.11\"";
+    const SCHEMA: &'static str = r#"{"enum": ["0.10", "0.11"]}"#;
+}
+
+impl ConfigValue for Mode {
+    const ALLOWED: &'static str = "\"conservative\", \"canonical\", \"minimal\"";
+    const SCHEMA: &'static str = r#"{"enum": ["conservative", "canonical", "minimal"]}"#;
+}
+
+/// How much freedom the formatter takes with the layout of the source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Only normalize whitespace, line breaks chosen by the author are kept.
+    Conservative,
+    /// Fully re-layout the code.
+    #[default]
+    Canonical,
+    /// Like canonical but when the author's layout is acceptable it is kept: calls
+    /// and blocks stay broken if they were and text keeps its line breaks when
+    /// under the max line length. Enabling the formatter then changes fewer lines.
+    Minimal,
+}
+
+/// What one level of indentation is made of.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndentStyle {
+    /// `indent_space` spaces.
+    #[default]
+    Spaces,
+    /// A tab, counted as `indent_space` columns.
+    Tabs,
+}
+
+/// The line endings of the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    /// The ending of most lines of the input.
+    #[default]
+    Auto,
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    Crlf,
+}
+
+/// What is done with a document containing syntax errors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnError {
+    /// The constructs containing errors are copied as written, the rest is
+    /// formatted.
+    #[default]
+    FormatValidRegions,
+    /// The document is returned as it is.
+    Passthrough,
+    /// [crate::try_format] returns the syntax errors, the other functions
+    /// return the document as it is.
+    Abort,
+}
+
+/// Where the text of markup is broken, see [Config::line_wrap]. Written `true`
+/// for [Wrap::Width] and `false` for [Wrap::Off], the values `line_wrap` had
+/// before it could be semantic.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Wrap {
+    /// Not broken, the lines of the author are kept.
+    Off,
+    /// As late as possible before `max_line_length`.
+    #[default]
+    Width,
+    /// After each sentence, one sentence per line whatever its length.
+    Semantic,
+}
+
+impl Serialize for Wrap {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Wrap::Off => serializer.serialize_bool(false),
+            Wrap::Width => serializer.serialize_bool(true),
+            Wrap::Semantic => serializer.serialize_str("semantic"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Wrap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Bool(bool),
+            Name(String),
+        }
+        match Value::deserialize(deserializer)? {
+            Value::Bool(true) => Ok(Wrap::Width),
+            Value::Bool(false) => Ok(Wrap::Off),
+            Value::Name(name) => match name.as_str() {
+                "width" => Ok(Wrap::Width),
+                "semantic" => Ok(Wrap::Semantic),
+                _ => Err(serde::de::Error::custom(format!(
+                    "unknown line_wrap {name:?}, expected one of: {}",
+                    Wrap::ALLOWED
+                ))),
+            },
+        }
+    }
+}
+
+/// The version of Typst a project compiles with, see [Config::typst_version].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TypstVersion {
+    #[default]
+    #[serde(rename = "0.10")]
+    V0_10,
+    /// The one adding `context` expressions, typstfmt doesn't parse them yet.
+    #[serde(rename = "0.11")]
+    V0_11,
+}
+
+impl TypstVersion {
+    /// The version of the syntax typstfmt parses.
+    pub const PARSED: Self = Self::V0_10;
+
+    /// added to the syntax errors of a document written for this version, the
+    /// errors may be newer syntax. Empty up to [Self::PARSED].
+    pub(crate) fn syntax_hint(self) -> String {
+        if self <= Self::PARSED {
+            return String::new();
+        }
+        format!(
+            " (typstfmt parses the syntax of typst {}, not of {self})",
+            Self::PARSED
+        )
+    }
+}
+
+impl std::fmt::Display for TypstVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::V0_10 => "0.10",
+            Self::V0_11 => "0.11",
+        })
+    }
+}
+
+/// What the command line does when its version doesn't match `required_version`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionMismatch {
+    /// Refuse to run.
+    #[default]
+    Error,
+    /// Print a warning and format anyway.
+    Warn,
+}
+
+/// Declares the config struct, its default and the [ConfigOption] table from a
+/// single definition so the documentation can't go stale.
+macro_rules! config {
+    (
+        $(#[$($attr:tt)*])*
+        pub struct $name:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                pub $field:ident: $ty:ty = $default:expr,
+            )*
+        }
+    ) => {
+        $(#[$($attr)*])*
+        pub struct $name {
+            $(
+                $(#[doc = $doc])*
+                pub $field: $ty,
+            )*
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self {
+                    $($field: $default,)*
+                }
+            }
+        }
+
+        impl $name {
+            /// All the keys accepted in a config file, in declaration order.
+            pub const OPTIONS: &'static [ConfigOption] = &[
+                $(ConfigOption {
+                    name: stringify!($field),
+                    description: concat!($($doc, "\n",)*),
+                    ty: stringify!($ty),
+                    allowed: <$ty as ConfigValue>::ALLOWED,
+                    schema: <$ty as ConfigValue>::SCHEMA,
+                },)*
+            ];
+        }
+    };
+}
+
+config! {
+    #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+    #[serde(default)]
+    #[serde(deny_unknown_fields)]
+    pub struct Config {
+        /// Number of spaces used for one level of indentation.
+        // this being strictly > to 1 is assumed.
+        pub indent_space: usize = 2,
+        /// Indent with `spaces` or with `tabs`. Tabs are measured as `indent_space`
+        /// columns, spaces aligning a line after its indentation are kept.
+        pub indent_style: IndentStyle = IndentStyle::Spaces,
+        /// The line endings written: `lf`, `crlf`, or `auto` for the ones most
+        /// lines of the file end with.
+        pub line_ending: LineEnding = LineEnding::Auto,
+        /// Lines longer than this will be broken when possible.
+        pub max_line_length: usize = 80,
+        /// If enabled, when breaking arguments, it will try to keep more on one line.
+        pub experimental_args_breaking_consecutive: bool = false,
+        /// Wrap the text of markup: `true` (or `width`) breaks it as late as possible
+        /// to stay under `max_line_length`, `semantic` puts one sentence per line,
+        /// breaking after the `.`, `!` and `?` followed by a space, which keeps the
+        /// diffs of prose small. `false` keeps the lines as they are.
+        pub line_wrap: Wrap = Wrap::Width,
+        /// `conservative` only normalizes whitespace and keeps the author's line
+        /// breaks, `canonical` fully re-lays out the code, `minimal` only re-lays out
+        /// what goes over the max line length.
+        pub mode: Mode = Mode::Canonical,
+        /// A code block with several statements written on one line may stay on
+        /// one line if it is not wider than this, 0 always puts them on separate lines.
+        /// The body of a loop written on one line stays on it too.
+        pub max_inline_code_block_width: usize = 0,
+        /// A content block written on one line and wider than this is broken, its
+        /// content on its own lines, if it has spaces around its content. 0 never
+        /// breaks them for their width, content blocks holding headings or lists are
+        /// always broken.
+        pub max_inline_content_block_width: usize = 0,
+        /// A call with more arguments than this is broken, one argument per line,
+        /// even if it fits on the line. 0 breaks calls for their width only.
+        pub max_args_inline: usize = 0,
+        /// A dictionary with more entries than this is broken, one entry per line.
+        /// 0 breaks dictionaries for their width only.
+        pub max_dict_entries_inline: usize = 0,
+        /// An array with more items than this is broken, one item per line. 0
+        /// breaks arrays for their width only.
+        pub max_array_items_inline: usize = 0,
+        /// Put code blocks holding a single statement and content blocks holding a
+        /// single line on one line when they fit, even in `conservative` and
+        /// `minimal` modes.
+        pub collapse_single_statement_blocks: bool = false,
+        /// Always break code blocks and content blocks with spaces around their
+        /// content, the content on its own lines.
+        pub expand_blocks: bool = false,
+        /// Remove the `;` ending the lines of code blocks, a line break separates the
+        /// statements. The ones of code blocks kept on one line stay.
+        pub remove_line_end_semicolons: bool = false,
+        /// Put spaces inside the parentheses kept on one line, of arguments,
+        /// arrays and parenthesized expressions for instance, `( a, b )`. The
+        /// brackets of content blocks keep the spacing of the author, it is part
+        /// of the text.
+        pub space_inside_parens: bool = false,
+        /// Put spaces inside the braces of code blocks kept on one line, `{ x }`
+        /// rather than `{x}`.
+        pub space_inside_braces: bool = true,
+        /// Put a space before the colon of named arguments and dictionary entries,
+        /// `(key : value)`.
+        pub space_before_colon: bool = false,
+        /// Put a space after the colon of named arguments and dictionary entries,
+        /// `(key: value)` rather than `(key:value)`.
+        pub space_after_colon: bool = true,
+        /// Turn `//comment` into `// comment`, directives like `//typstfmt: off` are left as is.
+        pub space_after_line_comment: bool = false,
+        /// Rewrite `/* ... */` comments ending a line into `//` comments.
+        pub block_comments_to_line_comments: bool = false,
+        /// Number of spaces between code and a `//` comment ending its line, at least 1.
+        pub trailing_comment_spaces: usize = 1,
+        /// Sort the entries of dictionaries by key, comments move with their entry.
+        pub sort_dict_keys: bool = false,
+        /// Sort the named arguments of calls and set rules by name, positional
+        /// arguments keep their order and come first.
+        pub sort_named_args: bool = false,
+        /// Arguments, arrays and dictionaries stay on one line or broken like the author
+        /// wrote them, only their spacing and indentation is normalized.
+        pub keep_collection_layout: bool = false,
+        /// The versions of typstfmt allowed to format the project, so contributors
+        /// with different versions don't fight each other in diffs.
+        pub required_version: Option<VersionReq> = None,
+        /// Whether a version not matching `required_version` is an error or a warning.
+        pub version_mismatch: VersionMismatch = VersionMismatch::Error,
+        /// The version of Typst the project compiles with. The syntax of Typst 0.10
+        /// is parsed, with a later version the syntax errors say that they may be
+        /// newer syntax, the constructs holding them are still copied as written.
+        pub typst_version: TypstVersion = TypstVersion::V0_10,
+        /// What is done with a document containing syntax errors:
+        /// `format-valid-regions` copies the constructs containing them as written,
+        /// `passthrough` leaves the document as it is, `abort` too but the command line
+        /// reports the errors and fails.
+        pub on_error: OnError = OnError::FormatValidRegions,
+        /// Write `#{emph[x]}` as `#emph[x]` when a code block in markup holds a single
+        /// call, field access or variable and it means the same thing.
+        pub unwrap_embedded_blocks: bool = false,
+        /// Blocks and collections nested deeper than this are copied as they are,
+        /// for generated code too deeply nested to be formatted quickly. 0 is no limit.
+        pub max_format_depth: usize = 0,
+        /// Remove the blank lines at the start and the end of `{...}` and `[...]`
+        /// blocks written on several lines, the `compact` style does.
+        pub trim_block_blank_lines: bool = false,
+        /// The number of blank lines kept in a row, more are removed.
+        pub max_blank_lines: usize = 1,
+        /// Put exactly one blank line before the headings of documents and content
+        /// blocks.
+        pub blank_line_before_headings: bool = false,
+        /// Put exactly one blank line after the headings of documents and content
+        /// blocks.
+        pub blank_line_after_headings: bool = false,
+        /// Put exactly one blank line after a group of `#let`, `#set` and `#show`
+        /// lines in markup, before the content following them.
+        pub blank_line_after_rules: bool = false,
+        /// Write the `\u{..}` escapes of visible characters in strings as the
+        /// characters themselves, `"caf\u{e9}"` as `"café"`, and the ones with a
+        /// short escape with it, `\u{a}` as `\n`.
+        pub normalize_string_escapes: bool = false,
+        /// Calls in markup put on their own line, separated from the surrounding text,
+        /// where they are only separated by a space.
+        pub own_line_calls: Names = Names::from_static(&["pagebreak", "colbreak", "v", "line"]),
+        /// Calls to these functions keep their arguments as written, `cetz.canvas`
+        /// matches the function of a module or a method.
+        pub verbatim_args_calls: Names = Names::from_static(&[]),
+        /// Calls to these functions are always broken, one argument per line.
+        pub always_break_calls: Names = Names::from_static(&[]),
+        /// Calls to these functions keep their arguments on one line.
+        pub never_break_calls: Names = Names::from_static(&[]),
+        /// Globs of the files the command line skips, relative to the directory of
+        /// the config file, `["vendor/**", "*.generated.typ"]` for instance. The
+        /// `.typstfmtignore` files of the project, in the syntax of `.gitignore`, are
+        /// honored too.
+        pub exclude: Names = Names::from_static(&[]),
+        /// Parse the output again and check it has the same syntax tree as the input,
+        /// the input is returned as it is with a warning otherwise. On by default in
+        /// debug builds.
+        pub verify_syntax: bool = cfg!(debug_assertions),
+        /// Put the cells of `table` and `grid` calls with a `columns` argument one row
+        /// per line, padded to the width of their column, like `// fmt: align` does.
+        pub align_table_cells: bool = false,
+        /// Put the entries of broken dictionaries and arguments with several named
+        /// ones one per line, the values aligned after the longest name.
+        pub align_named_values: bool = false,
+        /// Keep arguments and arrays aligned in columns by hand where they are, the
+        /// rows of `mat(..)` or of a data array for instance, rather than collapsing
+        /// the spaces.
+        pub preserve_alignment: bool = false,
+        /// Pad the lines of block equations so their `&` alignment points line up,
+        /// like the aligned environments of LaTeX.
+        pub align_math_points: bool = true,
+        /// A comma after the last element of arguments, parameters, arrays and
+        /// dictionaries keeps them broken with one element per line, without one they
+        /// are put on one line when they fit.
+        pub magic_trailing_comma: bool = false,
+        /// Sort the `#import` lines at the top of a document by source, merge the ones
+        /// of the same file and sort the imported items.
+        pub sort_imports: bool = false,
+    }
+}
+
+/// Other names of the rules given to [Config::set_rule], with the key they set.
+/// `line_wrap` isn't a boolean but takes `true` and `false`.
+const RULE_ALIASES: &[(&str, &str)] = &[("markup-reflow", "line_wrap"), ("line-wrap", "line_wrap")];
+
+/// The options [Config::fingerprint] leaves out, they check the input or choose
+/// the files but don't change the output.
+const NOT_IN_FINGERPRINT: &[&str] = &[
+    "required_version",
+    "version_mismatch",
+    "typst_version",
+    "on_error",
+    "exclude",
+    "verify_syntax",
+];
+
+/// Name of the config file looked for in a directory or a package given to `extends`.
+pub(crate) const CONFIG_FILE_NAME: &str = "typstfmt.toml";
+
+/// Where a style given to `extends` lives, `@namespace/name:version` is a typst
+/// package, anything else a path relative to `dir`.
+fn resolve_extends(spec: &str, dir: &Path) -> Result<PathBuf, String> {
+    let path = match spec.strip_prefix('@') {
+        Some(package) => {
+            let (namespace, rest) = package
+                .split_once('/')
+                .ok_or_else(|| format!("invalid package {spec:?} in extends"))?;
+            let (name, version) = rest
+                .split_once(':')
+                .ok_or_else(|| format!("package {spec:?} in extends has no version"))?;
+            package_dirs()
+                .into_iter()
+                .map(|root| root.join(namespace).join(name).join(version))
+                .find(|dir| dir.is_dir())
+                .ok_or_else(|| format!("package {spec:?} in extends is not installed"))?
+        }
+        None => dir.join(spec),
+    };
+    Ok(if path.is_dir() {
+        path.join(CONFIG_FILE_NAME)
+    } else {
+        path
+    })
+}
+
+/// What the config of a file depends on besides the files of its directories:
+/// the environment and the global config. [Config::resolve_for] takes them from
+/// the process, the tests give their own.
+pub(crate) struct Environment {
+    pub(crate) vars: Vec<(String, String)>,
+    /// the global config file, it may not exist.
+    pub(crate) global: Option<PathBuf>,
+    /// no config file is looked for above this directory, if any.
+    pub(crate) ceiling: Option<PathBuf>,
+}
+
+impl Environment {
+    pub(crate) fn of_process() -> Self {
         Self {
-            // this being strictly > to 1 is assumed.
-            indent_space: 2,
-            max_line_length: 80,
-            line_wrap: true,
-            experimental_args_breaking_consecutive: false,
+            vars: std::env::vars().collect(),
+            global: confy::get_configuration_file_path("typstfmt", "typstfmt").ok(),
+            ceiling: None,
+        }
+    }
+}
+
+/// the `typstfmt.toml` (or `.typstfmt.toml`) of `dir` and the directories above
+/// it up to `ceiling`, from the outermost one.
+fn config_files(dir: &Path, ceiling: Option<&Path>) -> Vec<PathBuf> {
+    let dot_file_name = format!(".{CONFIG_FILE_NAME}");
+    let mut files = vec![];
+    let ancestors = dir.ancestors();
+    for ancestor in ancestors.take_while(|a| ceiling.map_or(true, |c| a.starts_with(c))) {
+        let file = ancestor.join(CONFIG_FILE_NAME);
+        let dot_file = ancestor.join(&dot_file_name);
+        match (file.is_file(), dot_file.is_file()) {
+            (true, true) => {
+                warn!("both {file:?} and {dot_file:?} are present, using {file:?}");
+                files.push(file);
+            }
+            (true, false) => files.push(file),
+            (false, true) => files.push(dot_file),
+            (false, false) => {}
+        }
+    }
+    files.reverse();
+    files
+}
+
+/// The configs applying to the file or directory at `path`, from the weakest to
+/// the strongest, each described by where it comes from:
+/// - the global config, only if no config file is found in the directories above `path`,
+/// - the `.editorconfig` files,
+/// - the `typstfmt.toml` (or `.typstfmt.toml`) of each directory above `path`,
+///   from the outermost one to the nearest one.
+pub(crate) fn layers(path: &Path, env: &Environment) -> Result<Vec<(String, toml::Table)>, String> {
+    let path = std::env::current_dir()
+        .map_err(|err| err.to_string())?
+        .join(path);
+    let dir = if path.is_dir() {
+        path.as_path()
+    } else {
+        path.parent().unwrap_or(&path)
+    };
+    let files = config_files(dir, env.ceiling.as_deref());
+
+    let mut res = vec![];
+    if files.is_empty() {
+        if let Some(global) = env.global.as_deref().filter(|g| g.is_file()) {
+            res.push(load_file(global)?);
         }
     }
+    // for a directory, the sections matching any typst file apply.
+    let typst_file = if path.is_dir() {
+        dir.join("main.typ")
+    } else {
+        path.clone()
+    };
+    let from_editorconfig = editorconfig::settings(&typst_file);
+    if !from_editorconfig.is_empty() {
+        res.push((".editorconfig".to_string(), from_editorconfig));
+    }
+    for file in &files {
+        res.push(load_file(file)?);
+    }
+    Ok(res)
+}
+
+/// the keys of the config file at `file`, following `extends` and `style`, with
+/// where they come from.
+fn load_file(file: &Path) -> Result<(String, toml::Table), String> {
+    let source = file.display().to_string();
+    let s = std::fs::read_to_string(file).map_err(|err| format!("{source}: {err}"))?;
+    let table = load_table(&s, file.parent().unwrap_or(Path::new(".")), &mut vec![])
+        .map_err(|err| format!("{source}: {err}"))?;
+    Ok((source, table))
+}
+
+/// The directories where typst stores packages, local ones first.
+pub fn package_dirs() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let (data, cache) = if cfg!(target_os = "windows") {
+        let local = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+        (std::env::var_os("APPDATA").map(PathBuf::from), local)
+    } else if cfg!(target_os = "macos") {
+        let library = home.map(|home| home.join("Library"));
+        (
+            library.as_ref().map(|l| l.join("Application Support")),
+            library.map(|l| l.join("Caches")),
+        )
+    } else {
+        let var = |name| std::env::var_os(name).map(PathBuf::from);
+        (
+            var("XDG_DATA_HOME").or_else(|| home.as_ref().map(|h| h.join(".local/share"))),
+            var("XDG_CACHE_HOME").or_else(|| home.map(|h| h.join(".cache"))),
+        )
+    };
+    [data, cache]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join("typst").join("packages"))
+        .collect()
+}
+
+/// The presets a config file selects with `style`, the keys they set.
+const PRESETS: &[(&str, &str)] = &[
+    ("default", ""),
+    (
+        "compact",
+        "max_line_length = 100
+max_inline_code_block_width = 40
+experimental_args_breaking_consecutive = true
+collapse_single_statement_blocks = true
+trim_block_blank_lines = true
+max_blank_lines = 1",
+    ),
+    (
+        "expanded",
+        "expand_blocks = true
+magic_trailing_comma = true
+blank_line_before_headings = true
+blank_line_after_headings = true
+blank_line_after_rules = true
+max_blank_lines = 2",
+    ),
+];
+
+/// the keys set by the preset `name`.
+fn preset_table(name: &str) -> Result<toml::Table, String> {
+    let (_, keys) = PRESETS.iter().find(|(n, _)| *n == name).ok_or_else(|| {
+        let names: Vec<_> = PRESETS.iter().map(|(n, _)| *n).collect();
+        format!(
+            "unknown style {name:?}, expected one of: {}",
+            names.join(", ")
+        )
+    })?;
+    Ok(toml::from_str(keys).unwrap())
+}
+
+/// Parses a config, following `extends` and `style`, the keys of `s` override the
+/// ones of the preset, that override the inherited ones.
+///
+/// `seen` holds the files already included to report cycles.
+fn load_table(s: &str, dir: &Path, seen: &mut Vec<PathBuf>) -> Result<toml::Table, String> {
+    let mut table: toml::Table = toml::from_str(s).map_err(|e| e.message().to_string())?;
+    if let Some(style) = table.remove("style") {
+        let name = style
+            .as_str()
+            .ok_or_else(|| "style must be a string".to_string())?;
+        let mut preset = preset_table(name)?;
+        preset.extend(table);
+        table = preset;
+    }
+    let Some(extends) = table.remove("extends") else {
+        return Ok(table);
+    };
+    let spec = extends
+        .as_str()
+        .ok_or_else(|| "extends must be a string".to_string())?;
+    let path = resolve_extends(spec, dir)?;
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+    if seen.contains(&canonical) {
+        return Err(format!("{path:?} extends itself"));
+    }
+    seen.push(canonical);
+    let base = std::fs::read_to_string(&path)
+        .map_err(|err| format!("couldn't read {path:?} from extends: {err}"))?;
+    let mut res = load_table(&base, path.parent().unwrap_or(dir), seen)
+        .map_err(|err| format!("in {path:?}: {err}"))?;
+    res.extend(table);
+    Ok(res)
 }
 
+// the config is shared between threads by `format_many`.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Config>();
+};
+
 impl Config {
+    /// Parses a config, a path given to `extends` is relative to the current directory.
     pub fn from_toml(s: &str) -> Result<Self, String> {
-        toml::from_str(s).map_err(|e| e.message().to_string())
+        Self::from_toml_in(s, Path::new("."))
+    }
+
+    /// Reads a config file, a path given to `extends` is relative to the file.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let s = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::from_toml_in(s.as_str(), path.parent().unwrap_or(Path::new(".")))
+    }
+
+    fn from_toml_in(s: &str, dir: &Path) -> Result<Self, String> {
+        Self::from_table(load_table(s, dir, &mut vec![])?)
+    }
+
+    fn from_table(table: toml::Table) -> Result<Self, String> {
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.message().to_string())
+    }
+
+    /// The config used to format the file at `path`, see [layers] for the files
+    /// taken into account, the environment overrides them.
+    ///
+    /// This is what the command line does for each file, editors should use it too
+    /// so they format like it.
+    pub fn resolve_for(path: &Path) -> Result<Self, String> {
+        Self::resolve_in(path, &Environment::of_process())
+    }
+
+    /// [resolve_for](Self::resolve_for) in the environment `env`.
+    pub(crate) fn resolve_in(path: &Path, env: &Environment) -> Result<Self, String> {
+        Self::resolve_with_sources(path, env).map(|(config, _)| config)
+    }
+
+    /// The config of the file or directory at `path` from the config files
+    /// [discover](Self::discover) finds, the nearer ones override the outer ones,
+    /// that override the defaults. Unlike [resolve_for](Self::resolve_for), the
+    /// `.editorconfig` files, the global config and the environment are ignored.
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let mut table = toml::Table::new();
+        for file in Self::discover(path) {
+            let (source, layer) = load_file(&file)?;
+            Self::from_table(layer.clone()).map_err(|err| format!("{source}: {err}"))?;
+            table.extend(layer);
+        }
+        Self::from_table(table)
+    }
+
+    /// The config files merged by [resolve_for](Self::resolve_for) for the file or
+    /// directory at `path`, from the outermost one, for instance to reload the
+    /// config when one of them changes. The inherited files are not included.
+    pub fn discover(path: &Path) -> Vec<PathBuf> {
+        Self::discover_in(path, &Environment::of_process())
+    }
+
+    /// [discover](Self::discover) in the environment `env`.
+    pub(crate) fn discover_in(path: &Path, env: &Environment) -> Vec<PathBuf> {
+        let path = std::env::current_dir().unwrap_or_default().join(path);
+        let dir = if path.is_dir() {
+            path.as_path()
+        } else {
+            path.parent().unwrap_or(&path)
+        };
+        config_files(dir, env.ceiling.as_deref())
+    }
+
+    /// The directory the `exclude` globs of the config of `path` are relative to:
+    /// the one of the config file setting them, the current directory if they come
+    /// from the global config or the environment.
+    pub fn exclude_dir(path: &Path) -> Result<PathBuf, String> {
+        Self::exclude_dir_in(path, &Environment::of_process())
+    }
+
+    /// [exclude_dir](Self::exclude_dir) in the environment `env`.
+    pub(crate) fn exclude_dir_in(path: &Path, env: &Environment) -> Result<PathBuf, String> {
+        Self::exclude_in(path, env).map(|(_, dir)| dir)
+    }
+
+    /// The `exclude` globs of the config of `path` in the environment `env`, with
+    /// the directory they are relative to, see [exclude_dir](Self::exclude_dir).
+    pub(crate) fn exclude_in(path: &Path, env: &Environment) -> Result<(Names, PathBuf), String> {
+        let cwd = std::env::current_dir().map_err(|err| err.to_string())?;
+        let (config, sources) = Self::resolve_with_sources(path, env)?;
+        let Some(source) = sources.get("exclude") else {
+            return Ok((config.exclude, cwd));
+        };
+        let file = Self::discover_in(path, env)
+            .into_iter()
+            .find(|file| file.display().to_string() == *source);
+        let dir = match file.as_deref().and_then(Path::parent) {
+            Some(dir) => dir.to_path_buf(),
+            None => cwd,
+        };
+        Ok((config.exclude, dir))
+    }
+
+    /// The config [resolve_for](Self::resolve_for) gives, as TOML, each key annotated
+    /// with where its value comes from, to debug options that seem ignored.
+    pub fn dump_for(path: &Path) -> Result<String, String> {
+        Self::dump_in(path, &Environment::of_process())
+    }
+
+    /// [dump_for](Self::dump_for) in the environment `env`.
+    pub(crate) fn dump_in(path: &Path, env: &Environment) -> Result<String, String> {
+        let (config, sources) = Self::resolve_with_sources(path, env)?;
+        let toml::Value::Table(values) = toml::Value::try_from(config).unwrap() else {
+            unreachable!("the config is a table");
+        };
+        let mut res = String::new();
+        for option in Self::OPTIONS {
+            let source = sources.get(option.name).map_or("default", String::as_str);
+            match values.get(option.name) {
+                Some(value) => res.push_str(&format!("{} = {value} # {source}\n", option.name)),
+                None => res.push_str(&format!("# {} is not set # {source}\n", option.name)),
+            }
+        }
+        Ok(res)
+    }
+
+    /// the resolved config and the source of each key that isn't the default.
+    fn resolve_with_sources(
+        path: &Path,
+        env: &Environment,
+    ) -> Result<(Self, HashMap<String, String>), String> {
+        let mut table = toml::Table::new();
+        let mut sources = HashMap::new();
+        for (source, layer) in layers(path, env)? {
+            Self::from_table(layer.clone()).map_err(|err| format!("{source}: {err}"))?;
+            for key in layer.keys() {
+                sources.insert(key.clone(), source.clone());
+            }
+            table.extend(layer);
+        }
+        let vars: Vec<_> = env
+            .vars
+            .iter()
+            .filter(|(var, _)| var.starts_with("TYPSTFMT_"))
+            .cloned()
+            .collect();
+        for (var, _) in &vars {
+            let key = var["TYPSTFMT_".len()..].to_lowercase();
+            if Self::OPTIONS.iter().any(|o| o.name == key) {
+                sources.insert(key, format!("environment ({var})"));
+            }
+        }
+        let config = Self::from_table(table)?.with_overrides(vars.into_iter())?;
+        Ok((config, sources))
+    }
+
+    /// Overrides the keys set in the environment: `TYPSTFMT_MAX_LINE_LENGTH=100`
+    /// sets `max_line_length`. Values are TOML, strings may be given without quotes.
+    pub fn with_env(self) -> Result<Self, String> {
+        self.with_overrides(std::env::vars())
+    }
+
+    pub(crate) fn with_overrides(
+        self,
+        vars: impl Iterator<Item = (String, String)>,
+    ) -> Result<Self, String> {
+        let Ok(toml::Value::Table(mut table)) = toml::Value::try_from(self) else {
+            unreachable!("the config is a table");
+        };
+        for (var, value) in vars {
+            let Some(key) = var.strip_prefix("TYPSTFMT_").map(str::to_lowercase) else {
+                continue;
+            };
+            if !Self::OPTIONS.iter().any(|o| o.name == key) {
+                continue;
+            }
+            let value = toml::from_str::<toml::Table>(&format!("value = {value}"))
+                .ok()
+                .and_then(|mut t| t.remove("value"))
+                .unwrap_or(toml::Value::String(value));
+            table.insert(key, value);
+        }
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| format!("in the environment: {}", e.message()))
+    }
+
+    /// The names of the rules that can be enabled or disabled on their own, the
+    /// boolean keys written with dashes (`sort-dict-keys`) and some aliases.
+    pub fn rules() -> Vec<String> {
+        Self::OPTIONS
+            .iter()
+            .filter(|o| o.ty == "bool")
+            .map(|o| o.name.replace('_', "-"))
+            .chain(RULE_ALIASES.iter().map(|(alias, _)| alias.to_string()))
+            .collect()
+    }
+
+    /// Enables or disables the rule `name`, see [rules](Self::rules).
+    pub fn set_rule(self, name: &str, enabled: bool) -> Result<Self, String> {
+        let alias = RULE_ALIASES.iter().find(|(alias, _)| *alias == name);
+        let key = alias.map_or_else(|| name.replace('-', "_"), |(_, key)| key.to_string());
+        if alias.is_none()
+            && !Self::OPTIONS
+                .iter()
+                .any(|o| o.name == key && o.ty == "bool")
+        {
+            return Err(format!(
+                "unknown rule {name:?}, expected one of: {}",
+                Self::rules().join(", ")
+            ));
+        }
+        let Ok(toml::Value::Table(mut table)) = toml::Value::try_from(self) else {
+            unreachable!("the config is a table");
+        };
+        table.insert(key, toml::Value::Boolean(enabled));
+        Self::from_table(table)
     }
 
     pub fn default_toml() -> String {
         toml::to_string_pretty(&Self::default()).unwrap()
     }
+
+    /// A short hash of the values of the config, stable across runs and platforms
+    /// so it can be recorded in files. The options that don't change the output,
+    /// [NOT_IN_FINGERPRINT], are left out.
+    pub fn fingerprint(&self) -> String {
+        let Ok(toml::Value::Table(mut table)) = toml::Value::try_from(self) else {
+            unreachable!("the config is a table");
+        };
+        for key in NOT_IN_FINGERPRINT {
+            table.remove(*key);
+        }
+        // FNV-1a, the hashers of std may change between releases.
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in toml::to_string(&table).unwrap().bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{hash:016x}")
+    }
+
+    /// The config of the preset `name`, `compact`, `default` or `expanded`, that a
+    /// config file selects with `style = "compact"`.
+    pub fn preset(name: &str) -> Option<Self> {
+        Self::from_table(preset_table(name).ok()?).ok()
+    }
+
+    /// A JSON schema of the config files, for editors to validate and complete
+    /// `typstfmt.toml`.
+    pub fn json_schema() -> String {
+        let defaults = serde_json::to_value(Self::default()).unwrap();
+        let mut properties = serde_json::Map::new();
+        properties.insert(
+            "extends".to_string(),
+            serde_json::json!({
+                "description": "A config file, a directory or an installed package `@namespace/name:version` whose config this one overrides.",
+                "type": "string",
+            }),
+        );
+        properties.insert(
+            "style".to_string(),
+            serde_json::json!({
+                "description": "A preset the other keys of this file override.",
+                "enum": PRESETS.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+            }),
+        );
+        for option in Self::OPTIONS {
+            let mut property: serde_json::Map<_, _> = serde_json::from_str(option.schema).unwrap();
+            let description = option
+                .description
+                .lines()
+                .map(str::trim)
+                .collect::<Vec<_>>();
+            property.insert("description".to_string(), description.join(" ").into());
+            if let Some(default) = defaults.get(option.name).filter(|d| !d.is_null()) {
+                property.insert("default".to_string(), default.clone());
+            }
+            properties.insert(option.name.to_string(), property.into());
+        }
+        let schema = serde_json::json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "title": "typstfmt.toml",
+            "type": "object",
+            "additionalProperties": false,
+            "properties": properties,
+        });
+        serde_json::to_string_pretty(&schema).unwrap()
+    }
+
+    /// Describes a config key: its documentation, type, default and allowed values.
+    ///
+    /// Returns `None` if the key doesn't exist.
+    pub fn explain(key: &str) -> Option<String> {
+        let option = Self::OPTIONS.iter().find(|o| o.name == key)?;
+        let default = toml::Value::try_from(Self::default())
+            .ok()
+            .and_then(|v| v.get(key).map(|v| v.to_string()))
+            .unwrap_or_else(|| "none".to_string());
+        let mut res = format!("{}: {}\n", option.name, option.ty);
+        for line in option.description.lines() {
+            res.push_str(&format!("    {}\n", line.trim()));
+        }
+        res.push_str(&format!("    default: {default}\n"));
+        res.push_str(&format!("    allowed values: {}", option.allowed));
+        Some(res)
+    }
 }
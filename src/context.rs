@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use super::*;
 
 #[derive(Default)]
@@ -6,6 +8,24 @@ pub(crate) struct Ctx {
     pub(crate) just_spaced: bool,
     pub(crate) consec_new_line: i32,
     pub(crate) off: bool,
+    /// where the current `typstfmt::off` region started.
+    pub(crate) off_since: Option<usize>,
+    pub(crate) skipped: Vec<std::ops::Range<usize>>,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    pub(crate) stats: Stats,
+    /// the top level constructs already formatted, when formatting incrementally.
+    pub(crate) cache: Option<incremental::Cache>,
+    /// the levels of indentation of the line the node being formatted starts
+    /// on, as far as the source tells.
+    pub(crate) indent_level: usize,
+    /// the column the node being formatted starts at in the output, estimated
+    /// from the source, see [utils::start_column].
+    pub(crate) start_column: usize,
+    /// the formatted children of the nodes their parent may lay out again, the
+    /// operations at the start of a chain of binary operations or of method calls.
+    pub(crate) laid_out_again: HashMap<(SyntaxKind, std::ops::Range<usize>), Vec<String>>,
+    /// true if the time spent on each kind of node goes in [Stats::time].
+    pub(crate) timed: bool,
 }
 
 /// you may push into your own buffer using this to ensure you push considering context
@@ -15,13 +35,14 @@ impl Ctx {
     pub(crate) fn from_config(config: Config) -> Self {
         Self {
             config,
+            timed: utils::timed(),
             ..Default::default()
         }
     }
 
     /// Pushes the string in the result avoiding:
     /// - putting two consecutive spaces.
-    /// - putting more than two consecutive newlines.
+    /// - putting more than `max_blank_lines` blank lines.
     /// - trims the string if it DOES contain a newline.
     /// Won't work for indents.
     #[instrument(skip_all)]
@@ -41,7 +62,7 @@ impl Ctx {
                     }
                 }
                 '\n' => {
-                    if self.consec_new_line <= 1 {
+                    if self.consec_new_line <= self.config.max_blank_lines as i32 {
                         self.consec_new_line += 1;
                         result.push('\n');
                     } else {
@@ -65,17 +86,22 @@ impl Ctx {
 
     /// adds an indentation for each line the input except the first to match the current level of indentation.
     pub(crate) fn push_raw_indent(&mut self, s: &str, result: &mut String) {
-        for (i, s) in s.split_inclusive('\n').enumerate() {
-            if i == 0 {
-                self.push_raw_in(s, result);
-                continue;
-            }
-            let s = if s.ends_with("\n") {
-                format!("{}\n", s.trim_end())
+        let mut lines = s.split_inclusive('\n');
+        let Some(first) = lines.next() else {
+            return;
+        };
+        self.push_raw_in(first, result);
+        // every node is indented again by each of its parents, this is the hot path.
+        let indent = self.get_indent();
+        result.reserve(s.len() - first.len());
+        for line in lines {
+            result.push_str(&indent);
+            if line.ends_with('\n') {
+                result.push_str(line.trim_end());
+                result.push('\n');
             } else {
-                s.to_string()
-            };
-            self.push_raw_in(&format!("{}{}", self.get_indent(), s), result);
+                result.push_str(line);
+            }
         }
     }
 
@@ -103,6 +129,41 @@ impl Ctx {
         self.consec_new_line = 0;
     }
 
+    /// records a region of the input that was left untouched.
+    ///
+    /// The same comment can be handled by several parents, regions are only recorded once.
+    pub(crate) fn push_skipped(&mut self, range: std::ops::Range<usize>) {
+        if !self.skipped.contains(&range) {
+            self.skipped.push(range);
+        }
+    }
+
+    /// records a diagnostic, once even if the node is handled by several parents.
+    pub(crate) fn push_diagnostic(&mut self, diagnostic: Diagnostic) {
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// the spaces between code and a `//` comment ending its line.
+    pub(crate) fn comment_gap(&self) -> String {
+        " ".repeat(self.config.trailing_comment_spaces.max(1))
+    }
+
+    /// the width `s` takes once in place: its first line starts at the column of
+    /// the node being formatted, the others are indented like its line.
+    pub(crate) fn width_in_place(&self, s: &str) -> usize {
+        let indent = self.indent_level * self.config.indent_space;
+        s.split('\n')
+            .enumerate()
+            .map(|(i, line)| {
+                let start = if i == 0 { self.start_column } else { indent };
+                start + utils::width(line)
+            })
+            .max()
+            .unwrap_or(self.start_column)
+    }
+
     /// returns an indent using config to get it's length.
     pub(crate) fn get_indent(&self) -> String {
         " ".repeat(self.config.indent_space)
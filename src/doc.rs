@@ -0,0 +1,167 @@
+use super::*;
+
+/// A layout description in the style of Wadler's "prettier printer": rather than
+/// building a string and measuring it to choose between a tight and a breaking
+/// version, a formatting function describes both at once with groups and lines,
+/// [render] then breaks the groups that don't fit.
+///
+/// Sorted collections, see [crate::sorting], and the chains of binary operations,
+/// see [crate::binary], are built this way; moving the other formatting functions
+/// to it is on the roadmap of the README.
+pub(crate) enum Doc {
+    Text(String),
+    /// a space, a line break in a broken group.
+    Line,
+    /// nothing, a line break in a broken group.
+    SoftLine,
+    /// a line break, even in a group that fits.
+    HardLine,
+    /// only written in a broken group, for trailing commas.
+    IfBreak(String),
+    /// indents the line breaks of its content by one level.
+    Nest(Vec<Doc>),
+    /// put on one line if it fits, otherwise all its lines are broken. The flag
+    /// forces it broken, when it ends with a line comment for instance.
+    Group(Vec<Doc>, bool),
+}
+
+impl Doc {
+    pub(crate) fn text(s: impl Into<String>) -> Doc {
+        Doc::Text(s.into())
+    }
+
+    pub(crate) fn group(docs: Vec<Doc>) -> Doc {
+        Doc::Group(docs, false)
+    }
+
+    pub(crate) fn broken(docs: Vec<Doc>) -> Doc {
+        Doc::Group(docs, true)
+    }
+
+    /// true if a hard line forces the groups containing it to break.
+    fn has_hard_line(&self) -> bool {
+        match self {
+            Doc::HardLine => true,
+            Doc::Nest(docs) | Doc::Group(docs, _) => docs.iter().any(Doc::has_hard_line),
+            _ => false,
+        }
+    }
+}
+
+/// renders `doc` with lines of at most `width` columns when possible, `indent`
+/// being one level of indentation.
+pub(crate) fn render(doc: &Doc, width: usize, indent: &str) -> String {
+    let mut printer = Printer::new(width, indent);
+    printer.print(doc, false);
+    printer.res
+}
+
+/// renders `doc` in place of the node being formatted, measured like
+/// [Ctx::width_in_place]: its lines stay under `max_line_length` when possible.
+/// The flag is true if a group was broken.
+pub(crate) fn render_in_place(doc: &Doc, ctx: &Ctx) -> (String, bool) {
+    let indent = ctx.get_indent();
+    let mut printer = Printer {
+        start_column: ctx.start_column,
+        line_start: ctx.indent_level * ctx.config.indent_space,
+        ..Printer::new(ctx.config.max_line_length.saturating_sub(1), &indent)
+    };
+    printer.print(doc, false);
+    (printer.res, printer.broke)
+}
+
+struct Printer<'a> {
+    res: String,
+    width: usize,
+    indent: &'a str,
+    level: usize,
+    /// the column the first line starts at.
+    start_column: usize,
+    /// the column the other lines start at, before the indentation of [Doc::Nest].
+    line_start: usize,
+    /// true once a group was broken.
+    broke: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn new(width: usize, indent: &'a str) -> Self {
+        Printer {
+            res: String::new(),
+            width,
+            indent,
+            level: 0,
+            start_column: 0,
+            line_start: 0,
+            broke: false,
+        }
+    }
+
+    fn print(&mut self, doc: &Doc, broken: bool) {
+        match doc {
+            Doc::Text(s) if broken => self.push_indented(s),
+            Doc::Text(s) => self.res.push_str(s),
+            Doc::Line if !broken => self.res.push(' '),
+            Doc::SoftLine if !broken => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => self.newline(),
+            Doc::IfBreak(s) if broken => self.res.push_str(s),
+            Doc::IfBreak(_) => {}
+            Doc::Nest(docs) => {
+                self.level += 1;
+                for doc in docs {
+                    self.print(doc, broken);
+                }
+                self.level -= 1;
+            }
+            Doc::Group(docs, force) => {
+                let broken = *force || docs.iter().any(Doc::has_hard_line) || !self.fits(docs);
+                self.broke |= broken;
+                for doc in docs {
+                    self.print(doc, broken);
+                }
+            }
+        }
+    }
+
+    /// true if `docs` fits on the current line when not broken, and its other
+    /// lines (multi-line strings for instance) under the width.
+    fn fits(&self, docs: &[Doc]) -> bool {
+        let mut flat = Printer::new(self.width, self.indent);
+        for doc in docs {
+            flat.print_flat(doc);
+        }
+        let start = if self.res.contains('\n') {
+            self.line_start
+        } else {
+            self.start_column
+        };
+        let column = start + utils::last_line_length(&self.res);
+        let mut lines = flat.res.split('\n');
+        let first = lines.next().unwrap_or_default();
+        column + utils::width(first) <= self.width
+            && lines.all(|l| self.line_start + utils::width(l) <= self.width)
+    }
+
+    fn print_flat(&mut self, doc: &Doc) {
+        match doc {
+            Doc::Nest(docs) | Doc::Group(docs, _) => docs.iter().for_each(|d| self.print_flat(d)),
+            doc => self.print(doc, false),
+        }
+    }
+
+    fn newline(&mut self) {
+        let trimmed = self.res.trim_end_matches(' ').len();
+        self.res.truncate(trimmed);
+        self.res.push('\n');
+        self.res.push_str(&self.indent.repeat(self.level));
+    }
+
+    /// like [Ctx::push_raw_indent], the lines after the first are indented.
+    fn push_indented(&mut self, s: &str) {
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                self.newline();
+            }
+            self.res.push_str(line);
+        }
+    }
+}
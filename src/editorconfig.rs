@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The config keys set by the `.editorconfig` files applying to `path`:
+/// `indent_size` gives `indent_space`, `indent_style` gives `indent_style` and
+/// `max_line_length` gives `max_line_length`.
+///
+/// Files are read from the directory of `path` up to the one marked `root = true`,
+/// the nearer ones and the later sections win.
+pub(crate) fn settings(path: &Path) -> toml::Table {
+    let mut files = vec![];
+    for dir in path.ancestors().skip(1) {
+        let Ok(content) = std::fs::read_to_string(dir.join(".editorconfig")) else {
+            continue;
+        };
+        let root = is_root(&content);
+        files.push((dir, content));
+        if root {
+            break;
+        }
+    }
+
+    let mut properties = HashMap::new();
+    for (dir, content) in files.iter().rev() {
+        let Ok(relative) = path.strip_prefix(dir) else {
+            continue;
+        };
+        let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let mut matching = false;
+        for line in lines(content) {
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                matching = glob_matches(section, &relative);
+            } else if let Some((key, value)) = line.split_once('=').filter(|_| matching) {
+                properties.insert(key.trim().to_lowercase(), value.trim().to_lowercase());
+            }
+        }
+    }
+
+    let mut res = toml::Table::new();
+    let number = |key: &str| {
+        properties
+            .get(key)
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|v| *v > 0)
+    };
+    let tabs = properties.get("indent_style").is_some_and(|s| s == "tab");
+    let indent = match properties.get("indent_size").map(String::as_str) {
+        Some("tab") => number("tab_width"),
+        _ => number("indent_size"),
+    };
+    // a level of indentation with tabs is as wide as a tab.
+    let indent = if tabs {
+        number("tab_width").or(indent)
+    } else {
+        indent
+    };
+    if let Some(indent) = indent {
+        res.insert("indent_space".to_string(), toml::Value::Integer(indent));
+    }
+    let style = match properties.get("indent_style").map(String::as_str) {
+        Some("tab") => Some("tabs"),
+        Some("space") => Some("spaces"),
+        _ => None,
+    };
+    if let Some(style) = style {
+        res.insert(
+            "indent_style".to_string(),
+            toml::Value::String(style.into()),
+        );
+    }
+    if let Some(max) = number("max_line_length") {
+        res.insert("max_line_length".to_string(), toml::Value::Integer(max));
+    }
+    res
+}
+
+/// the meaningful lines, comments and blank lines left out.
+fn lines(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with(['#', ';']))
+}
+
+/// `root = true` in the preamble, before any section, stops the search upward.
+fn is_root(content: &str) -> bool {
+    lines(content)
+        .take_while(|l| !l.starts_with('['))
+        .filter_map(|l| l.split_once('='))
+        .any(|(k, v)| {
+            k.trim().eq_ignore_ascii_case("root") && v.trim().eq_ignore_ascii_case("true")
+        })
+}
+
+/// matches an EditorConfig glob: `*`, `**`, `?`, `[abc]`, `[!abc]` and `{a,b}`.
+/// A glob without a slash matches the file name in any directory.
+pub(crate) fn glob_matches(glob: &str, path: &str) -> bool {
+    let glob = match glob.strip_prefix('/') {
+        Some(glob) => glob.to_string(),
+        None if glob.contains('/') => glob.to_string(),
+        None => format!("**/{glob}"),
+    };
+    let mut re = String::from("^");
+    let mut chars = glob.chars().peekable();
+    let mut braces = 0;
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' if chars.peek() == Some(&'!') => {
+                chars.next();
+                re.push_str("[^");
+            }
+            '[' | ']' => re.push(c),
+            '{' => {
+                braces += 1;
+                re.push_str("(?:");
+            }
+            '}' if braces > 0 => {
+                braces -= 1;
+                re.push(')');
+            }
+            ',' if braces > 0 => re.push('|'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    regex::Regex::new(&re).is_ok_and(|re| re.is_match(path))
+}
@@ -0,0 +1,61 @@
+use super::*;
+
+/// formats `if condition {..} else {..}`, one space around the condition and
+/// `else` on the line of the closing brace.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_conditional(
+    parent: &LinkedNode,
+    children: &[String],
+    ctx: &mut Ctx,
+) -> String {
+    let mut res = String::new();
+    ctx.push_raw_in(parent.text(), &mut res);
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            _ if ctx.off => res.push_str(node.text()),
+            Space => {}
+            If => {
+                ctx.push_raw_in(s, &mut res);
+                ctx.push_raw_in(" ", &mut res);
+            }
+            CodeBlock | ContentBlock => {
+                ctx.push_raw_in(" ", &mut res);
+                ctx.push_raw_in(s, &mut res);
+            }
+            Else => {
+                ctx.push_raw_in(" ", &mut res);
+                ctx.push_raw_in(s, &mut res);
+                if node.next_sibling_kind() == Some(Conditional) {
+                    ctx.push_raw_in(" ", &mut res);
+                }
+            }
+            _ => ctx.push_raw_in(s, &mut res),
+        }
+    }
+    res
+}
+
+/// formats `while condition {..}` and `for pattern in iterable {..}` with one
+/// space between the keywords and the expressions, the body is broken by
+/// [crate::code_blocks::format_code_blocks].
+#[instrument(skip_all, ret)]
+pub(crate) fn format_loop(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    if parent
+        .children()
+        .any(|c| matches!(c.kind(), LineComment | BlockComment))
+    {
+        return format_default(parent, children, ctx);
+    }
+    let mut res = String::new();
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            Space => {}
+            While | For => ctx.push_raw_in(s, &mut res),
+            _ => {
+                ctx.push_raw_in(" ", &mut res);
+                ctx.push_raw_in(s, &mut res);
+            }
+        }
+    }
+    res
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::{Environment, CONFIG_FILE_NAME};
+use crate::{editorconfig, Config, Names};
+
+/// The files the command line doesn't format: those matching the `exclude`
+/// globs of the config of their directory, relative to the directory of the
+/// config file setting them, or a `.typstfmtignore` file of one of their
+/// directories up to the root of their project.
+///
+/// `.typstfmtignore` files have the syntax of `.gitignore`: one glob per line
+/// relative to their directory, matching the name at any depth without a `/`,
+/// only directories with a trailing `/`, re-including what an earlier glob
+/// excluded with a leading `!`, `#` starting a comment. The last matching glob
+/// wins.
+pub struct Ignore {
+    exclude: Exclude,
+    /// the rules of the `.typstfmtignore` file of each directory already read.
+    files: Mutex<HashMap<PathBuf, Vec<Rule>>>,
+}
+
+enum Exclude {
+    /// the globs of one config.
+    Fixed(Vec<Rule>),
+    /// the globs of the config of each directory already seen.
+    PerDirectory(Environment, Mutex<HashMap<PathBuf, Vec<Rule>>>),
+}
+
+struct Rule {
+    base: PathBuf,
+    glob: String,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Rule {
+    fn parse(base: &Path, line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, glob) = match line.strip_prefix('!') {
+            Some(glob) => (true, glob),
+            None => (false, line),
+        };
+        let (dir_only, glob) = match glob.strip_suffix('/') {
+            Some(glob) => (true, glob),
+            None => (false, glob),
+        };
+        Some(Self {
+            base: base.to_path_buf(),
+            glob: glob.to_string(),
+            negated,
+            dir_only,
+        })
+    }
+
+    /// `Some(true)` if the rule excludes `path`, or one of its directories,
+    /// `Some(false)` if it includes it again.
+    fn applies(&self, path: &Path) -> Option<bool> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        let components: Vec<_> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        (1..=components.len())
+            .any(|i| {
+                let is_dir = i < components.len() || path.is_dir();
+                (is_dir || !self.dir_only)
+                    && editorconfig::glob_matches(&self.glob, &components[..i].join("/"))
+            })
+            .then_some(!self.negated)
+    }
+}
+
+impl Ignore {
+    /// the files matching the `exclude` globs of a config, relative to `base`,
+    /// see [Config::exclude_dir](crate::Config::exclude_dir), or ignored by their
+    /// directories.
+    pub fn new(exclude: Names, base: &Path) -> Self {
+        Self {
+            exclude: Exclude::Fixed(exclude_rules(exclude, base)),
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// the files matching the `exclude` globs of the config of their directory,
+    /// [Config::resolve_for] gives it, or ignored by their directories: a nested
+    /// config file excludes files below it.
+    pub fn of_configs() -> Self {
+        Self::of_configs_in(Environment::of_process())
+    }
+
+    /// [of_configs](Self::of_configs) in the environment `env`.
+    pub(crate) fn of_configs_in(env: Environment) -> Self {
+        Self {
+            exclude: Exclude::PerDirectory(env, Mutex::new(HashMap::new())),
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// true if `path` is excluded, or one of its directories, the
+    /// `.typstfmtignore` files are read once.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let path = path.canonicalize().unwrap_or_else(|_| cwd.join(path));
+        let root = project_root(&path, &cwd);
+        let mut files = self.files.lock().unwrap_or_else(|err| err.into_inner());
+        let mut dirs: Vec<_> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(root))
+            .collect();
+        // the nearer files come last, they win.
+        dirs.reverse();
+        for dir in &dirs {
+            if !files.contains_key(*dir) {
+                files.insert(dir.to_path_buf(), read_rules(dir));
+            }
+        }
+        let mut ignored = false;
+        let mut check = |rules: &[Rule]| {
+            for rule in rules {
+                if let Some(excluded) = rule.applies(&path) {
+                    ignored = excluded;
+                }
+            }
+        };
+        match &self.exclude {
+            Exclude::Fixed(rules) => check(rules),
+            Exclude::PerDirectory(env, configs) => {
+                let mut configs = configs.lock().unwrap_or_else(|err| err.into_inner());
+                let dir = path.parent().unwrap_or(&path);
+                let rules = configs.entry(dir.to_path_buf()).or_insert_with(|| {
+                    Config::exclude_in(dir, env)
+                        .map(|(exclude, base)| exclude_rules(exclude, &base))
+                        .unwrap_or_default()
+                });
+                check(rules);
+            }
+        }
+        for dir in &dirs {
+            check(&files[*dir]);
+        }
+        ignored
+    }
+}
+
+/// the `exclude` globs of a config file in `base`.
+fn exclude_rules(exclude: Names, base: &Path) -> Vec<Rule> {
+    let base = base.canonicalize().unwrap_or_else(|_| base.to_path_buf());
+    exclude
+        .iter()
+        .filter_map(|g| Rule::parse(&base, g))
+        .collect()
+}
+
+/// the outermost directory whose `.typstfmtignore` applies to `path`: the root of
+/// its git repository, else the outermost directory with a config file, else the
+/// current directory `cwd` if `path` is in it, else the directory of `path`.
+fn project_root<'a>(path: &'a Path, cwd: &'a Path) -> &'a Path {
+    let mut dirs = path.ancestors().skip(1);
+    if let Some(repo) = dirs.clone().find(|dir| dir.join(".git").exists()) {
+        return repo;
+    }
+    let dot_file_name = format!(".{CONFIG_FILE_NAME}");
+    let configured = dirs
+        .clone()
+        .filter(|dir| dir.join(CONFIG_FILE_NAME).is_file() || dir.join(&dot_file_name).is_file())
+        .last();
+    match configured {
+        Some(dir) => dir,
+        None if path.starts_with(cwd) => cwd,
+        None => dirs.next().unwrap_or(path),
+    }
+}
+
+fn read_rules(dir: &Path) -> Vec<Rule> {
+    let Ok(content) = std::fs::read_to_string(dir.join(".typstfmtignore")) else {
+        return vec![];
+    };
+    content
+        .lines()
+        .filter_map(|line| Rule::parse(dir, line))
+        .collect()
+}
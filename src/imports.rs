@@ -0,0 +1,222 @@
+use typst_syntax::ast::AstNode;
+
+use super::*;
+
+/// formats `import "module": items`, one space around the source and after the colon.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_import(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    let mut res = String::new();
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            _ if ctx.off => res.push_str(&deep_no_format(&node)),
+            Space if node.next_sibling_kind() == Some(Colon) => {}
+            Space => ctx.push_in(" ", &mut res),
+            Colon => {
+                ctx.push_raw_in(s, &mut res);
+                ctx.push_in(" ", &mut res);
+            }
+            _ => ctx.push_raw_in(s, &mut res),
+        }
+    }
+    res
+}
+
+/// formats the items of an import, `a, b, c`, sorted with `sort_imports`.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_import_items(
+    parent: &LinkedNode,
+    children: &[String],
+    ctx: &mut Ctx,
+) -> String {
+    let has_comment = parent
+        .children()
+        .any(|c| matches!(c.kind(), LineComment | BlockComment));
+    if ctx.config.sort_imports && !has_comment && !ctx.off {
+        let mut items = children
+            .iter()
+            .zip(parent.children())
+            .filter(|(_, node)| !matches!(node.kind(), Space | Comma))
+            .map(|(s, _)| s.as_str())
+            .collect_vec();
+        items.sort();
+        items.dedup();
+        ctx.lost_context();
+        return items.join(", ");
+    }
+    let mut res = String::new();
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            _ if ctx.off => res.push_str(&deep_no_format(&node)),
+            Space => {}
+            Comma => {
+                ctx.push_raw_in(s, &mut res);
+                if node.next_sibling().is_some() {
+                    ctx.push_in(" ", &mut res);
+                }
+            }
+            _ => ctx.push_raw_in(s, &mut res),
+        }
+    }
+    res
+}
+
+/// An `#import` line at the top of a document.
+struct Import {
+    /// the comment lines above it.
+    comments: Vec<String>,
+    source: String,
+    /// `None` for `#import "a.typ"` and `#import "a.typ" as b`, `*` for a wildcard.
+    items: Option<Vec<String>>,
+    line: String,
+}
+
+impl Import {
+    /// `None` if `line` isn't a whole import on one line.
+    fn parse(line: &str) -> Option<Import> {
+        let root = parse(line);
+        let root = LinkedNode::new(&root);
+        let mut children = root.children();
+        if children.next()?.kind() != Hash {
+            return None;
+        }
+        let import = children.next().filter(|c| c.kind() == ModuleImport)?;
+        // `as` renames, the items of a renamed module can't be merged.
+        if children.next().is_some() || import.children().any(|c| c.text() == "as") {
+            return None;
+        }
+        let source = import
+            .children()
+            .find(|c| c.kind() == Str)?
+            .text()
+            .to_string();
+        let items = if import.children().any(|c| c.kind() == Star) {
+            Some(vec!["*".to_string()])
+        } else {
+            match import.children().find(|c| c.kind() == ImportItems) {
+                Some(items) => {
+                    let items = items
+                        .children()
+                        .filter(|c| !matches!(c.kind(), Space | Comma))
+                        .collect_vec();
+                    // renamed items, `a as b`, are left where they are.
+                    if items.iter().any(|c| c.kind() != Ident) {
+                        return None;
+                    }
+                    Some(items.iter().map(|c| c.text().to_string()).collect())
+                }
+                None => None,
+            }
+        };
+        Some(Import {
+            comments: vec![],
+            source,
+            items,
+            line: line.to_string(),
+        })
+    }
+
+    fn names(&self) -> &[String] {
+        self.items.as_deref().unwrap_or_default()
+    }
+}
+
+/// with `sort_imports`, sorts the `#import` lines at the top of the formatted
+/// document `s` by source and merges the item lists of the same source.
+///
+/// The run stops at the first line that isn't an import or a comment: what comes
+/// after may depend on the order. A wildcard import, `#import "a.typ": *`, may
+/// shadow any name, the imports are only sorted between them. Nothing is
+/// reordered between two of them if an item is imported from two sources, the
+/// last one wins in typst.
+pub(crate) fn sort_imports(s: &str) -> String {
+    let lines = s.split('\n').collect_vec();
+    let mut imports: Vec<Import> = vec![];
+    let mut comments = vec![];
+    let mut end = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if line.starts_with("//") && utils::comment_directive(line).is_none() {
+            comments.push(line.to_string());
+            continue;
+        }
+        let Some(mut import) = Import::parse(line) else {
+            break;
+        };
+        import.comments = std::mem::take(&mut comments);
+        imports.push(import);
+        end = i + 1;
+    }
+    if imports.len() < 2 {
+        return s.to_string();
+    }
+
+    let mut res = vec![];
+    let mut run = vec![];
+    for import in imports {
+        if import.names().contains(&"*".to_string()) {
+            res.extend(sort_run(std::mem::take(&mut run)));
+            res.extend(import.comments);
+            res.push(import.line);
+        } else {
+            run.push(import);
+        }
+    }
+    res.extend(sort_run(run));
+    res.extend(lines[end..].iter().map(|l| l.to_string()));
+    res.join("\n")
+}
+
+/// the lines of the `imports` between two wildcard imports, sorted and merged
+/// unless one of their items is imported from two sources.
+fn sort_run(mut imports: Vec<Import>) -> Vec<String> {
+    let clash = imports.iter().any(|a| {
+        imports
+            .iter()
+            .any(|b| a.source != b.source && a.names().iter().any(|n| b.names().contains(n)))
+    });
+    if !clash {
+        imports.sort_by(|a, b| a.source.cmp(&b.source));
+    }
+    let mut merged: Vec<Import> = vec![];
+    for import in imports {
+        match merged.last_mut() {
+            Some(last)
+                if !clash
+                    && last.source == import.source
+                    && last.items.is_some()
+                    && import.items.is_some() =>
+            {
+                last.comments.extend(import.comments);
+                let mut items = last.names().to_vec();
+                items.extend(import.names().iter().cloned());
+                items.sort();
+                items.dedup();
+                last.line = format!("#import {}: {}", last.source, items.join(", "));
+                last.items = Some(items);
+            }
+            _ => merged.push(import),
+        }
+    }
+    let mut res = vec![];
+    for import in merged {
+        res.extend(import.comments);
+        res.push(import.line);
+    }
+    res
+}
+
+/// the sources of the `import` and `include` of the tree that are files, packages
+/// (`@preview/name:version`) are left out.
+pub(crate) fn local_imports(node: &LinkedNode, res: &mut Vec<String>) {
+    if matches!(node.kind(), ModuleImport | ModuleInclude) {
+        let source = node
+            .children()
+            .find(|c| c.kind() == Str)
+            .and_then(|c| typst_syntax::ast::Str::from_untyped(c.get()).map(|s| s.get()));
+        if let Some(source) = source.filter(|s| !s.starts_with('@')) {
+            res.push(source.to_string());
+        }
+    }
+    for child in node.children() {
+        local_imports(&child, res);
+    }
+}
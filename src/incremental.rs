@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use typst_syntax::Source;
+
+use super::*;
+
+/// A document formatted again after each edit, for editors formatting large
+/// documents as one types.
+///
+/// The edits are reparsed incrementally and the top level constructs (calls,
+/// blocks, rules, headings...) left unchanged since the last formatting are
+/// taken from a cache rather than formatted again.
+pub struct Incremental {
+    config: Config,
+    source: Source,
+    cache: Option<Cache>,
+}
+
+impl Incremental {
+    pub fn new(text: &str, config: Config) -> Self {
+        Self {
+            config,
+            source: Source::detached(text),
+            cache: Some(Cache::default()),
+        }
+    }
+
+    /// the text of the document, with the edits applied.
+    pub fn text(&self) -> &str {
+        self.source.text()
+    }
+
+    /// replaces the bytes of `range` by `with`, like an editor would.
+    pub fn edit(&mut self, range: Range<usize>, with: &str) {
+        self.source.edit(range, with);
+    }
+
+    /// formats the document, like [format_output].
+    #[must_use]
+    pub fn format(&mut self) -> FormatOutput {
+        let s = self.source.text();
+        if s.contains(['\t', '\r']) {
+            // the tree of the source isn't the one of the text formatted.
+            let expanded = s.replace("\r\n", "\n");
+            let expanded = &utils::expand_tabs(&expanded, self.config.indent_space);
+            return format_parsed(
+                s,
+                expanded,
+                &parse(expanded),
+                self.config,
+                &[],
+                &mut self.cache,
+            );
+        }
+        format_parsed(s, s, self.source.root(), self.config, &[], &mut self.cache)
+    }
+}
+
+/// The formatted text of top level constructs, by kind, source text and what
+/// else their formatting depends on.
+#[derive(Default)]
+pub(crate) struct Cache {
+    previous: HashMap<Key, Entry>,
+    current: HashMap<Key, Entry>,
+}
+
+/// what the formatting of a node depends on: its source and where it starts in
+/// the output, what its previous siblings left in [Ctx].
+#[derive(PartialEq, Eq, Hash)]
+struct Key {
+    kind: SyntaxKind,
+    text: String,
+    start_column: usize,
+    indent_level: usize,
+    just_spaced: bool,
+    consec_new_line: i32,
+}
+
+impl Key {
+    fn new(node: &LinkedNode, ctx: &Ctx) -> Self {
+        Self {
+            kind: node.kind(),
+            text: node.get().clone().into_text().to_string(),
+            start_column: ctx.start_column,
+            indent_level: ctx.indent_level,
+            just_spaced: ctx.just_spaced,
+            consec_new_line: ctx.consec_new_line,
+        }
+    }
+}
+
+/// a formatted node with the diagnostics and the skipped regions its formatting
+/// reported, their offsets relative to the start of the node.
+#[derive(Clone)]
+struct Entry {
+    text: String,
+    diagnostics: Vec<Diagnostic>,
+    skipped: Vec<Range<usize>>,
+    /// what the formatting left in [Ctx].
+    just_spaced: bool,
+    consec_new_line: i32,
+}
+
+impl Cache {
+    /// the cache for the next formatting: what this one used, the constructs
+    /// removed from the document are forgotten.
+    pub(crate) fn next(self) -> Self {
+        Self {
+            previous: self.current,
+            current: HashMap::new(),
+        }
+    }
+}
+
+/// true for the nodes formatted the same wherever they are in the document:
+/// the constructs at the top level, starting with a keyword or a delimiter so the
+/// spacing before them doesn't matter. Formatting directives change what follows
+/// them, the constructs containing some aren't cached. Neither are the `let` of
+/// a document with a `// fmt: align` marker, [align::align_let_runs] needs them
+/// formatted again.
+fn is_cacheable(node: &LinkedNode, ctx: &Ctx) -> bool {
+    ctx.cache.is_some()
+        && !ctx.off
+        && node.parent().is_some_and(|p| {
+            p.parent().is_none() && !(node.kind() == LetBinding && align::has_marker(p))
+        })
+        && matches!(
+            node.kind(),
+            FuncCall
+                | LetBinding
+                | SetRule
+                | ShowRule
+                | Conditional
+                | WhileLoop
+                | ForLoop
+                | CodeBlock
+                | ContentBlock
+                | ModuleImport
+                | ModuleInclude
+                | Heading
+                | ListItem
+                | EnumItem
+                | TermItem
+                | Equation
+        )
+        && !node.erroneous()
+        && !utils::contains_directive(node)
+}
+
+/// the formatted text of `node` if it was cached, its diagnostics and skipped
+/// regions are reported again.
+pub(crate) fn cached(node: &LinkedNode, ctx: &mut Ctx) -> Option<String> {
+    if !is_cacheable(node, ctx) {
+        return None;
+    }
+    let key = Key::new(node, ctx);
+    let cache = ctx.cache.as_mut()?;
+    let entry = match cache.current.get(&key) {
+        Some(entry) => entry.clone(),
+        None => {
+            let entry = cache.previous.remove(&key)?;
+            cache.current.insert(key, entry.clone());
+            entry
+        }
+    };
+    let start = node.offset();
+    for diagnostic in entry.diagnostics {
+        ctx.push_diagnostic(Diagnostic {
+            range: diagnostic.range.start + start..diagnostic.range.end + start,
+            ..diagnostic
+        });
+    }
+    for range in entry.skipped {
+        ctx.push_skipped(range.start + start..range.end + start);
+    }
+    ctx.stats.applied("incremental_cache");
+    ctx.just_spaced = entry.just_spaced;
+    ctx.consec_new_line = entry.consec_new_line;
+    Some(entry.text)
+}
+
+/// what [Ctx] was before formatting a node, see [store].
+pub(crate) struct Mark {
+    key: Option<Key>,
+    diagnostics: usize,
+    skipped: usize,
+}
+
+/// marks the start of the formatting of `node`, to [store] it after.
+pub(crate) fn mark(node: &LinkedNode, ctx: &Ctx) -> Mark {
+    Mark {
+        key: is_cacheable(node, ctx).then(|| Key::new(node, ctx)),
+        diagnostics: ctx.diagnostics.len(),
+        skipped: ctx.skipped.len(),
+    }
+}
+
+/// caches the formatted text `res` of `node` with what its formatting reported
+/// since `mark`.
+pub(crate) fn store(node: &LinkedNode, res: &str, mark: Mark, ctx: &mut Ctx) {
+    let Some(key) = mark.key else {
+        return;
+    };
+    // the formatting turned `typstfmt::off`.
+    if !is_cacheable(node, ctx) {
+        return;
+    }
+    let start = node.offset();
+    let entry = Entry {
+        text: res.to_string(),
+        diagnostics: ctx.diagnostics[mark.diagnostics..]
+            .iter()
+            .map(|d| Diagnostic {
+                range: d.range.start.saturating_sub(start)..d.range.end.saturating_sub(start),
+                ..d.clone()
+            })
+            .collect(),
+        skipped: ctx.skipped[mark.skipped..]
+            .iter()
+            .map(|r| r.start.saturating_sub(start)..r.end.saturating_sub(start))
+            .collect(),
+        just_spaced: ctx.just_spaced,
+        consec_new_line: ctx.consec_new_line,
+    };
+    if let Some(cache) = ctx.cache.as_mut() {
+        cache.current.insert(key, entry);
+    }
+}
@@ -18,33 +18,407 @@ use Option::None;
 
 mod config;
 
-pub use config::Config;
+pub use config::{
+    package_dirs, Config, ConfigOption, IndentStyle, LineEnding, Mode, OnError, TypstVersion,
+    VersionMismatch, Wrap,
+};
 
 mod context;
+mod editorconfig;
+mod ignore;
+mod names;
+mod output;
+mod version;
+
+pub use ignore::Ignore;
+pub use incremental::Incremental;
+pub use names::Names;
+pub use output::{
+    Diagnostic, FormatOutput, FormatReport, SourceMap, Stats, SyntaxErrors, TextEdit,
+};
+pub use raw::EmbeddedFormatter;
+pub use version::VersionReq;
 
 use context::Ctx;
+use utils::Directive;
 
 mod utils;
 
+mod align;
 mod binary;
+mod chain;
 mod code_blocks;
+mod doc;
+mod flow;
+mod imports;
+mod incremental;
+mod lists;
+mod markdown;
 mod markup;
 mod math;
 mod params;
+mod partial;
+mod raw;
+mod sorting;
+mod strings;
+mod styling;
+pub mod verify;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 #[must_use]
 pub fn format(s: &str, config: Config) -> String {
-    //replace tabs
-    let s = &s.replace('\t', &" ".repeat(config.indent_space));
+    format_output(s, config).text
+}
+
+/// Like [format] but also reports whether something changed, the warnings and
+/// the regions that were left untouched.
+#[must_use]
+pub fn format_output(s: &str, config: Config) -> FormatOutput {
+    format_output_with_embedded(s, config, &[])
+}
+
+/// Like [format_output] but with `on_error = "abort"` a document containing syntax
+/// errors is an error, the errors are returned rather than the document.
+pub fn try_format(s: &str, config: Config) -> Result<FormatOutput, SyntaxErrors> {
+    let expanded = &utils::expand_tabs(&s.replace("\r\n", "\n"), config.indent_space);
+    let tree = parse(expanded);
+    if config.on_error == OnError::Abort && tree.erroneous() {
+        return Err(SyntaxErrors(syntax_errors(s, expanded, &tree, config)));
+    }
+    Ok(format_parsed(s, expanded, &tree, config, &[], &mut None))
+}
+
+/// The syntax errors of `s`, the ones [try_format] returns, without formatting it.
+pub fn check_syntax(s: &str, config: Config) -> Result<(), SyntaxErrors> {
+    let expanded = &utils::expand_tabs(&s.replace("\r\n", "\n"), config.indent_space);
+    let tree = parse(expanded);
+    if tree.erroneous() {
+        return Err(SyntaxErrors(syntax_errors(s, expanded, &tree, config)));
+    }
+    Ok(())
+}
+
+/// the syntax errors of `tree`, the syntax of `expanded`, at their offsets in `s`.
+fn syntax_errors(
+    s: &str,
+    expanded: &str,
+    tree: &typst_syntax::SyntaxNode,
+    config: Config,
+) -> Vec<Diagnostic> {
+    let offset = |o| utils::unexpand_offset(s, expanded, config.indent_space, o);
+    utils::errors(&LinkedNode::new(tree))
+        .into_iter()
+        .map(|(range, message)| Diagnostic {
+            range: offset(range.start)..offset(range.end),
+            message: format!(
+                "syntax error: {message}{}",
+                config.typst_version.syntax_hint()
+            ),
+        })
+        .collect()
+}
+
+/// Like [format_output] but the code of the raw blocks tagged with a language is
+/// formatted by the first of `formatters` that knows it. The other raw blocks are
+/// always written byte for byte.
+#[must_use]
+pub fn format_output_with_embedded(
+    s: &str,
+    config: Config,
+    formatters: &[&dyn EmbeddedFormatter],
+) -> FormatOutput {
+    //replace tabs, the line endings are put back at the end
+    let expanded = &utils::expand_tabs(&s.replace("\r\n", "\n"), config.indent_space);
+    format_parsed(s, expanded, &parse(expanded), config, formatters, &mut None)
+}
+
+/// formats `tree`, the syntax of `expanded`: `s` with its tabs expanded and its
+/// line endings replaced. The top level constructs found in `cache` aren't
+/// formatted again, see [Incremental].
+fn format_parsed(
+    s: &str,
+    expanded: &str,
+    tree: &typst_syntax::SyntaxNode,
+    config: Config,
+    formatters: &[&dyn EmbeddedFormatter],
+    cache: &mut Option<incremental::Cache>,
+) -> FormatOutput {
+    let indent_space = config.indent_space;
+    let crlf = match config.line_ending {
+        LineEnding::Auto => utils::mostly_crlf(s),
+        LineEnding::Lf => false,
+        LineEnding::Crlf => true,
+    };
+
+    if config.on_error != OnError::FormatValidRegions && tree.erroneous() {
+        return FormatOutput {
+            text: s.to_string(),
+            changed: false,
+            diagnostics: syntax_errors(s, expanded, tree, config),
+            skipped_regions: vec![0..s.len()],
+            stats: Stats::default(),
+        };
+    }
 
-    let init = parse(s);
     let mut context = Ctx::from_config(config);
-    let root = LinkedNode::new(&init);
-    let s = visit(&root, &mut context);
-    regex::Regex::new("( )+\n")
-        .unwrap()
-        .replace_all(&s, "\n")
-        .to_string()
+    context.cache = cache.take();
+    let root = LinkedNode::new(tree);
+    let formatted = visit(&root, &mut context);
+    *cache = context.cache.take().map(incremental::Cache::next);
+    let text = utils::trim_line_ends(&formatted);
+
+    if config.verify_syntax {
+        if let Err(err) = verify::same_syntax(expanded, &text) {
+            warn!("formatting changed the syntax, the input is kept: {err}");
+            return FormatOutput {
+                text: s.to_string(),
+                changed: false,
+                diagnostics: vec![Diagnostic {
+                    range: 0..s.len(),
+                    message: format!(
+                        "formatting would change the document, it was left as is: {err}"
+                    ),
+                }],
+                skipped_regions: vec![0..s.len()],
+                stats: context.stats,
+            };
+        }
+    }
+
+    let text = match config.indent_style {
+        IndentStyle::Spaces => text,
+        IndentStyle::Tabs => utils::indent_with_tabs(&text, indent_space),
+    };
+    let text = if crlf {
+        text.replace('\n', "\r\n")
+    } else {
+        text
+    };
+    // offsets were computed on the text with tabs and line endings replaced.
+    let to_input = |range: std::ops::Range<usize>| {
+        if s == expanded {
+            return range;
+        }
+        utils::unexpand_offset(s, expanded, indent_space, range.start)
+            ..utils::unexpand_offset(s, expanded, indent_space, range.end)
+    };
+    // the raw blocks of the input are found in its tree, not by parsing it again.
+    let originals = raw::raw_ranges(&root).into_iter().map(to_input).collect();
+    let text = raw::restore(s, originals, &text, formatters);
+
+    if let Some(start) = context.off_since.take() {
+        context.push_skipped(start..expanded.len());
+    }
+    FormatOutput {
+        changed: text != s,
+        diagnostics: context
+            .diagnostics
+            .into_iter()
+            .map(|d| Diagnostic {
+                range: to_input(d.range),
+                ..d
+            })
+            .collect(),
+        skipped_regions: context.skipped.into_iter().map(to_input).collect(),
+        stats: context.stats,
+        text,
+    }
+}
+
+/// Like [format_output] but also measures the time spent formatting each kind of
+/// node, see [Stats::time], to find what makes a document slow.
+#[must_use]
+pub fn format_output_timed(s: &str, config: Config) -> FormatOutput {
+    utils::with_timings(|| format_output(s, config))
+}
+
+/// Like [format_output] but lines are measured by `width` rather than by their
+/// width in a monospace font, to count a tab as several columns, to ignore escape
+/// sequences or to approximate a proportional font for instance.
+///
+/// Breaking decisions and alignment use it, `max_line_length` is in its unit.
+#[must_use]
+pub fn format_output_with_width(s: &str, config: Config, width: fn(&str) -> usize) -> FormatOutput {
+    utils::with_width(width, || format_output(s, config))
+}
+
+/// Formats a snippet that will be inserted at a position already indented by `indent`,
+/// for instance when pasting code in an editor.
+///
+/// Every line but the first one is prefixed by `indent` and the max line length is
+/// reduced by its width. To indent by a level, use `" ".repeat(level * config.indent_space)`.
+/// The lines inside raw blocks and strings are kept as they are, indenting them
+/// would change their content.
+#[must_use]
+pub fn format_fragment(s: &str, config: Config, indent: &str) -> String {
+    let indent_width =
+        utils::max_line_length(&indent.replace('\t', &" ".repeat(config.indent_space)));
+    let config = Config {
+        max_line_length: config.max_line_length.saturating_sub(indent_width),
+        ..config
+    };
+    let formatted = format(s, config);
+    let tree = parse(&formatted);
+    let mut verbatim = vec![];
+    utils::verbatim_ranges(&LinkedNode::new(&tree), &mut verbatim);
+    let in_verbatim = |offset: usize| {
+        verbatim
+            .iter()
+            .any(|range| range.start < offset && offset < range.end)
+    };
+    let mut res = String::new();
+    let mut offset = 0;
+    for (i, line) in formatted.split_inclusive('\n').enumerate() {
+        if i > 0 && !line.trim().is_empty() && !in_verbatim(offset) {
+            res.push_str(indent);
+        }
+        res.push_str(line);
+        offset += line.len();
+    }
+    res
+}
+
+/// Formats only the construct closed by what the user just typed, for editors
+/// formatting as one types.
+///
+/// `offset` is the byte offset right after the typed character: after a `}` or a
+/// `]` the block it closes is formatted, after a newline the expression ending the
+/// previous line is. Returns `None` if there is nothing to format there, if it is
+/// already formatted or can't be formatted on its own, for instance because it
+/// contains syntax errors or tabs.
+#[must_use]
+pub fn format_on_type(s: &str, offset: usize, config: Config) -> Option<TextEdit> {
+    if offset == 0 || offset > s.len() {
+        return None;
+    }
+    let tree = parse(s);
+    let root = LinkedNode::new(&tree);
+    let node = partial::typed_construct(&root, offset)?;
+    partial::format_node(s, &root, &node, config)
+}
+
+/// Formats `s` and returns the edits turning it into the formatted text rather
+/// than the whole text, for editors: applying small edits keeps the cursors and
+/// the undo history in place. Empty if `s` is already formatted.
+#[must_use]
+pub fn format_edits(s: &str, config: Config) -> Vec<TextEdit> {
+    TextEdit::diff(s, &format(s, config))
+}
+
+/// Like [format] but also returns the map from the offsets of `s` to the offsets
+/// of the formatted text, for editors restoring the cursors after formatting.
+#[must_use]
+pub fn format_with_map(s: &str, config: Config) -> (String, SourceMap) {
+    let text = format(s, config);
+    let map = SourceMap::new(s, &text);
+    (text, map)
+}
+
+/// Formats the code of the fenced blocks tagged `typst` or `typ` of the Markdown
+/// document `s`, the rest of the document is kept byte for byte. The code keeps
+/// the indentation of its fence, in a list item for instance.
+#[must_use]
+pub fn format_markdown(s: &str, config: Config) -> String {
+    markdown::format_typst_blocks(s, config)
+}
+
+/// Formats only the smallest constructs (statement, call, block...) enclosing each
+/// byte range of `ranges`, for editors formatting selections.
+///
+/// The edits are sorted and don't overlap, a range inside a construct already
+/// formatted for another range adds nothing.
+#[must_use]
+pub fn format_ranges(s: &str, ranges: &[std::ops::Range<usize>], config: Config) -> Vec<TextEdit> {
+    partial::format_ranges(s, ranges, config)
+}
+
+/// Formats only the smallest construct (statement, call, block...) enclosing the
+/// byte `range`, for an editor "format selection" command.
+///
+/// Returns the whole text with the construct formatted and the range of `s` that
+/// was replaced, `None` if it is already formatted or can't be formatted on its own.
+#[must_use]
+pub fn format_range(
+    s: &str,
+    range: std::ops::Range<usize>,
+    config: Config,
+) -> Option<(String, std::ops::Range<usize>)> {
+    let edit = format_ranges(s, &[range], config).pop()?;
+    let mut res = s.to_string();
+    res.replace_range(edit.range.clone(), &edit.replacement);
+    Some((res, edit.range))
+}
+
+/// Formats only the smallest construct (statement, call, block...) containing the
+/// byte `offset`, for an editor "format current block" command.
+///
+/// Returns `None` if it is already formatted or can't be formatted on its own.
+#[must_use]
+pub fn format_enclosing(s: &str, offset: usize, config: Config) -> Option<TextEdit> {
+    format_ranges(s, &[offset..offset], config).pop()
+}
+
+/// Formats every source of `items` with the same config on the threads of the
+/// [rayon] pool, the results are in the order of `items`.
+#[cfg(feature = "parallel")]
+#[must_use]
+pub fn format_many<I>(items: I, config: Config) -> Vec<String>
+where
+    I: rayon::iter::IntoParallelIterator,
+    I::Item: AsRef<str>,
+{
+    use rayon::iter::ParallelIterator;
+    items
+        .into_par_iter()
+        .map(|s| format(s.as_ref(), config))
+        .collect()
+}
+
+/// Formats every source of `items` like [format_output] on the threads of the
+/// [rayon] pool, the outputs are in the order of `items`, with a summary of the
+/// batch.
+#[cfg(feature = "parallel")]
+#[must_use]
+pub fn format_batch<I>(items: I, config: Config) -> (Vec<FormatOutput>, FormatReport)
+where
+    I: rayon::iter::IntoParallelIterator,
+    I::Item: AsRef<str> + Send,
+{
+    use rayon::iter::ParallelIterator;
+    // there is no clock in `wasm32-unknown-unknown`.
+    #[cfg(not(target_arch = "wasm32"))]
+    let start = std::time::Instant::now();
+    let results: Vec<_> = items
+        .into_par_iter()
+        .map(|s| {
+            let output = format_output(s.as_ref(), config);
+            let mut report = FormatReport::default();
+            report.add(s.as_ref(), &output.text);
+            (output, report)
+        })
+        .collect();
+    let mut report = FormatReport::default();
+    let mut outputs = vec![];
+    for (output, file) in results {
+        report.merge(&file);
+        outputs.push(output);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        report.elapsed = start.elapsed();
+    }
+    (outputs, report)
+}
+
+/// The paths of the files `s` imports or includes, as written: relative to the
+/// file, or to the root of the project if they start with `/`. Packages are left out.
+#[must_use]
+pub fn local_imports(s: &str) -> Vec<String> {
+    let tree = parse(s);
+    let mut res = vec![];
+    imports::local_imports(&LinkedNode::new(&tree), &mut res);
+    res
 }
 
 /// This is recursively called on the AST, the formatting is bottom up,
@@ -54,34 +428,95 @@ pub fn format(s: &str, config: Config) -> String {
 /// One assumed rule is that no kind should be formatting with surrounded space
 #[instrument(skip_all, name = "V", fields(kind = format!("{:?}",node.kind())))]
 fn visit(node: &LinkedNode, ctx: &mut Ctx) -> String {
+    // the siblings of the node find the layout of their parent again.
+    let outer = (ctx.indent_level, ctx.start_column);
+    if node.children().len() > 0 {
+        ctx.start_column = utils::start_column(node, ctx.indent_level, ctx.config.indent_space);
+    }
+    let res = visit_node(node, ctx);
+    (ctx.indent_level, ctx.start_column) = outer;
+    res
+}
+
+fn visit_node(node: &LinkedNode, ctx: &mut Ctx) -> String {
+    if node.erroneous() && utils::is_top_level(node) {
+        return format_erroneous(node, ctx);
+    }
+    if !ctx.off && utils::is_ignored(node) {
+        ctx.stats.applied("ignore");
+        ctx.push_skipped(node.range());
+        ctx.lost_context();
+        return deep_no_format(node);
+    }
+    let max_depth = ctx.config.max_format_depth;
+    if max_depth > 0 && !ctx.off && utils::nesting_depth(node) > max_depth {
+        ctx.stats.applied("max_format_depth");
+        ctx.push_skipped(node.range());
+        ctx.lost_context();
+        return deep_no_format(node);
+    }
+    if let Some(res) = incremental::cached(node, ctx) {
+        return res;
+    }
+    let mark = incremental::mark(node, ctx);
     let mut res: Vec<String> = vec![];
+    let indents = utils::indents_children(node);
+    ctx.indent_level += usize::from(indents);
     for child in node.children() {
         let child_fmt = visit(&child, ctx);
         res.push(child_fmt);
     }
+    ctx.indent_level -= usize::from(indents);
+    // there is no clock in `wasm32-unknown-unknown`.
+    #[cfg(not(target_arch = "wasm32"))]
+    let start = ctx.timed.then(std::time::Instant::now);
     let res = match node.kind() {
         LineComment => format_comment_handling_disable(node, &res, ctx),
         _ if ctx.off => no_format(node, &res, ctx),
         Binary => binary::format_bin_left_assoc(node, &res, ctx),
+        FieldAccess | FuncCall => chain::format_chain(node, &res, ctx),
         Named | Keyed => format_named_args(node, &res, ctx),
-        ListItem | EnumItem | TermItem => format_list_enum(node, &res, ctx),
+        ListItem | EnumItem | TermItem => lists::format_list_enum(node, &res, ctx),
         CodeBlock => code_blocks::format_code_blocks(node, &res, ctx),
+        Code => code_blocks::format_code(node, &res, ctx),
         Markup => markup::format_markup(node, &res, ctx),
         ContentBlock => markup::format_content_blocks(node, &res, ctx),
+        Heading => markup::format_heading(node, &res, ctx),
         Args | Params | Dict | Array | Destructuring | Parenthesized => {
             params::format_args(node, &res, ctx)
         }
-        LetBinding => format_let_binding(node, &res, ctx),
-        Conditional => conditional_format(node, &res, ctx),
-        Raw | BlockComment => {
+        LetBinding | DestructAssignment => format_let_binding(node, &res, ctx),
+        Spread => params::format_spread(node, &res, ctx),
+        Closure => format_closure(node, &res, ctx),
+        FuncReturn => code_blocks::format_return(node, &res, ctx),
+        ModuleImport => imports::format_import(node, &res, ctx),
+        ImportItems => imports::format_import_items(node, &res, ctx),
+        Conditional => flow::format_conditional(node, &res, ctx),
+        WhileLoop | ForLoop => flow::format_loop(node, &res, ctx),
+        SetRule => styling::format_set_rule(node, &res, ctx),
+        ShowRule => styling::format_show_rule(node, &res, ctx),
+        Raw => {
             ctx.lost_context();
             node.text().to_string()
         }
+        BlockComment => {
+            ctx.lost_context();
+            format_comment_text(node, ctx)
+        }
         Equation => math::format_equation(node, &res, ctx),
         Math => math::format_math(node, &res, ctx),
-        Str => no_format(node, &res, ctx),
+        MathAttach => math::format_attach(node, &res, ctx),
+        MathFrac => math::format_frac(node, &res, ctx),
+        Str => strings::format_str(node, &res, ctx),
         _ => format_default(node, &res, ctx),
     };
+    incremental::store(node, &res, mark, ctx);
+    let kind = node.kind().name();
+    *ctx.stats.nodes.entry(kind).or_default() += 1;
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(start) = start {
+        *ctx.stats.time.entry(kind).or_default() += start.elapsed();
+    }
     if node.children().count() == 0 {
         debug!("TOKEN : {:?}", node.kind());
     } else {
@@ -108,6 +543,23 @@ fn format_default(node: &LinkedNode, children: &[String], ctx: &mut Ctx) -> Stri
     res
 }
 
+/// copies a top level node containing syntax errors verbatim, the other top
+/// level nodes are still formatted.
+fn format_erroneous(node: &LinkedNode, ctx: &mut Ctx) -> String {
+    let (line, col) = utils::line_col(node);
+    let hint = ctx.config.typst_version.syntax_hint();
+    for (range, message) in utils::errors(node) {
+        let message = format!(
+            "syntax error in the node starting at {line}:{col}: {message}{hint}, leaving it untouched."
+        );
+        warn!("{message}");
+        ctx.push_diagnostic(Diagnostic { range, message });
+    }
+    ctx.push_skipped(node.range());
+    ctx.lost_context();
+    deep_no_format(node)
+}
+
 fn no_format(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
     let mut res = String::new();
     ctx.push_raw_in(parent.text(), &mut res);
@@ -126,50 +578,65 @@ fn deep_no_format(parent: &LinkedNode) -> String {
     no_format(parent, &res, &mut Ctx::default())
 }
 
-fn conditional_format(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+#[instrument(skip_all, ret)]
+pub(crate) fn format_named_args(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
     let mut res = String::new();
-    ctx.push_raw_in(parent.text(), &mut res);
     for (s, node) in children.iter().zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
-            Space => {}
-            If => {
+            Show | Set => {
                 ctx.push_raw_in(s, &mut res);
-                ctx.push_raw_in(" ", &mut res);
+                ctx.push_in(" ", &mut res);
             }
-            CodeBlock | ContentBlock => {
-                ctx.push_raw_in(" ", &mut res);
+            Colon => {
+                if ctx.config.space_before_colon {
+                    res.push(' ');
+                }
+                res.push(':');
+                if ctx.config.space_after_colon {
+                    res.push(' ');
+                }
+            }
+            Space => {}
+            // the value goes on the next line, the comment would swallow it.
+            LineComment => {
                 ctx.push_raw_in(s, &mut res);
+                ctx.push_raw_in("\n", &mut res);
+                ctx.push_raw_in(&ctx.get_indent(), &mut res);
             }
-            Else => {
+            BlockComment => {
+                ctx.push_raw_in(s, &mut res);
                 ctx.push_raw_in(" ", &mut res);
+            }
+            _ => {
                 ctx.push_raw_in(s, &mut res);
-                if node.next_sibling_kind() == Some(Conditional) {
-                    ctx.push_raw_in(" ", &mut res);
-                }
             }
-            _ => ctx.push_raw_in(s, &mut res),
         }
     }
     res
 }
 
+/// formats `(a, b) => body` and the `f(a, b) = body` of a `let`: `=>` and `=`
+/// stay on the line of the parameters, with the start of the body. Broken
+/// parameters get one per line, see [params::format_args].
 #[instrument(skip_all, ret)]
-pub(crate) fn format_named_args(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+pub(crate) fn format_closure(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    if parent
+        .children()
+        .any(|c| matches!(c.kind(), LineComment | BlockComment))
+    {
+        return format_default(parent, children, ctx);
+    }
     let mut res = String::new();
     for (s, node) in children.iter().zip(parent.children()) {
         match node.kind() {
-            _ if ctx.off => res.push_str(node.text()),
-            Show | Set => {
-                ctx.push_raw_in(s, &mut res);
-                ctx.push_in(" ", &mut res);
-            }
-            Colon => res.push_str(": "),
             Space => {}
-            LineComment | BlockComment => ctx.push_raw_in(s, &mut res),
-            _ => {
+            Arrow | Eq => {
+                ctx.push_raw_in(" ", &mut res);
                 ctx.push_raw_in(s, &mut res);
+                ctx.push_raw_in(" ", &mut res);
             }
+            _ => ctx.push_raw_in(s, &mut res),
         }
     }
     res
@@ -182,10 +649,12 @@ pub(crate) fn format_let_binding(
     ctx: &mut Ctx,
 ) -> String {
     let mut res = String::new();
+    let mut eq = None;
     for (s, node) in children.iter().zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
             Eq => {
+                eq = Some(res.len());
                 ctx.push_in(" ", &mut res);
                 ctx.push_in(s, &mut res);
                 ctx.push_in(" ", &mut res);
@@ -196,35 +665,101 @@ pub(crate) fn format_let_binding(
             }
         }
     }
+    if let Some(eq) = eq.filter(|_| parent.kind() == LetBinding) {
+        align::keep_let_binding(parent, &res, eq, ctx);
+    }
     res
 }
 
 fn format_comment_handling_disable(parent: &LinkedNode, _: &[String], ctx: &mut Ctx) -> String {
     ctx.lost_context();
-    if parent.text().contains("typstfmt::off") {
-        ctx.off = true;
-    } else if parent.text().contains("typstfmt::on") {
-        ctx.off = false;
-    } else if parent.text().contains("typstfmt::") {
-        warn!("your comment contains `typstfmt::` not followed by `on` or `off`, did you make a typo?");
+    match utils::directive(parent) {
+        Some(Directive::Off) => {
+            if !ctx.off {
+                ctx.off_since = Some(parent.offset());
+            }
+            ctx.off = true;
+        }
+        Some(Directive::On) => {
+            if let Some(start) = ctx.off_since.take() {
+                ctx.push_skipped(start..parent.range().end);
+            }
+            ctx.off = false;
+        }
+        Some(Directive::Ignore) | None => {}
+        Some(Directive::Unknown) => {
+            let message = "your comment contains `typstfmt:` not followed by `on`, `off` or `ignore`, did you make a typo?";
+            warn!("{message}");
+            ctx.push_diagnostic(Diagnostic {
+                range: parent.range(),
+                message: message.to_string(),
+            });
+        }
     }
-    parent.text().to_string()
+
+    format_comment_text(parent, ctx)
 }
 
-fn format_list_enum(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
-    let mut res = String::new();
-    for (s, node) in children.iter().zip(parent.children()) {
-        match node.kind() {
-            _ if ctx.off => res.push_str(node.text()),
-            EnumMarker | ListMarker | TermMarker => {
-                ctx.push_raw_in(node.text(), &mut res);
+/// applies the opt-in rewrites of comments, directives are never changed.
+fn format_comment_text(parent: &LinkedNode, ctx: &Ctx) -> String {
+    let text = parent.text();
+    if ctx.off || utils::directive(parent).is_some() {
+        return text.to_string();
+    }
+    match parent.kind() {
+        LineComment if ctx.config.space_after_line_comment => {
+            if let Some(rest) = text.strip_prefix("//") {
+                if !rest.is_empty() && !rest.starts_with(|c| matches!(c, ' ' | '\t' | '/' | '!')) {
+                    return format!("// {rest}");
+                }
             }
-            _ => {
-                ctx.push_raw_indent(s, &mut res);
+        }
+        BlockComment if ctx.config.block_comments_to_line_comments => {
+            // the rest of the line would be commented out, the elements of a
+            // collection may be joined on one line.
+            let ends_line = utils::next_sibling_or_trivia(parent)
+                .is_some_and(|n| matches!(n.kind(), Space | Parbreak) && n.text().contains('\n'));
+            let in_collection = matches!(
+                parent.parent_kind(),
+                Some(Args | Array | Dict | Params | Destructuring)
+            );
+            if ends_line && !in_collection {
+                return block_to_line_comment(text);
             }
         }
+        _ => {}
+    }
+    text.to_string()
+}
+
+/// `/* a\n * b */` becomes `// a\n// b`.
+fn block_to_line_comment(text: &str) -> String {
+    let inner = text
+        .strip_prefix("/*")
+        .and_then(|t| t.strip_suffix("*/"))
+        .unwrap_or(text);
+    let lines = inner
+        .lines()
+        .map(|l| {
+            let l = l.trim();
+            l.strip_prefix('*').map(|l| l.trim_start()).unwrap_or(l)
+        })
+        .collect_vec();
+    let first = lines.iter().position(|l| !l.is_empty());
+    let last = lines.iter().rposition(|l| !l.is_empty());
+    match (first, last) {
+        (Some(first), Some(last)) => lines[first..=last]
+            .iter()
+            .map(|l| {
+                if l.is_empty() {
+                    "//".to_string()
+                } else {
+                    format!("// {l}")
+                }
+            })
+            .join("\n"),
+        _ => "//".to_string(),
     }
-    res
 }
 
 #[cfg(test)]
@@ -0,0 +1,65 @@
+use super::*;
+
+/// formats `- item`, `+ item`, `1. item` and `/ Term: description`: one space
+/// after the marker, the lines continuing the text of the item are aligned under
+/// it and the nested items are indented by one level.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_list_enum(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    let mut res = String::new();
+    let marker = parent
+        .children()
+        .find(|c| matches!(c.kind(), EnumMarker | ListMarker | TermMarker))
+        .map_or(0, |c| utils::width(c.text()));
+    // the lines of a raw block are kept as they were indented.
+    let has_raw = utils::find_child(parent, &|node| node.kind() == Raw).is_some();
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            _ if ctx.off => res.push_str(node.text()),
+            EnumMarker | ListMarker | TermMarker => {
+                ctx.push_raw_in(node.text(), &mut res);
+            }
+            Markup if !has_raw => {
+                push_item_body(s, marker + 1, ctx, &mut res);
+            }
+            _ => {
+                ctx.push_raw_indent(s, &mut res);
+            }
+        }
+    }
+    res
+}
+
+/// pushes the body of an item, the text starting at column `text_column`.
+///
+/// A line starting an item is a nested item, it gets one level of indentation,
+/// and so do the already indented lines following it. The other lines continue
+/// the text of the item.
+fn push_item_body(s: &str, text_column: usize, ctx: &mut Ctx, res: &mut String) {
+    let text_indent = " ".repeat(text_column);
+    let mut indent = text_indent.clone();
+    for (i, line) in s.split('\n').enumerate() {
+        if i > 0 {
+            ctx.push_raw_in("\n", res);
+            if !line.starts_with(' ') {
+                indent = if starts_item(line) {
+                    ctx.get_indent()
+                } else {
+                    text_indent.clone()
+                };
+            }
+            if !line.trim().is_empty() {
+                ctx.push_raw_in(&indent, res);
+            }
+        }
+        ctx.push_raw_in(line.trim_end_matches(' '), res);
+    }
+}
+
+/// true if `line` starts a list, enum or term item.
+fn starts_item(line: &str) -> bool {
+    let marker = line.split(' ').next().unwrap_or_default();
+    let numbered = marker
+        .strip_suffix('.')
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+    matches!(marker, "-" | "+" | "/") || numbered
+}
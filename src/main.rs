@@ -2,39 +2,105 @@
 #![warn(clippy::dbg_macro)]
 
 use std::{
-    ffi::OsString,
+    collections::HashMap,
+    ffi::{OsStr, OsString},
     fs::File,
-    io::{stdin, stdout, Read, Write},
+    io::{stderr, stdin, stdout, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::{Duration, Instant},
 };
 
 use lexopt::prelude::*;
-use typstfmt::{format, Config};
+use serde::{Deserialize, Serialize};
+use typstfmt::{
+    check_syntax, format, format_markdown, format_ranges, local_imports, package_dirs, Config,
+    FormatReport, Ignore, OnError, TextEdit, VersionMismatch,
+};
 
 const VERSION: &str = env!("TYPSTFMT_VERSION");
-// `DOT_CONFIG_FILE_NAME` is not created as a const due to the fact that we
-// would have to duplicate the whole string slice, because
-// `format!(".{CONFIG_FILE_NAME}")` (non-const function) cannot be applied to
-// `const` (or `static`) values in Rust (1.72.1).
 const CONFIG_FILE_NAME: &str = "typstfmt.toml";
 /// Note: used in [`confy`](https://crates.io/crates/confy) functions.
 const APP_NAME: &str = "typstfmt";
 const HELP: &str = r#"Format Typst code
 
 usage: typstfmt [options] [file...]
+       typstfmt config [--explain <key>] [--dump [--for <path>]]
+       typstfmt test-corpus <dir>
+       typstfmt install-hooks [--force] [--pre-commit-config]
 
-If no file is specified, stdin will be used.
+If no file is specified, stdin will be used. `@files.txt` reads the files to
+format from `files.txt`, one path per line. The `*.typ` files of a directory are
+formatted, recursively, on several threads. A file named like a subcommand,
+`config` for instance, is formatted if it exists.
 Files will be overwritten unless --output is passed.
 
 Options:
         -o, --output                If not specified, files will be overwritten. '-' for stdout.
+        --output-dir <dir>          Write the formatted files to the same paths under <dir>
+                                    instead of overwriting them.
+        --stdin-filepath <path>     Format stdin as if it was the file at <path>: its config is
+                                    used and nothing is formatted if the path is excluded.
         --stdout                    Same as `--output -` (Deprecated, here for compatibility).
-        --check                     Run in 'check' mode. Exits with 0 if input is
-                                    formatted correctly. Exits with 1 if formatting is required.
+        --check                     Run in 'check' mode, nothing is written. Lists the files
+                                    needing formatting and exits with 1 if there are some,
+                                    exits with 0 if the input is formatted correctly.
+        --diff                      Print a unified diff of the changes instead of writing them,
+                                    colored on a terminal, `patch -p0` applies it. Exits with 1
+                                    if there are some.
+        --output-format <format>    `text` (default), `json` to print a JSON report of the files
+                                    instead of the messages, or `jsonl` for one line per file.
         --verbose                   increase verbosity for non errors
+        --quiet                     Don't print the files already formatted nor the progress
+                                    shown on a terminal when formatting many files.
+        --baseline <file.json>      With --check, only fail on files not listed in the baseline.
+                                    If the file doesn't exist, it is created with the files
+                                    currently needing formatting.
+        --range <start:end>         Only format the smallest constructs enclosing this byte range,
+                                    may be repeated.
+        --ranges <s:e,s:e...>       Same as several --range.
+        --changed-lines-only        Only format the constructs enclosing the lines changed since
+                                    the last commit according to git, files not tracked by
+                                    git are formatted as a whole.
+        --changed                   Same as --changed-lines-only, the files being the typst files
+                                    changed since the last commit when none is given.
+        --staged                    Same as --changed, for the staged content of the files with
+                                    staged changes. Only with --check or --diff.
+        --select <rule,rule...>     Enable rules for this run, a rule is a boolean config key
+                                    written with dashes, `sort-dict-keys` for instance.
+        --ignore <rule,rule...>     Disable rules for this run, `markup-reflow` for instance.
         -v, --version               Prints the current version.
         -h, --help                  Prints this help.
         --get-global-config-path    Prints the path of the global configuration file.
         -C, --make-default-config   Create a default config file at typstfmt.toml
+        --print-config              Prints the configuration used for the current directory,
+                                    the config files merged, as TOML.
+        --project <main.typ>        Format <main.typ> and all the local files it imports or
+                                    includes, transitively.
+        --package-path <dir>        A directory of typst packages, never formatted like the
+                                    package directories of typst.
+        --include-packages          Also format files in package directories.
+        --emit-config-header        Write (or update) a comment on the first line of the files
+                                    recording the version of typstfmt and a hash of the config.
+        --embedded markdown         The files are Markdown, only the code of their ```typst
+                                    blocks is formatted. A directory gives its `*.md` files.
+
+Config command:
+        --explain <key>             Describes a config key, its type, default and allowed values.
+                                    Without it, all the keys are described.
+        --dump                      Prints the configuration used for the current directory,
+                                    each value annotated with the file it comes from.
+        --for <path>                With --dump, the configuration used for this file instead.
+        --schema                    Prints a JSON schema of the config files, for editors.
+
+Install-hooks command:
+        Writes a git pre-commit hook checking the staged typst files are formatted.
+        --force                     Overwrite an existing pre-commit hook.
+        --pre-commit-config         Print a configuration for the pre-commit framework instead.
+
+Test-corpus command:
+        Formats every `*.typ` file under <dir> and compares the result to the sibling
+        `*.expected.typ` file, prints the differences and exits with 1 if any.
 "#;
 
 enum Inputs {
@@ -48,51 +114,85 @@ struct Input {
 }
 
 impl Inputs {
-    fn read(&self) -> Box<dyn Iterator<Item = Input> + '_> {
+    /// the inputs, or the error reading each file that couldn't be read.
+    fn read(&self) -> Box<dyn Iterator<Item = Result<Input, String>> + '_> {
         match self {
             Inputs::Stdin => {
                 let mut input_buf = String::new();
                 stdin()
                     .read_to_string(&mut input_buf)
                     .expect("Couldn't read stdin.");
-                Box::new(std::iter::once(Input {
+                Box::new(std::iter::once(Ok(Input {
                     name: "stdin".to_owned(),
                     content: input_buf,
-                }))
+                })))
             }
             Inputs::Files(paths) => Box::new(paths.iter().map(|path| {
-                let mut input_buf = String::new();
-                let mut file = File::options()
-                    .read(true)
-                    .open(path)
-                    .unwrap_or_else(|err| panic!("Failed to open file {path:?}: {err}"));
-                file.read_to_string(&mut input_buf)
-                    .unwrap_or_else(|err| panic!("Couldn't read file {path:?}: {err}"));
-                Input {
+                let content = std::fs::read_to_string(path)
+                    .map_err(|err| format!("Couldn't read file {path:?}: {err}"))?;
+                Ok(Input {
                     name: path.to_string_lossy().into_owned(),
-                    content: input_buf,
-                }
+                    content,
+                })
             })),
         }
     }
+
+    /// replaces the directories by the files with the extension `ext` they
+    /// contain, recursively.
+    fn expand_dirs(self, excluded: &Excluded, ext: &str) -> Self {
+        let paths = match self {
+            Inputs::Stdin => return Inputs::Stdin,
+            Inputs::Files(paths) => paths,
+        };
+        let mut files = vec![];
+        for path in paths {
+            if Path::new(&path).is_dir() {
+                let found = source_files(Path::new(&path), excluded, ext);
+                files.extend(found.into_iter().map(PathBuf::into_os_string));
+            } else {
+                files.push(path);
+            }
+        }
+        Inputs::Files(files)
+    }
 }
 
 enum Output {
     None,
     Check,
+    /// prints a unified diff of the files needing formatting, colored for a
+    /// terminal if `color`.
+    Diff {
+        color: bool,
+    },
     Stdout,
     File(OsString),
+    /// writes a copy of each file to the same path under this directory.
+    Dir(PathBuf),
 }
 
 impl Output {
-    fn write(&self, input: &Input, formatted: &str, verbose: bool) -> Result<(), ()> {
+    /// writes `formatted`, the messages are only printed for `OutputFormat::Text`.
+    fn write(
+        &self,
+        input: &Input,
+        formatted: &str,
+        verbose: bool,
+        quiet: bool,
+        format: OutputFormat,
+    ) -> Result<(), ()> {
+        let text = format == OutputFormat::Text;
+        let (verbose, quiet) = (verbose && text, quiet || !text);
         match self {
             Output::None => {
                 // this is not stdout by the check after parsing the arguments that sets the output
                 // to stdout rather than none for stdin.
                 let path = &input.name;
                 if formatted == input.content {
-                    println!("file: {path:?} up to date.");
+                    if !quiet {
+                        println!("file: {path:?} up to date.");
+                    }
                     return Ok(());
                 }
                 let mut file = File::options()
@@ -109,7 +209,8 @@ impl Output {
             }
             Output::Check => {
                 if input.content != formatted {
-                    if verbose {
+                    // listed even without --verbose, these are what CI has to report.
+                    if text {
                         println!("{} needs formatting.", input.name);
                     }
                     return Err(());
@@ -118,6 +219,24 @@ impl Output {
                     println!("{} is already formatted.", input.name);
                 }
             }
+            Output::Diff { color } => {
+                if input.content == formatted {
+                    if verbose {
+                        println!("{} is already formatted.", input.name);
+                    }
+                    return Ok(());
+                }
+                let diff = similar::TextDiff::from_lines(&input.content, formatted)
+                    .unified_diff()
+                    .header(&input.name, &input.name)
+                    .to_string();
+                if *color {
+                    print!("{}", colorize(&diff));
+                } else {
+                    print!("{diff}");
+                }
+                return Err(());
+            }
             Output::Stdout => {
                 if verbose {
                     println!("=== {:?} ===", input.name);
@@ -128,6 +247,20 @@ impl Output {
                         panic!("Couldn't write to stdout: {}", err);
                     });
             }
+            Output::Dir(dir) => {
+                let path = mirror_path(dir, Path::new(&input.name))
+                    .expect("the inputs going up were rejected");
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).unwrap_or_else(|err| {
+                        panic!("Couldn't create output directory {parent:?}: {err}")
+                    });
+                }
+                std::fs::write(&path, formatted)
+                    .unwrap_or_else(|err| panic!("Couldn't write to file: {path:?}: {err}"));
+                if verbose {
+                    println!("file: {:?} written to {path:?}.", input.name);
+                }
+            }
             Output::File(output) => {
                 let mut file = File::options()
                     .create(true)
@@ -144,11 +277,561 @@ impl Output {
     }
 }
 
+/// the lines of a unified diff in the colors of `git diff`.
+fn colorize(diff: &str) -> String {
+    diff.split_inclusive('\n')
+        .map(|line| {
+            let color = if line.starts_with("---") || line.starts_with("+++") {
+                "1"
+            } else if line.starts_with('-') {
+                "31"
+            } else if line.starts_with('+') {
+                "32"
+            } else if line.starts_with("@@") {
+                "36"
+            } else {
+                return line.to_string();
+            };
+            let text = line.trim_end_matches('\n');
+            let end = &line[text.len()..];
+            format!("\x1b[{color}m{text}\x1b[0m{end}")
+        })
+        .collect()
+}
+
+/// How the results are reported.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+    JsonLines,
+}
+
+/// What happened to a file, in the JSON reports.
+#[derive(Serialize)]
+struct FileReport {
+    file: String,
+    /// `formatted`, `unchanged`, `would-change` in check mode or `parse-error`
+    /// when the file has syntax errors, its valid parts are still formatted.
+    status: &'static str,
+    /// the byte ranges of the input that differ from the formatted text.
+    changes: Vec<std::ops::Range<usize>>,
+    time_ms: f64,
+}
+
+impl FileReport {
+    /// `erroneous` if the input has syntax errors.
+    fn new(
+        input: &Input,
+        formatted: &str,
+        output: &Output,
+        time: Duration,
+        erroneous: bool,
+    ) -> Self {
+        let changes: Vec<_> = TextEdit::diff(&input.content, formatted)
+            .into_iter()
+            .map(|edit| edit.range)
+            .collect();
+        let status = if erroneous {
+            "parse-error"
+        } else if changes.is_empty() {
+            "unchanged"
+        } else if matches!(output, Output::Check) {
+            "would-change"
+        } else {
+            "formatted"
+        };
+        Self {
+            file: input.name.clone(),
+            status,
+            changes,
+            time_ms: time.as_secs_f64() * 1000.,
+        }
+    }
+}
+
+/// The files allowed to need formatting in check mode, for a gradual adoption.
+#[derive(Serialize, Deserialize)]
+struct Baseline {
+    unformatted: Vec<String>,
+}
+
+impl Baseline {
+    /// `None` if the file doesn't exist yet.
+    fn read(path: &Path) -> Option<Self> {
+        let content = std::fs::read_to_string(path).ok()?;
+        Some(
+            serde_json::from_str(&content)
+                .unwrap_or_else(|err| panic!("Baseline {path:?} is invalid: {err}")),
+        )
+    }
+
+    fn write(&self, path: &Path) {
+        let content = serde_json::to_string_pretty(self).unwrap();
+        std::fs::write(path, content + "\n")
+            .unwrap_or_else(|err| panic!("Couldn't write baseline {path:?}: {err}"));
+    }
+}
+
+/// the paths listed in a response file given as `@files.txt`, one per line.
+fn response_file(path: &Path) -> Vec<OsString> {
+    std::fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("Couldn't read the list of files {path:?}: {err}"))
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(OsString::from)
+        .collect()
+}
+
+/// the `*.typ` files changed since the last commit according to git: the
+/// modified ones and the untracked ones, or only the ones with staged changes if
+/// `staged`.
+fn changed_files(staged: bool) -> Vec<OsString> {
+    let git = |args: &[&str]| {
+        let output = std::process::Command::new("git")
+            .args(args)
+            .output()
+            .unwrap_or_else(|err| panic!("Couldn't run git: {err}"));
+        if !output.status.success() {
+            eprintln!("{}", String::from_utf8_lossy(&output.stderr).trim_end());
+            std::process::exit(1);
+        }
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    let diff = ["diff", "--name-only", "--relative", "--diff-filter=d"];
+    let names = if staged {
+        git(&[&diff[..], &["--cached"]].concat())
+    } else {
+        git(&[&diff[..], &["HEAD"]].concat())
+            + &git(&["ls-files", "--others", "--exclude-standard"])
+    };
+    names
+        .lines()
+        .filter(|name| name.ends_with(".typ"))
+        .map(OsString::from)
+        .collect()
+}
+
+/// parses `start:end`, byte offsets.
+fn parse_range(s: &str) -> Option<std::ops::Range<usize>> {
+    let (start, end) = s.split_once(':')?;
+    Some(start.trim().parse().ok()?..end.trim().parse().ok()?)
+}
+
+/// the byte ranges of the lines of the file at `path` changed since the last
+/// commit according to `git diff`, `None` if git doesn't track the file. With
+/// `staged`, `content` is the staged content and the changes are the staged ones.
+fn changed_ranges(path: &Path, content: &str, staged: bool) -> Option<Vec<std::ops::Range<usize>>> {
+    let git = |args: &[&str]| {
+        std::process::Command::new("git")
+            .args(args)
+            .arg(path)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+    };
+    git(&["ls-files", "--error-unmatch", "--"])?;
+    let against = if staged { "--cached" } else { "HEAD" };
+    let diff = git(&["diff", "--no-color", "--unified=0", against, "--"])?;
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line = |n: usize| line_starts.get(n).copied().unwrap_or(content.len());
+    let ranges = String::from_utf8_lossy(&diff.stdout)
+        .lines()
+        .filter_map(|l| l.strip_prefix("@@ "))
+        .filter_map(|hunk| {
+            // `@@ -old,count +new,count @@`, the count is 1 when omitted.
+            let new = hunk.split(' ').find_map(|p| p.strip_prefix('+'))?;
+            let (start, count) = match new.split_once(',') {
+                Some((start, count)) => (start.parse::<usize>().ok()?, count.parse().ok()?),
+                None => (new.parse().ok()?, 1),
+            };
+            (count > 0).then(|| line(start - 1)..line(start - 1 + count))
+        })
+        .collect();
+    Some(ranges)
+}
+
+/// the content of the file at `path` in the git index, what will be committed.
+fn staged_content(path: &Path) -> Option<String> {
+    let path = path.to_str()?;
+    let spec = if Path::new(path).is_absolute() {
+        format!(":{path}")
+    } else {
+        format!(":./{path}")
+    };
+    let output = std::process::Command::new("git")
+        .args(["show", &spec])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    String::from_utf8(output.stdout).ok()
+}
+
+/// formats only the `ranges` of `content`.
+fn format_only(content: &str, ranges: &[std::ops::Range<usize>], config: Config) -> String {
+    let mut res = content.to_string();
+    // from the end so the offsets of the next edits stay valid.
+    for edit in format_ranges(content, ranges, config).into_iter().rev() {
+        res.replace_range(edit.range, &edit.replacement);
+    }
+    res
+}
+
+/// A progress bar on stderr with the current file and the estimated remaining
+/// time, so that formatting a whole repository doesn't look hung.
+struct Progress {
+    total: usize,
+    start: Instant,
+}
+
+impl Progress {
+    /// only shown for several files with stderr on a terminal.
+    fn new(total: usize) -> Option<Progress> {
+        (total > 1 && stderr().is_terminal()).then(|| Progress {
+            total,
+            start: Instant::now(),
+        })
+    }
+
+    fn update(&self, done: usize, name: &str) {
+        const WIDTH: usize = 20;
+        let filled = WIDTH * done / self.total;
+        let eta = if done == 0 {
+            String::new()
+        } else {
+            let remaining = self.start.elapsed() / done as u32 * (self.total - done) as u32;
+            format!(" ETA {}s", remaining.as_secs())
+        };
+        eprint!(
+            "\r\x1b[K[{}{}] {done}/{}{eta} {name}",
+            "=".repeat(filled),
+            " ".repeat(WIDTH - filled),
+            self.total
+        );
+    }
+
+    fn clear(&self) {
+        eprint!("\r\x1b[K");
+    }
+}
+
+/// where the copy of `path` goes under `dir`, paths are taken relative to the
+/// current directory. `None` if `path` goes up with `..`, its copy could
+/// overwrite the one of another file.
+fn mirror_path(dir: &Path, path: &Path) -> Option<PathBuf> {
+    let current = std::env::current_dir().ok();
+    let relative = current
+        .as_ref()
+        .and_then(|current| path.strip_prefix(current).ok())
+        .unwrap_or(path);
+    if relative
+        .components()
+        .any(|c| c == std::path::Component::ParentDir)
+    {
+        return None;
+    }
+    let relative: PathBuf = relative
+        .components()
+        .filter(|c| matches!(c, std::path::Component::Normal(_)))
+        .collect();
+    Some(dir.join(relative))
+}
+
+/// true if the argument `v` is the subcommand `name`: it comes first and no file
+/// has that name, `typstfmt config` formats a file named `config` if there is one.
+fn is_subcommand(v: &OsStr, name: &str, inputs: &Inputs) -> bool {
+    v == name && matches!(inputs, Inputs::Stdin) && !Path::new(v).exists()
+}
+
+/// `typstfmt config`, gives information about the configuration.
+fn config_command(parser: &mut lexopt::Parser) -> Result<(), lexopt::Error> {
+    let mut key = None;
+    let mut dump = false;
+    let mut path = PathBuf::from(".");
+    while let Some(arg) = parser.next()? {
+        match arg {
+            Long("explain") => {
+                key = Some(parser.value()?.string()?);
+            }
+            Long("dump") => {
+                dump = true;
+            }
+            Long("for") => {
+                path = PathBuf::from(parser.value()?);
+            }
+            Long("schema") => {
+                println!("{}", Config::json_schema());
+                return Ok(());
+            }
+            _ => {
+                println!("{}", arg.unexpected());
+                println!("use -h or --help");
+                return Ok(());
+            }
+        }
+    }
+
+    if dump {
+        match Config::dump_for(&path) {
+            Ok(dump) => print!("{dump}"),
+            Err(err) => {
+                eprintln!("Invalid config {err}");
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    match key {
+        Some(key) => match Config::explain(&key) {
+            Some(explanation) => println!("{explanation}"),
+            None => {
+                let keys: Vec<_> = Config::OPTIONS.iter().map(|o| o.name).collect();
+                eprintln!(
+                    "Unknown config key {key:?}, expected one of: {}",
+                    keys.join(", ")
+                );
+                std::process::exit(1);
+            }
+        },
+        None => {
+            for option in Config::OPTIONS {
+                println!("{}\n", Config::explain(option.name).unwrap());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Start of the comment written by `--emit-config-header`.
+const CONFIG_HEADER: &str = "// formatted with typstfmt ";
+
+/// puts the `--emit-config-header` comment on the first line, replacing the
+/// previous one if any, ended like the other lines.
+fn with_config_header(formatted: &str, config: &Config) -> String {
+    let header = format!("{CONFIG_HEADER}{VERSION}, config {}", config.fingerprint());
+    let newline = if formatted.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let rest = match formatted.split_once('\n') {
+        Some((first, rest)) if first.starts_with(CONFIG_HEADER) => rest,
+        None if formatted.starts_with(CONFIG_HEADER) => "",
+        _ => formatted,
+    };
+    format!("{header}{newline}{rest}")
+}
+
+/// Files never formatted when walking directories or following imports: typst
+/// packages, formatting third party code is unwanted, and what the config
+/// excludes or the `.typstfmtignore` files ignore.
+struct Excluded {
+    packages: Vec<PathBuf>,
+    ignore: Ignore,
+}
+
+impl Excluded {
+    fn new(package_paths: Vec<PathBuf>, include_packages: bool) -> Self {
+        let packages = if include_packages {
+            vec![]
+        } else {
+            package_dirs()
+                .into_iter()
+                .chain(package_paths)
+                .filter_map(|dir| dir.canonicalize().ok())
+                .collect()
+        };
+        Self {
+            packages,
+            ignore: Ignore::of_configs(),
+        }
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.packages.iter().any(|dir| canonical.starts_with(dir)) || self.ignore.is_ignored(path)
+    }
+}
+
+/// `main` and the local files it imports or includes, transitively.
+fn project_files(main: &Path, excluded: &Excluded) -> Vec<OsString> {
+    let root = main.parent().unwrap_or(Path::new("."));
+    let mut res: Vec<PathBuf> = vec![];
+    let mut todo = vec![main.to_path_buf()];
+    while let Some(path) = todo.pop() {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if res.contains(&canonical) || excluded.contains(&path) {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            eprintln!("Warning! Couldn't read {path:?}, imported in the project.");
+            continue;
+        };
+        res.push(canonical);
+        let dir = path.parent().unwrap_or(root);
+        for import in local_imports(&content) {
+            todo.push(match import.strip_prefix('/') {
+                Some(from_root) => root.join(from_root),
+                None => dir.join(import),
+            });
+        }
+    }
+    res.into_iter().map(PathBuf::into_os_string).collect()
+}
+
+const PRE_COMMIT_HOOK: &str = r#"#!/bin/sh
+# written by `typstfmt install-hooks`, checks the staged typst files are formatted.
+exec typstfmt --check --staged
+"#;
+
+const PRE_COMMIT_CONFIG: &str = r#"repos:
+  - repo: local
+    hooks:
+      - id: typstfmt
+        name: typstfmt
+        entry: typstfmt --check --verbose
+        language: system
+        files: \.typ$
+"#;
+
+/// `typstfmt install-hooks`, sets up the git integration.
+fn install_hooks_command(parser: &mut lexopt::Parser) -> Result<(), lexopt::Error> {
+    let mut force = false;
+    while let Some(arg) = parser.next()? {
+        match arg {
+            Long("force") => force = true,
+            Long("pre-commit-config") => {
+                print!("{PRE_COMMIT_CONFIG}");
+                return Ok(());
+            }
+            _ => {
+                println!("{}", arg.unexpected());
+                println!("use -h or --help");
+                return Ok(());
+            }
+        }
+    }
+
+    // respects worktrees and `core.hooksPath`.
+    let hooks = std::process::Command::new("git")
+        .args(["rev-parse", "--git-path", "hooks"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .unwrap_or_else(|| {
+            eprintln!("Not in a git repository.");
+            std::process::exit(1);
+        });
+    let hooks = PathBuf::from(hooks.trim());
+    let path = hooks.join("pre-commit");
+    if path.exists() && !force {
+        eprintln!("{path:?} already exists, use --force to overwrite it.");
+        std::process::exit(1);
+    }
+    std::fs::create_dir_all(&hooks)
+        .unwrap_or_else(|err| panic!("Couldn't create {hooks:?}: {err}"));
+    std::fs::write(&path, PRE_COMMIT_HOOK)
+        .unwrap_or_else(|err| panic!("Couldn't write {path:?}: {err}"));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .unwrap_or_else(|err| panic!("Couldn't make {path:?} executable: {err}"));
+    }
+    println!("Installed the pre-commit hook at {path:?}.");
+    Ok(())
+}
+
+/// the files with the extension `ext` under `dir`, recursively, sorted.
+fn source_files(dir: &Path, excluded: &Excluded, ext: &str) -> Vec<PathBuf> {
+    let mut res = vec![];
+    let entries = std::fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("Couldn't read directory {dir:?}: {err}"));
+    for entry in entries {
+        let path = entry
+            .unwrap_or_else(|err| panic!("Couldn't read directory {dir:?}: {err}"))
+            .path();
+        if excluded.contains(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            res.extend(source_files(&path, excluded, ext));
+        } else if path.extension().is_some_and(|e| e == ext) {
+            res.push(path);
+        }
+    }
+    res.sort();
+    res
+}
+
+/// `typstfmt test-corpus <dir>`, compares the formatting of each file to the
+/// sibling `.expected.typ` file, returns the number of files that differ.
+fn test_corpus(dir: &Path, excluded: &Excluded, config: Config, verbose: bool) -> usize {
+    let mut passed = 0;
+    let mut failed = 0;
+    for path in source_files(dir, excluded, "typ") {
+        let name = path.to_string_lossy();
+        let Some(stem) = name.strip_suffix(".typ") else {
+            continue;
+        };
+        if stem.ends_with(".expected") {
+            continue;
+        }
+        let expected_path = PathBuf::from(format!("{stem}.expected.typ"));
+        let Ok(expected) = std::fs::read_to_string(&expected_path) else {
+            if verbose {
+                println!("{name}: no {expected_path:?}, skipped.");
+            }
+            continue;
+        };
+        let input = std::fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("Couldn't read file {path:?}: {err}"));
+        let formatted = format(&input, config);
+        if formatted == expected {
+            passed += 1;
+            if verbose {
+                println!("{name}: ok.");
+            }
+            continue;
+        }
+        failed += 1;
+        println!("{name}: differs from {expected_path:?}");
+        print!(
+            "{}",
+            similar::TextDiff::from_lines(&expected, &formatted)
+                .unified_diff()
+                .header(&expected_path.to_string_lossy(), "formatted")
+        );
+    }
+    println!("{passed} passed, {failed} failed.");
+    failed
+}
+
 fn main() -> Result<(), lexopt::Error> {
     let mut parser = lexopt::Parser::from_env();
     let mut inputs = Inputs::Stdin;
     let mut output = Output::None;
     let mut verbose = false;
+    let mut quiet = false;
+    let mut corpus = None;
+    let mut project = None;
+    let mut package_paths = vec![];
+    let mut include_packages = false;
+    let mut ranges = vec![];
+    let mut rules = vec![];
+    let mut changed_lines_only = false;
+    let mut changed_files_staged = None;
+    let mut baseline_path = None;
+    let mut emit_config_header = false;
+    let mut stdin_filepath: Option<PathBuf> = None;
+    let mut output_format = OutputFormat::Text;
+    let mut print_config = false;
+    let mut markdown = false;
     while let Some(arg) = parser.next()? {
         match arg {
             Long("version") | Short('v') => {
@@ -180,15 +863,34 @@ fn main() -> Result<(), lexopt::Error> {
                 println!("Created config file at: {CONFIG_FILE_NAME}");
                 return Ok(());
             }
+            Value(v) if is_subcommand(&v, "config", &inputs) => {
+                return config_command(&mut parser);
+            }
+            Value(v) if is_subcommand(&v, "install-hooks", &inputs) => {
+                return install_hooks_command(&mut parser);
+            }
+            Value(v) if is_subcommand(&v, "test-corpus", &inputs) => {
+                corpus = Some(PathBuf::from(parser.value()?));
+            }
             Value(v) => {
+                let new = match v.to_str().and_then(|v| v.strip_prefix('@')) {
+                    Some(list) => response_file(Path::new(list)),
+                    None => vec![v],
+                };
                 inputs = match inputs {
-                    Inputs::Stdin => Inputs::Files(vec![v]),
+                    Inputs::Stdin => Inputs::Files(new),
                     Inputs::Files(mut files) => {
-                        files.push(v);
+                        files.extend(new);
                         Inputs::Files(files)
                     }
                 };
             }
+            Long("print-config") => {
+                print_config = true;
+            }
+            Long("stdin-filepath") => {
+                stdin_filepath = Some(parser.value()?.into());
+            }
             Long("stdout") => {
                 output = Output::Stdout;
             }
@@ -200,12 +902,83 @@ fn main() -> Result<(), lexopt::Error> {
                     Output::File(value)
                 };
             }
+            Long("baseline") => {
+                baseline_path = Some(PathBuf::from(parser.value()?));
+            }
+            Long("changed-lines-only") => {
+                changed_lines_only = true;
+            }
+            Long("changed") | Long("staged") => {
+                changed_lines_only = true;
+                changed_files_staged = Some(matches!(arg, Long("staged")));
+            }
+            Long("select") | Long("ignore") => {
+                let enabled = matches!(arg, Long("select"));
+                let value = parser.value()?.string()?;
+                rules.extend(
+                    value
+                        .split(',')
+                        .map(|rule| (rule.trim().to_string(), enabled)),
+                );
+            }
+            Long("range") | Long("ranges") => {
+                let value = parser.value()?.string()?;
+                for range in value.split(',') {
+                    match parse_range(range) {
+                        Some(range) => ranges.push(range),
+                        None => {
+                            eprintln!("invalid range {range:?}, expected start:end");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+            }
+            Long("output-dir") => {
+                output = Output::Dir(PathBuf::from(parser.value()?));
+            }
             Long("verbose") => {
                 verbose = true;
             }
+            Long("output-format") => {
+                output_format = match parser.value()?.string()?.as_str() {
+                    "text" => OutputFormat::Text,
+                    "json" => OutputFormat::Json,
+                    "jsonl" => OutputFormat::JsonLines,
+                    other => {
+                        eprintln!("Unknown output format {other:?}, use text, json or jsonl.");
+                        std::process::exit(1);
+                    }
+                };
+            }
+            Long("quiet") => {
+                quiet = true;
+            }
             Long("check") => {
                 output = Output::Check;
             }
+            Long("diff") => {
+                let color = stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+                output = Output::Diff { color };
+            }
+            Long("project") => {
+                project = Some(PathBuf::from(parser.value()?));
+            }
+            Long("package-path") => {
+                package_paths.push(PathBuf::from(parser.value()?));
+            }
+            Long("include-packages") => {
+                include_packages = true;
+            }
+            Long("emit-config-header") => {
+                emit_config_header = true;
+            }
+            Long("embedded") => match parser.value()?.string()?.as_str() {
+                "markdown" => markdown = true,
+                other => {
+                    eprintln!("Unknown embedding {other:?}, only markdown is supported.");
+                    std::process::exit(1);
+                }
+            },
             _ => {
                 println!("{}", arg.unexpected());
                 println!("use -h or --help");
@@ -214,55 +987,99 @@ fn main() -> Result<(), lexopt::Error> {
         }
     }
 
+    let resolve = |path: &Path| {
+        let mut config = Config::resolve_for(path).unwrap_or_else(|err| {
+            panic!(
+                "Invalid config {err}.\n{}",
+                "You'll maybe have to delete it and use -C to create a default config file."
+            )
+        });
+        for (rule, enabled) in &rules {
+            config = config.set_rule(rule, *enabled).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            });
+        }
+        config
+    };
+    let config = resolve(Path::new("."));
+    if print_config {
+        print!("{}", toml::to_string_pretty(&config).unwrap());
+        return Ok(());
+    }
+
+    let excluded = Excluded::new(package_paths, include_packages);
+    if let Some(main) = project {
+        inputs = Inputs::Files(project_files(&main, &excluded));
+    }
+    if let (Some(staged), Inputs::Stdin) = (changed_files_staged, &inputs) {
+        let files = changed_files(staged).into_iter();
+        inputs = Inputs::Files(files.filter(|f| !excluded.contains(Path::new(f))).collect());
+    }
+    let inputs = inputs.expand_dirs(&excluded, if markdown { "md" } else { "typ" });
+
     if matches!(inputs, Inputs::Stdin) && matches!(output, Output::None) {
         output = Output::Stdout;
     }
 
-    let config = {
-        let open_config = |file_name| File::options().read(true).open(file_name);
-        let config = open_config(CONFIG_FILE_NAME);
-        let dot_config_file_name = format!(".{CONFIG_FILE_NAME}");
-        let dot_config = open_config(&dot_config_file_name);
-        let is_config_ok = config.is_ok();
-        if is_config_ok && dot_config.is_ok() {
-            eprintln!(
-                "Warning! Both {first:?} and {second:?} are present. Using {first:?}.",
-                first = CONFIG_FILE_NAME,
-                second = dot_config_file_name
-            );
+    if let Some(required) = config.required_version {
+        let version = env!("CARGO_PKG_VERSION");
+        if !required.matches(version) {
+            let message =
+                format!("the config requires typstfmt {required} but this is typstfmt {version}.");
+            match config.version_mismatch {
+                VersionMismatch::Error => {
+                    eprintln!("Error: {message}");
+                    std::process::exit(1);
+                }
+                VersionMismatch::Warn => eprintln!("Warning: {message}"),
+            }
         }
-        if let Ok(mut f) = config.or(dot_config) {
-            let mut buf = String::default();
-            let used_config_file_name = if is_config_ok {
-                CONFIG_FILE_NAME
-            } else {
-                &dot_config_file_name
-            };
-            f.read_to_string(&mut buf).unwrap_or_else(|err| {
-                panic!("Failed to read config file {used_config_file_name:?}: {err}");
-            });
-            Config::from_toml(&buf).unwrap_or_else(|err| {
-                panic!(
-                    "Config file {used_config_file_name:?} is invalid: {err}.\n{}",
-                    "You'll maybe have to delete it and use -C to create a default config file."
-                )
-            })
-        } else {
-            let config_path = confy::get_configuration_file_path(APP_NAME, APP_NAME)
-                .unwrap_or_else(|e| panic!("Error loading global configuration file: {e}"));
-            confy::load(APP_NAME, APP_NAME).unwrap_or_else(|e| {
-                panic!(
-                    "Error loading global configuration file at {}: {e}",
-                    config_path.display()
-                )
-            })
+    }
+
+    if let Some(dir) = corpus {
+        if test_corpus(&dir, &excluded, config, verbose) > 0 {
+            std::process::exit(1);
         }
-    };
+        return Ok(());
+    }
+
+    if changed_lines_only && !ranges.is_empty() {
+        eprintln!("--changed-lines-only and --range can't be used together.");
+        std::process::exit(1);
+    }
+    if markdown && (changed_lines_only || !ranges.is_empty() || emit_config_header) {
+        eprintln!(
+            "--embedded can't be used with --range, --changed-lines-only or --emit-config-header."
+        );
+        std::process::exit(1);
+    }
+    if output_format != OutputFormat::Text && matches!(output, Output::Stdout | Output::Diff { .. })
+    {
+        eprintln!("--output-format reports on stdout, it can't be used to write to stdout.");
+        std::process::exit(1);
+    }
+    if baseline_path.is_some() && !matches!(output, Output::Check) {
+        eprintln!("--baseline only works with --check.");
+        std::process::exit(1);
+    }
+    let staged = changed_files_staged == Some(true);
+    if staged && !matches!(output, Output::Check | Output::Diff { .. }) {
+        eprintln!("--staged checks what will be committed, it only works with --check or --diff.");
+        std::process::exit(1);
+    }
+    let baseline = baseline_path.as_deref().map(Baseline::read);
+    let mut unformatted = vec![];
 
     let mut exit_status = 0;
 
     match &inputs {
-        Inputs::Stdin => {}
+        Inputs::Stdin => {
+            assert!(
+                !matches!(output, Output::Dir(_)),
+                "--output-dir only works with input files, use --output for stdin.\nAborting."
+            );
+        }
         Inputs::Files(paths) => {
             assert!(
                 !(matches!(output, Output::File(_)) && paths.len() > 1),
@@ -271,16 +1088,171 @@ fn main() -> Result<(), lexopt::Error> {
         }
     }
 
+    let run_start = Instant::now();
+    let mut configs = HashMap::new();
+    let mut jobs = vec![];
     for input in inputs.read() {
-        let formatted = format(&input.content, config);
+        let mut input = match input {
+            Ok(input) => input,
+            Err(err) => {
+                eprintln!("{err}");
+                exit_status = 1;
+                continue;
+            }
+        };
+        if let Output::Dir(dir) = &output {
+            if mirror_path(dir, Path::new(&input.name)).is_none() {
+                eprintln!(
+                    "{} goes up with `..`, it can't be mirrored under --output-dir.",
+                    input.name
+                );
+                exit_status = 1;
+                continue;
+            }
+        }
+        if staged {
+            match staged_content(Path::new(&input.name)) {
+                Some(content) => input.content = content,
+                None => {
+                    eprintln!("Couldn't read the staged content of {}.", input.name);
+                    exit_status = 1;
+                    continue;
+                }
+            }
+        }
+        // the config files are looked for from the directory of each file.
+        let config = match &inputs {
+            Inputs::Stdin => match &stdin_filepath {
+                Some(path) => {
+                    input.name = path.to_string_lossy().into_owned();
+                    resolve(path)
+                }
+                None => config,
+            },
+            Inputs::Files(_) => {
+                let dir = Path::new(&input.name).parent().unwrap_or(Path::new("."));
+                *configs
+                    .entry(dir.to_path_buf())
+                    .or_insert_with(|| resolve(Path::new(&input.name)))
+            }
+        };
+        let changed = match &inputs {
+            Inputs::Files(_) if changed_lines_only => {
+                changed_ranges(Path::new(&input.name), &input.content, staged)
+            }
+            // an excluded file is written back unchanged.
+            Inputs::Stdin
+                if stdin_filepath
+                    .as_deref()
+                    .is_some_and(|p| excluded.contains(p)) =>
+            {
+                Some(vec![])
+            }
+            _ => None,
+        };
+        jobs.push((input, config, changed));
+    }
+
+    let progress = match &inputs {
+        _ if quiet || verbose => None,
+        Inputs::Files(_) => Progress::new(jobs.len()),
+        Inputs::Stdin => None,
+    };
+    let done = AtomicUsize::new(0);
+    // set when a file has syntax errors and `on_error = "abort"`.
+    let aborted = AtomicBool::new(false);
+    let format_job = |(input, config, changed): &(Input, Config, Option<Vec<_>>)| {
+        if let Some(progress) = &progress {
+            progress.update(done.fetch_add(1, Ordering::Relaxed), &input.name);
+        }
+        let start = Instant::now();
+        // checked first, the ranges and the changed lines are formatted on their own.
+        let errors = (config.on_error == OnError::Abort && !markdown)
+            .then(|| check_syntax(&input.content, *config).err())
+            .flatten();
+        if let Some(errors) = &errors {
+            eprintln!("{}: {errors}", input.name);
+            aborted.store(true, Ordering::Relaxed);
+        }
+        let mut formatted = match changed {
+            _ if errors.is_some() => input.content.clone(),
+            Some(changed) => format_only(&input.content, changed, *config),
+            None if markdown => format_markdown(&input.content, *config),
+            None if ranges.is_empty() => format(&input.content, *config),
+            None => format_only(&input.content, &ranges, *config),
+        };
+        if emit_config_header {
+            formatted = with_config_header(&formatted, config);
+        }
+        let time = start.elapsed();
+        // the reports are made here too, diffing and parsing again are as slow as
+        // formatting.
+        let mut report = FormatReport::default();
+        report.add(&input.content, &formatted);
+        let erroneous = report.parse_failures > 0;
+        let file_report = (output_format != OutputFormat::Text)
+            .then(|| FileReport::new(input, &formatted, &output, time, erroneous));
+        (formatted, report, file_report)
+    };
+    // the files are formatted on all the cores, the results are written in order.
+    #[cfg(feature = "parallel")]
+    let results: Vec<(String, FormatReport, Option<FileReport>)> = {
+        use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+        jobs.par_iter().map(format_job).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let results: Vec<(String, FormatReport, Option<FileReport>)> =
+        jobs.iter().map(format_job).collect();
+    if let Some(progress) = &progress {
+        progress.clear();
+    }
 
-        match output.write(&input, &formatted, verbose) {
+    let mut reports = vec![];
+    let mut summary = FormatReport::default();
+    for ((input, _, _), (formatted, report, file_report)) in jobs.into_iter().zip(results) {
+        summary.merge(&report);
+        match (output_format, file_report) {
+            (OutputFormat::Json, Some(file_report)) => reports.push(file_report),
+            (OutputFormat::JsonLines, Some(file_report)) => {
+                println!("{}", serde_json::to_string(&file_report).unwrap());
+            }
+            _ => {}
+        }
+        match output.write(&input, &formatted, verbose, quiet, output_format) {
             Ok(()) => {}
             Err(()) => {
-                exit_status = 1;
+                let allowed =
+                    matches!(&baseline, Some(Some(b)) if b.unformatted.contains(&input.name));
+                if !allowed {
+                    exit_status = 1;
+                }
+                unformatted.push(input.name);
             }
         }
     }
+    if aborted.load(Ordering::Relaxed) {
+        exit_status = 1;
+    }
+    summary.elapsed = run_start.elapsed();
+    if summary.files > 1 && output_format == OutputFormat::Text && !quiet {
+        eprintln!("{summary}");
+    }
+    if output_format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+    }
+    if let (Some(path), Some(None)) = (&baseline_path, &baseline) {
+        if output_format == OutputFormat::Text {
+            println!(
+                "Recorded {} files needing formatting in the baseline {path:?}.",
+                unformatted.len()
+            );
+        }
+        Baseline { unformatted }.write(path);
+        exit_status = 0;
+    }
+    if exit_status != 0 && matches!(output, Output::Check) {
+        eprintln!("Run typstfmt on these files to format them.");
+    }
     if exit_status == 0 {
         Ok(())
     } else {
@@ -0,0 +1,100 @@
+use super::*;
+
+/// An opening code fence of Markdown, ```` ```typst ```` for instance.
+struct Fence {
+    /// the spaces before the fence.
+    indent: usize,
+    marker: char,
+    len: usize,
+    /// true if the block is tagged `typst` or `typ`.
+    typst: bool,
+}
+
+impl Fence {
+    fn open(line: &str) -> Option<Self> {
+        let trimmed = line.trim_start_matches(' ');
+        let marker = trimmed.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+        let len = trimmed.len() - trimmed.trim_start_matches(marker).len();
+        let info = trimmed[len..].trim();
+        if len < 3 || (marker == '`' && info.contains('`')) {
+            return None;
+        }
+        let lang = info.split_whitespace().next().unwrap_or_default();
+        Some(Self {
+            indent: line.len() - trimmed.len(),
+            marker,
+            len,
+            typst: matches!(lang, "typst" | "typ"),
+        })
+    }
+
+    /// true if `line` is the closing fence of the block, as long as the opening
+    /// one or longer.
+    fn closes(&self, line: &str) -> bool {
+        let trimmed = line.trim_start_matches(' ');
+        let len = trimmed.len() - trimmed.trim_start_matches(self.marker).len();
+        len >= self.len && trimmed[len..].trim().is_empty()
+    }
+}
+
+/// formats the code of the fenced blocks tagged `typst` of the Markdown document
+/// `s`, see [crate::format_markdown].
+pub(crate) fn format_typst_blocks(s: &str, config: Config) -> String {
+    let lines: Vec<&str> = s.split_inclusive('\n').collect();
+    let mut res = String::with_capacity(s.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        res.push_str(line);
+        i += 1;
+        let Some(fence) = Fence::open(line) else {
+            continue;
+        };
+        // an unclosed block runs to the end of the document, it is kept as is.
+        let len = lines[i..]
+            .iter()
+            .position(|l| fence.closes(l))
+            .unwrap_or(lines.len() - i);
+        let code = &lines[i..i + len];
+        let closed = i + len < lines.len();
+        if fence.typst && closed {
+            res.push_str(&format_block(code, fence.indent, config));
+        } else {
+            res.push_str(&code.concat());
+        }
+        i += len;
+        if closed {
+            res.push_str(lines[i]);
+            i += 1;
+        }
+    }
+    res
+}
+
+/// the lines of `code` formatted, indented by the `indent` spaces of the fence.
+/// A block already formatted is kept byte for byte.
+fn format_block(code: &[&str], indent: usize, config: Config) -> String {
+    let original: String = code.concat();
+    let unindented: String = code
+        .iter()
+        .map(|l| &l[(l.len() - l.trim_start_matches(' ').len()).min(indent)..])
+        .collect();
+    let formatted = format(&unindented, config);
+    if formatted == unindented || formatted.trim().is_empty() {
+        return original;
+    }
+    let newline = if original.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut res = String::with_capacity(original.len());
+    for line in formatted.trim_end().lines() {
+        if !line.is_empty() {
+            res.push_str(&" ".repeat(indent));
+        }
+        res.push_str(line);
+        res.push_str(newline);
+    }
+    res
+}
@@ -40,9 +40,217 @@ pub(crate) fn format_content_blocks(
             _ => ctx.push_raw_indent(s, &mut res),
         }
     }
+    if !markup_has_raw && !ctx.off {
+        if breaks_content_block(parent, &res, spaced, ctx)
+            || (ctx.config.expand_blocks && spaced && !res.contains('\n'))
+        {
+            res = format_content_block_breaking(&res, ctx);
+        } else if ctx.config.collapse_single_statement_blocks && spaced {
+            if let Some(collapsed) = collapse_content_block(parent, &res, ctx) {
+                res = collapsed;
+            }
+        }
+    }
+    if ctx.config.trim_block_blank_lines && !utils::contains_directive(parent) {
+        return utils::trim_blank_edge_lines(&res, '[', ']');
+    }
+    res
+}
+
+/// true if the content block `res`, on one line, should be broken: it holds block
+/// level content, a heading or a list for instance, or it is wider than
+/// `max_inline_content_block_width`. The spaces around the content of a block
+/// holding text matter, it is only broken if there were some.
+fn breaks_content_block(parent: &LinkedNode, res: &str, spaced: bool, ctx: &Ctx) -> bool {
+    if res.contains('\n') || !res.starts_with('[') || !res.ends_with(']') {
+        return false;
+    }
+    let max_width = ctx.config.max_inline_content_block_width;
+    let too_wide = max_width > 0 && utils::width(res) > max_width;
+    holds_block_level(parent) || (too_wide && spaced && res.ends_with(" ]"))
+}
+
+/// true if the content block `parent` holds a heading or a list, they are always
+/// on their own lines.
+fn holds_block_level(parent: &LinkedNode) -> bool {
+    parent
+        .children()
+        .filter(|c| c.kind() == Markup)
+        .flat_map(|markup| markup.children())
+        .any(|c| matches!(c.kind(), Heading | ListItem | EnumItem | TermItem))
+}
+
+/// the broken content block `res` on one line, `[ content ]`, if its content is a
+/// single line, it fits and isn't block level content.
+fn collapse_content_block(parent: &LinkedNode, res: &str, ctx: &mut Ctx) -> Option<String> {
+    let body = res.strip_prefix('[')?.strip_suffix(']')?.trim();
+    if !res.contains('\n') || body.is_empty() || body.contains('\n') || holds_block_level(parent) {
+        return None;
+    }
+    let collapsed = format!("[ {body} ]");
+    let max_width = match ctx.config.max_inline_content_block_width {
+        0 => ctx.config.max_line_length,
+        width => width.min(ctx.config.max_line_length),
+    };
+    if utils::width(&collapsed) >= max_width {
+        return None;
+    }
+    ctx.stats.applied("collapse_single_statement_blocks");
+    Some(collapsed)
+}
+
+/// the content block `res` after the closing parenthesis of a call too long for
+/// its line, broken like [format_content_block_breaking] if the spaces around its
+/// content allow it, else `res`.
+pub(crate) fn break_hugged_block(parent: &LinkedNode, res: &str, ctx: &mut Ctx) -> String {
+    let spaced = res.starts_with("[ ") && res.ends_with(" ]");
+    let has_raw = utils::find_child(parent, &|node| node.kind() == Raw).is_some();
+    if !spaced || has_raw || res.contains('\n') || ctx.off {
+        return res.to_string();
+    }
+    format_content_block_breaking(res, ctx)
+}
+
+/// the content of the block on its own lines, indented, the brackets on the lines
+/// before and after it.
+fn format_content_block_breaking(res: &str, ctx: &mut Ctx) -> String {
+    ctx.stats.applied("content_block_breaking");
+    let body = res[1..res.len() - 1].trim();
+    let mut broken = String::new();
+    ctx.push_raw_in("[\n", &mut broken);
+    ctx.push_raw_in(&ctx.get_indent(), &mut broken);
+    ctx.push_raw_indent(body, &mut broken);
+    ctx.push_raw_in("\n]", &mut broken);
+    broken
+}
+
+/// formats `= Title`: one space after the marker, none ending the line.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_heading(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    let mut res = String::new();
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            _ if ctx.off => res.push_str(node.text()),
+            Space => ctx.push_raw_in(" ", &mut res),
+            Markup => ctx.push_raw_in(s.trim_end_matches(' '), &mut res),
+            _ => ctx.push_raw_in(s, &mut res),
+        }
+    }
     res
 }
 
+/// true if the child `idx` of the markup, a space, is just before or after a
+/// call of [Config::own_line_calls], `#pagebreak()` for instance.
+///
+/// Only in documents and content blocks: a line break would end a list item or
+/// a heading.
+fn next_to_own_line_call(parent: &LinkedNode, idx: usize, ctx: &Ctx) -> bool {
+    if ctx.config.mode == Mode::Conservative
+        || !matches!(parent.parent_kind(), None | Some(ContentBlock))
+    {
+        return false;
+    }
+    let children: Vec<_> = parent.children().collect();
+    let is_call = |hash: Option<&LinkedNode>, call: Option<&LinkedNode>| {
+        hash.is_some_and(|h| h.kind() == Hash)
+            && call.is_some_and(|call| {
+                call.kind() == FuncCall
+                    && call.children().next().is_some_and(|f| {
+                        f.kind() == Ident && ctx.config.own_line_calls.contains(f.text())
+                    })
+            })
+    };
+    is_call(children.get(idx + 1), children.get(idx + 2))
+        || idx >= 2 && is_call(children.get(idx - 2), children.get(idx - 1))
+}
+
+/// true if `node` is an item of a paragraph the text can be broken around, inline
+/// raw and equations, references, emphasis...
+fn is_inline(node: &typst_syntax::SyntaxNode) -> bool {
+    match node.kind() {
+        Text | Emph | Strong | Ref | Link | Escape | Shorthand | SmartQuote => true,
+        Raw => !node.clone().into_text().contains('\n'),
+        // `$ x $` is a block.
+        Equation => node.children().nth(1).is_some_and(|c| c.kind() != Space),
+        _ => false,
+    }
+}
+
+/// true if the child `idx` of the markup, a space between two inline items the
+/// wrapping of the text stops at, is reflowed like the spaces of the text.
+///
+/// Only in documents and content blocks, like [next_to_own_line_call].
+fn reflows(parent: &LinkedNode, idx: usize, ctx: &Ctx) -> bool {
+    if ctx.config.line_wrap != Wrap::Width
+        || ctx.config.mode != Mode::Canonical
+        || !matches!(parent.parent_kind(), None | Some(ContentBlock))
+    {
+        return false;
+    }
+    let siblings = parent.get().children().as_slice();
+    idx > 0 && is_inline(&siblings[idx - 1]) && siblings.get(idx + 1).is_some_and(is_inline)
+}
+
+/// true if the child `idx` of the markup, a line break, must be exactly one
+/// blank line: before a heading with [Config::blank_line_before_headings], after
+/// one with [Config::blank_line_after_headings], after a group of `#let`, `#set`
+/// and `#show` lines with [Config::blank_line_after_rules].
+///
+/// A blank line in the middle of a paragraph would split it, the one after rules
+/// is only added if they start a paragraph or come before a heading or a list.
+fn needs_blank_line(parent: &LinkedNode, idx: usize, ctx: &Ctx) -> bool {
+    if !matches!(parent.parent_kind(), None | Some(ContentBlock)) || idx == 0 {
+        return false;
+    }
+    let children: Vec<_> = parent.children().collect();
+    if !children[idx].text().contains('\n') {
+        return false;
+    }
+    let kind = |i: usize| children.get(i).map(LinkedNode::kind);
+    let is_rule = |hash: usize| {
+        kind(hash) == Some(Hash) && matches!(kind(hash + 1), Some(LetBinding | SetRule | ShowRule))
+    };
+    let is_line_break = |i: usize| kind(i) == Some(Space) && children[i].text().contains('\n');
+    let is_block_level =
+        |i: usize| matches!(kind(i), Some(Heading | ListItem | EnumItem | TermItem));
+    let before_heading = ctx.config.blank_line_before_headings && kind(idx + 1) == Some(Heading);
+    let after_heading = ctx.config.blank_line_after_headings && kind(idx - 1) == Some(Heading);
+    // comments are left where the author put them.
+    let after_rules = ctx.config.blank_line_after_rules
+        && idx >= 2
+        && is_rule(idx - 2)
+        && !is_rule(idx + 1)
+        && !matches!(kind(idx + 1), None | Some(LineComment | BlockComment))
+        && {
+            let mut start = idx - 2;
+            while start >= 3 && is_line_break(start - 1) && is_rule(start - 3) {
+                start -= 3;
+            }
+            let starts_paragraph = start == 0
+                || kind(start - 1) == Some(Parbreak)
+                || (start >= 2 && is_line_break(start - 1) && is_block_level(start - 2));
+            kind(idx) == Some(Parbreak) || starts_paragraph || is_block_level(idx + 1)
+        };
+    before_heading || after_heading || after_rules
+}
+
+/// true if the sentences of the markup `parent` are put on their own lines: a
+/// line break would end a heading.
+fn semantic_wrap(parent: &LinkedNode, ctx: &Ctx) -> bool {
+    ctx.config.line_wrap == Wrap::Semantic
+        && ctx.config.mode != Mode::Conservative
+        && parent.parent_kind() != Some(Heading)
+}
+
+/// true if the text of markup can be broken after `word` with `line_wrap = "semantic"`.
+fn ends_sentence(word: &str) -> bool {
+    word.trim_end_matches(['"', '\'', ')', ']', '_', '*'])
+        .ends_with(['.', '!', '?'])
+}
+
+/// stands for the spaces the text can't be broken at while it is split into words.
+const UNBREAKABLE: &str = "\u{1}";
+
 // break lines so they won't go over max_line_length
 #[instrument(skip_all)]
 pub(crate) fn format_markup(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
@@ -68,17 +276,56 @@ pub(crate) fn format_markup(parent: &LinkedNode, children: &[String], ctx: &mut
                 }
                 ctx.push_raw_in(&buf, &mut res);
             }
+            // a label is attached to what precedes it by a single space at most.
+            Space if node.next_sibling_kind() == Some(Label) => {
+                ctx.push_raw_in(" ", &mut res);
+            }
+            Space if !s.contains('\n') && next_to_own_line_call(parent, idx, ctx) => {
+                ctx.stats.applied("own_line_calls");
+                ctx.push_raw_in("\n", &mut res);
+            }
+            Space | Parbreak if needs_blank_line(parent, idx, ctx) => {
+                ctx.stats.applied("blank_lines");
+                ctx.push_raw_in("\n\n", &mut res);
+            }
+            Space
+                if !s.contains('\n')
+                    && semantic_wrap(parent, ctx)
+                    && matches!(node.prev_sibling_kind(), Some(Text | Emph | Strong))
+                    && ends_sentence(&res) =>
+            {
+                ctx.push_raw_in("\n", &mut res);
+                ctx.stats.lines_rebroken += 1;
+            }
+            Space if reflows(parent, idx, ctx) => {
+                let next = children[idx + 1]
+                    .split([' ', '\n'])
+                    .next()
+                    .unwrap_or_default();
+                if utils::last_line_length(&res) + 1 + utils::width(next)
+                    <= ctx.config.max_line_length
+                {
+                    ctx.push_raw_in(" ", &mut res);
+                } else {
+                    ctx.push_raw_in("\n", &mut res);
+                    ctx.stats.lines_rebroken += 1;
+                }
+            }
             Space => {
                 // careful, s has already been formatted.
                 ctx.push_raw_in(s, &mut res);
             }
-            Text if !ctx.config.line_wrap => ctx.push_raw_in(s, &mut res),
+            Text if ctx.config.line_wrap == Wrap::Off || ctx.config.mode == Mode::Conservative => {
+                ctx.push_raw_in(s, &mut res)
+            }
             Text => {
                 // We eat all the following nodes if they're in `[Space, Text, Emph, Strong, Label, Ref]`
                 // then we format ourselves breaking or spacing.
                 skip_until = Some(idx);
                 let mut this = node;
                 let mut add = s.to_string();
+                // a line break before a label would detach it from its text.
+                let mut labels = vec![];
                 loop {
                     let next = utils::find_next(&this, &|_| true);
                     match next.as_ref() {
@@ -102,6 +349,7 @@ pub(crate) fn format_markup(parent: &LinkedNode, children: &[String], ctx: &mut
                                     Equation,
                                     Emph,
                                     Raw,
+                                    Heading,
                                 ]
                                 .map(Some)
                                 .contains(&next.next_sibling_kind())
@@ -117,26 +365,53 @@ pub(crate) fn format_markup(parent: &LinkedNode, children: &[String], ctx: &mut
                     *skip_until.as_mut().unwrap() += 1;
                     this = next.unwrap();
                     match this {
+                        // the line breaks of the author are kept, if they don't
+                        // end up over the max line length.
+                        ref x
+                            if x.kind() == Space
+                                && x.text().contains('\n')
+                                && ctx.config.mode == Mode::Minimal
+                                && x.next_sibling_kind() != Some(Label) =>
+                        {
+                            add.push('\n')
+                        }
                         ref x if x.kind() == Space => add.push(' '),
+                        ref x if x.kind() == Label => {
+                            labels.push(x.text().to_string());
+                            add.push_str(&children[skip_until.unwrap()]);
+                        }
+                        // a reference stays on one line with its supplement.
+                        ref x if x.kind() == Ref => {
+                            add.push_str(&children[skip_until.unwrap()].replace(' ', UNBREAKABLE))
+                        }
                         _ => add.push_str(&children[skip_until.unwrap()]),
                     }
                 }
                 let add = add
                     .split(' ')
                     .filter(|&x| !x.is_empty() || (parent.parent_kind() == Some(ContentBlock)))
+                    .map(|x| x.replace(UNBREAKABLE, " "))
                     .collect_vec();
                 for (j, word) in add.iter().enumerate() {
                     ctx.push_raw_in(word, &mut res);
                     if let Some(next_word) = add.get(j + 1) {
-                        if utils::first_line_length(next_word)
-                        + 1 // the space we're adding
-                        + utils::last_line_length(&res)
-                            <= ctx.config.max_line_length
+                        let fits = if semantic_wrap(parent, ctx) {
+                            !ends_sentence(word)
+                        } else {
+                            utils::first_line_length(next_word)
+                                + 1 // the space we're adding
+                                + utils::last_line_length(&res)
+                                <= ctx.config.max_line_length
+                        };
+                        if fits
                             || (parent.parent_kind() == Some(Heading))
+                            // `<label>.` is a label followed by text.
+                            || labels.iter().any(|label| next_word.starts_with(label.as_str()))
                         {
                             ctx.push_raw_in(" ", &mut res);
                         } else {
                             ctx.push_raw_in("\n", &mut res);
+                            ctx.stats.lines_rebroken += 1;
                         }
                     }
                 }
@@ -148,5 +423,11 @@ pub(crate) fn format_markup(parent: &LinkedNode, children: &[String], ctx: &mut
     }
 
     ctx.lost_context();
+    if ctx.config.sort_imports && parent.parent().is_none() && !ctx.off {
+        res = imports::sort_imports(&res);
+    }
+    if align::has_marker(parent) {
+        return align::align_let_runs(parent, &res, ctx);
+    }
     res
 }
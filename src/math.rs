@@ -1,15 +1,23 @@
 use super::*;
 use crate::context::Ctx;
 use crate::format_comment_handling_disable;
-use unicode_width::UnicodeWidthStr as _;
 
 #[instrument(skip_all)]
 pub(crate) fn format_equation(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
     let mut res = String::new();
     let first_space = parent.children().nth(1);
-    let space_type = if first_space
-        .as_ref()
-        .is_some_and(|s| s.text().contains('\n'))
+    // `$ a \ b $` with a line break gets its own lines too.
+    let is_space = |node: Option<LinkedNode>| node.is_some_and(|n| n.kind() == Space);
+    let block_on_lines = is_space(first_space.clone())
+        && is_space(parent.children().rev().nth(1))
+        && children
+            .iter()
+            .zip(parent.children())
+            .any(|(s, node)| node.kind() == Math && s.contains('\n'));
+    let space_type = if block_on_lines
+        || first_space
+            .as_ref()
+            .is_some_and(|s| s.text().contains('\n'))
     {
         "\n"
     } else {
@@ -48,9 +56,54 @@ pub(crate) fn format_equation(parent: &LinkedNode, children: &[String], ctx: &mu
     res
 }
 
+/// `x ^ 2` is written `x^2`, spaces around the attachments don't change anything.
+#[instrument(skip_all)]
+pub(crate) fn format_attach(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    let mut res = String::new();
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            Space if !has_comment(parent) => {}
+            _ => ctx.push_raw_in(s, &mut res),
+        }
+    }
+    res
+}
+
+/// `a/ b` is written `a / b` and `a/b` stays tight: a space on one side of the
+/// slash gives one on both.
+#[instrument(skip_all)]
+pub(crate) fn format_frac(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    let mut res = String::new();
+    let spaced = parent.children().any(|node| node.kind() == Space);
+    if has_comment(parent) {
+        for s in children {
+            ctx.push_raw_in(s, &mut res);
+        }
+        return res;
+    }
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            Space => {}
+            Slash if spaced => ctx.push_raw_in(" / ", &mut res),
+            _ => ctx.push_raw_in(s, &mut res),
+        }
+    }
+    res
+}
+
+/// the spaces before a line comment can't be removed.
+fn has_comment(parent: &LinkedNode) -> bool {
+    parent
+        .children()
+        .any(|node| matches!(node.kind(), LineComment | BlockComment))
+}
+
 #[instrument(skip_all)]
 pub(crate) fn format_math(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
     let mut res = String::new();
+    // inline equations stay on one line, `$x$` is inline where `$ x $` is a block.
+    let inline =
+        parent.parent_kind() == Some(Equation) && parent.index() == 1 && !has_comment(parent);
 
     let align_columns = retrieve_align_columns(parent, children);
     let mut index = 0;
@@ -62,6 +115,7 @@ pub(crate) fn format_math(parent: &LinkedNode, children: &[String], ctx: &mut Ct
     for (s, node) in children.iter().zip(parent.children()) {
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
+            MathAlignPoint if !ctx.config.align_math_points => ctx.push_raw_in(s, &mut res),
             MathAlignPoint => {
                 debug_assert!(
                     align_columns[index] >= position,
@@ -79,16 +133,16 @@ pub(crate) fn format_math(parent: &LinkedNode, children: &[String], ctx: &mut Ct
                 }
 
                 ctx.push_raw_in(
-                    " ".repeat(align_columns[index] - position).as_str(),
+                    utils::padding(align_columns[index] - position).as_str(),
                     &mut res,
                 );
                 ctx.push_raw_in(s, &mut res);
-                position = align_columns[index] + s.width();
+                position = align_columns[index] + utils::width(s);
                 index += 1;
 
                 first_align = false;
             }
-            Space if s.contains('\n') => {
+            Space if s.contains('\n') && !inline => {
                 position = 0;
                 index = 0;
                 ctx.push_raw_in(s, &mut res);
@@ -98,7 +152,7 @@ pub(crate) fn format_math(parent: &LinkedNode, children: &[String], ctx: &mut Ct
                 ctx.push_raw_in(" ", &mut res);
             }
             _ => {
-                position += s.width();
+                position += utils::width(s);
                 ctx.push_raw_in(s, &mut res)
             }
         }
@@ -132,7 +186,7 @@ fn retrieve_align_columns(parent: &LinkedNode, children: &[String]) -> Vec<usize
                     // Pretend that we inserted the whitespace to ensure correct positions.
                     position = align_columns[index];
                 }
-                position += s.len();
+                position += utils::width(s);
                 index += 1
             }
             Space if s.contains('\n') => {
@@ -143,7 +197,7 @@ fn retrieve_align_columns(parent: &LinkedNode, children: &[String]) -> Vec<usize
                 position += 1;
             }
             _ => {
-                position += s.width();
+                position += utils::width(s);
             }
         }
     }
@@ -0,0 +1,75 @@
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// A list of names in the config, of functions for instance.
+///
+/// The names are interned so that the config stays `Copy`: they live for the
+/// whole program, and reading the same list or names again, when a config is
+/// reloaded for instance, reuses them rather than leaking them again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<String>", into = "Vec<String>")]
+pub struct Names(&'static [&'static str]);
+
+/// every list built by [Names::new].
+static INTERNED: Mutex<Vec<&'static [&'static str]>> = Mutex::new(Vec::new());
+
+/// every name of the lists in [INTERNED].
+static NAMES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+impl Names {
+    pub fn new<S: AsRef<str>>(names: &[S]) -> Self {
+        let mut interned = INTERNED.lock().unwrap_or_else(|err| err.into_inner());
+        let same = |list: &&&'static [&'static str]| {
+            list.len() == names.len() && list.iter().zip(names).all(|(a, b)| *a == b.as_ref())
+        };
+        if let Some(list) = interned.iter().find(same) {
+            return Names(*list);
+        }
+        let list: Vec<&'static str> = names.iter().map(|name| intern(name.as_ref())).collect();
+        let list: &'static [&'static str] = Box::leak(list.into_boxed_slice());
+        interned.push(list);
+        Names(list)
+    }
+
+    /// A list known at compile time, for defaults.
+    pub const fn from_static(names: &'static [&'static str]) -> Self {
+        Names(names)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.iter().any(|n| *n == name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'static str> {
+        self.0.iter().copied()
+    }
+}
+
+/// the interned `name`, leaked the first time only.
+fn intern(name: &str) -> &'static str {
+    let mut names = NAMES.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(interned) = names.iter().find(|n| **n == name) {
+        return *interned;
+    }
+    let interned: &'static str = Box::leak(Box::<str>::from(name));
+    names.push(interned);
+    interned
+}
+
+impl From<Vec<String>> for Names {
+    fn from(names: Vec<String>) -> Self {
+        Names::new(&names)
+    }
+}
+
+impl From<Names> for Vec<String> {
+    fn from(names: Names) -> Self {
+        names.iter().map(String::from).collect()
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::time::Duration;
+
+/// A warning emitted while formatting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Byte range in the input the diagnostic points at.
+    pub range: Range<usize>,
+    pub message: String,
+}
+
+/// The syntax errors of a document formatted by [crate::try_format] with
+/// `on_error = "abort"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxErrors(pub Vec<Diagnostic>);
+
+impl std::fmt::Display for SyntaxErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let messages: Vec<_> = self.0.iter().map(|d| d.message.as_str()).collect();
+        write!(f, "{}", messages.join(", "))
+    }
+}
+
+impl std::error::Error for SyntaxErrors {}
+
+/// The result of [crate::format_output], the formatted text with everything a
+/// caller needs to report what happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOutput {
+    pub text: String,
+    /// False if the input was already formatted.
+    pub changed: bool,
+    pub diagnostics: Vec<Diagnostic>,
+    /// Byte ranges of the input left untouched, because of `typstfmt::off`
+    /// directives or syntax errors.
+    pub skipped_regions: Vec<Range<usize>>,
+    pub stats: Stats,
+}
+
+/// Replace the text at `range` of the input by `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// Byte range in the input.
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+impl TextEdit {
+    /// The edits turning `old` into `new`, sorted and not overlapping. The lines
+    /// that changed are found by a diff then each edit is reduced to the bytes
+    /// that differ, so that an editor doesn't move the cursors in between.
+    pub fn diff(old: &str, new: &str) -> Vec<TextEdit> {
+        let diff = similar::TextDiff::from_lines(old, new);
+        let offsets = |lines: &[&str]| {
+            let mut offsets = vec![0];
+            for line in lines {
+                offsets.push(offsets.last().unwrap_or(&0) + line.len());
+            }
+            offsets
+        };
+        let old_offsets = offsets(diff.old_slices());
+        let new_offsets = offsets(diff.new_slices());
+        let mut res = vec![];
+        for op in diff.ops() {
+            let (tag, old_lines, new_lines) = op.as_tag_tuple();
+            if tag == similar::DiffTag::Equal {
+                continue;
+            }
+            let mut range = old_offsets[old_lines.start]..old_offsets[old_lines.end];
+            let mut replacement = &new[new_offsets[new_lines.start]..new_offsets[new_lines.end]];
+            let prefix = common_len(old[range.clone()].chars(), replacement.chars());
+            range.start += prefix;
+            replacement = &replacement[prefix..];
+            let suffix = common_len(old[range.clone()].chars().rev(), replacement.chars().rev());
+            range.end -= suffix;
+            replacement = &replacement[..replacement.len() - suffix];
+            res.push(TextEdit {
+                range,
+                replacement: replacement.to_string(),
+            });
+        }
+        res
+    }
+}
+
+/// the length in bytes of the common start of `a` and `b`.
+fn common_len(a: impl Iterator<Item = char>, b: impl Iterator<Item = char>) -> usize {
+    a.zip(b)
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum()
+}
+
+/// Counters collected while formatting, to find what makes a file slow or which
+/// rules shaped the output.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    /// Nodes visited, by kind.
+    pub nodes: BTreeMap<&'static str, usize>,
+    /// How many times each layout rule was applied, `args_breaking` for instance.
+    pub rules: BTreeMap<&'static str, usize>,
+    /// Lines of markup broken to stay under `max_line_length`.
+    pub lines_rebroken: usize,
+    /// Time spent in the handler of each kind, the time of the children isn't included.
+    /// Only measured by [crate::format_output_timed], and not compared by `==`.
+    pub time: BTreeMap<&'static str, Duration>,
+}
+
+// the timings differ from a run to the next, the same formatting gives equal stats.
+impl PartialEq for Stats {
+    fn eq(&self, other: &Self) -> bool {
+        self.nodes == other.nodes
+            && self.rules == other.rules
+            && self.lines_rebroken == other.lines_rebroken
+    }
+}
+
+impl Eq for Stats {}
+
+impl Stats {
+    pub(crate) fn applied(&mut self, rule: &'static str) {
+        *self.rules.entry(rule).or_default() += 1;
+    }
+}
+
+/// A summary of the formatting of many files, see [crate::format_batch].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormatReport {
+    pub files: usize,
+    /// Files the formatting changed.
+    pub reformatted: usize,
+    pub unchanged: usize,
+    /// Files with syntax errors, their valid parts are still formatted.
+    pub parse_failures: usize,
+    /// Lines of the inputs changed, removed or added.
+    pub lines_changed: usize,
+    pub elapsed: Duration,
+}
+
+impl FormatReport {
+    /// counts the file `input` formatted into `formatted`.
+    pub fn add(&mut self, input: &str, formatted: &str) {
+        self.files += 1;
+        if input == formatted {
+            self.unchanged += 1;
+        } else {
+            self.reformatted += 1;
+        }
+        if typst_syntax::parse(input).erroneous() {
+            self.parse_failures += 1;
+        }
+        let diff = similar::TextDiff::from_lines(input, formatted);
+        for op in diff.ops() {
+            let (tag, old_lines, new_lines) = op.as_tag_tuple();
+            if tag != similar::DiffTag::Equal {
+                self.lines_changed += old_lines.len().max(new_lines.len());
+            }
+        }
+    }
+
+    /// adds the counts of `other`, the file counts of several threads.
+    pub fn merge(&mut self, other: &FormatReport) {
+        self.files += other.files;
+        self.reformatted += other.reformatted;
+        self.unchanged += other.unchanged;
+        self.parse_failures += other.parse_failures;
+        self.lines_changed += other.lines_changed;
+    }
+}
+
+impl std::fmt::Display for FormatReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} files: {} reformatted, {} unchanged, {} with syntax errors, {} lines changed in {:.2}s",
+            self.files,
+            self.reformatted,
+            self.unchanged,
+            self.parse_failures,
+            self.lines_changed,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+/// Translates the byte offsets of the input of [crate::format_with_map] to
+/// offsets in its output, to put the cursors of an editor back where they were.
+///
+/// The formatting mostly moves whitespace: the characters that aren't whitespace
+/// are matched between the input and the output and the offsets are moved along
+/// with them. An offset in whitespace, or in text the formatting removed, follows
+/// the character before it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// the offset in the input, the offset in the output and the length of each
+    /// character found in both, sorted.
+    anchors: Vec<(usize, usize, usize)>,
+    input_len: usize,
+    output_len: usize,
+}
+
+impl SourceMap {
+    pub(crate) fn new(input: &str, output: &str) -> Self {
+        let chars = |s: &str| -> Vec<(usize, char)> {
+            s.char_indices()
+                .filter(|(_, c)| !c.is_whitespace())
+                .collect()
+        };
+        let (old, new) = (chars(input), chars(output));
+        let old_chars: Vec<_> = old.iter().map(|(_, c)| *c).collect();
+        let new_chars: Vec<_> = new.iter().map(|(_, c)| *c).collect();
+        // a diff of a huge document changed everywhere stops early, the map is
+        // less precise rather than slow. There is no clock in
+        // `wasm32-unknown-unknown`.
+        #[cfg(not(target_arch = "wasm32"))]
+        let deadline = Some(std::time::Instant::now() + Duration::from_millis(500));
+        #[cfg(target_arch = "wasm32")]
+        let deadline = None;
+        let mut anchors = vec![];
+        for op in similar::capture_diff_slices_deadline(
+            similar::Algorithm::Myers,
+            &old_chars,
+            &new_chars,
+            deadline,
+        ) {
+            if let similar::DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } = op
+            {
+                anchors.extend((0..len).map(|i| {
+                    let (start, c) = old[old_index + i];
+                    (start, new[new_index + i].0, c.len_utf8())
+                }));
+            }
+        }
+        Self {
+            anchors,
+            input_len: input.len(),
+            output_len: output.len(),
+        }
+    }
+
+    /// the offset in the output of the byte `offset` of the input, the end of the
+    /// input maps to the end of the output.
+    pub fn map_offset(&self, offset: usize) -> usize {
+        if offset >= self.input_len {
+            return self.output_len;
+        }
+        let i = self
+            .anchors
+            .partition_point(|(start, _, _)| *start <= offset);
+        match i.checked_sub(1).map(|i| self.anchors[i]) {
+            Some((start, output, len)) if offset < start + len => output + offset - start,
+            Some((_, output, len)) => output + len,
+            None => 0,
+        }
+    }
+}
@@ -1,13 +1,55 @@
-use typst_syntax::ast::AstNode;
-
 use super::*;
 use crate::utils::{get_next_ignoring, next_is_ignoring, Btype};
 
 #[instrument(skip_all)]
 /// format args using [format_args_tight] or [format_args_breaking] depending on the context.
 /// - if number of args is 0, format tight.
-/// - if line gets above max_length - 7 in tight mode, format breaking. (see TODO: why plus 7)
+/// - if line gets above max_length in tight mode, format breaking, see [tight_width].
+/// - if there are more elements than `max_args_inline` and the like, format breaking.
 pub(crate) fn format_args(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    let sort = match parent.kind() {
+        Dict => ctx.config.sort_dict_keys,
+        Args => {
+            ctx.config.sort_named_args
+                && matches!(parent.parent_kind(), Some(FuncCall | SetRule))
+                && utils::block_type(parent) != Btype::Math
+        }
+        _ => false,
+    };
+    if ctx.config.preserve_alignment && matches!(parent.kind(), Args | Array) && !ctx.off {
+        if align::is_hand_aligned(parent) {
+            return align::format_hand_aligned(parent, children, ctx);
+        }
+        align::keep_matrix_row(parent, children, ctx);
+    }
+    if let Some(name) = callee(parent) {
+        if ctx.config.verbatim_args_calls.contains(&name) {
+            ctx.stats.applied("verbatim_args_calls");
+            return deep_no_format(parent);
+        }
+        if ctx.config.always_break_calls.contains(&name) && !ctx.off {
+            ctx.stats.applied("always_break_calls");
+            return format_args_breaking(parent, children, ctx);
+        }
+        if ctx.config.never_break_calls.contains(&name) {
+            ctx.stats.applied("never_break_calls");
+            return format_args_tight(parent, children, ctx);
+        }
+    }
+    if sort && sorting::can_sort(parent) {
+        return sorting::format_sorted(parent, children, ctx);
+    }
+    if matches!(parent.kind(), Args | Dict | Array) {
+        if let Some(marker) = align::collection_marker(parent) {
+            return align::format_aligned(parent, children, Some(&marker), ctx);
+        }
+    }
+    if parent.kind() == Args && ctx.config.align_table_cells {
+        if let Some(res) = align::format_table(parent, children, ctx) {
+            return res;
+        }
+    }
+
     // check if any children is markup and contains a linebreak, if so, breaking
     // let mut res = vec![];
     // utils::find_children(&mut res, parent, &|c| {
@@ -23,6 +65,17 @@ pub(crate) fn format_args(parent: &LinkedNode, children: &[String], ctx: &mut Ct
         return format_args_breaking(parent, children, ctx);
     }
 
+    if ctx.config.magic_trailing_comma && one_per_line(parent, ctx) {
+        if has_magic_trailing_comma(parent) {
+            ctx.stats.applied("magic_trailing_comma");
+            return format_args_breaking(parent, children, ctx);
+        }
+        let res = format_args_tight(parent, children, ctx);
+        if tight_width(&res, ctx) < ctx.config.max_line_length {
+            return res;
+        }
+    }
+
     let number_of_args = parent
         .children()
         .filter_map(|node| {
@@ -47,28 +100,102 @@ pub(crate) fn format_args(parent: &LinkedNode, children: &[String], ctx: &mut Ct
         return format_args_tight(parent, children, ctx);
     }
 
+    let keep_layout = ctx.config.mode == Mode::Conservative
+        || (ctx.config.keep_collection_layout && matches!(parent.kind(), Args | Array | Dict));
+    if keep_layout {
+        return if utils::source_is_multiline(parent) {
+            format_args_breaking(parent, children, ctx)
+        } else {
+            format_args_tight(parent, children, ctx)
+        };
+    }
+
+    if too_many_elements(parent, ctx) {
+        ctx.stats.applied("max_elements_inline");
+        return format_args_breaking(parent, children, ctx);
+    }
+
     let res = format_args_tight(parent, children, ctx);
-    // TODO: why plus 7
-    // why plus 7? if you remove it you'll notice the official example
-    // fails, since the inner line is broken before reaching the limit,
-    // it's difficult to have a condition like "if one of my child had
-    // to break in order to not go over the max_len, break" So I had to
-    // resort to this hack. A more meaningful approach is desired.
-    if utils::max_line_length(&res) + 7 >= ctx.config.max_line_length {
+    let head = without_trailing_blocks(parent, children, &res);
+    if tight_width(head, ctx) >= ctx.config.max_line_length
+        || (ctx.config.mode == Mode::Minimal && utils::source_is_multiline(parent))
+    {
         return format_args_breaking(parent, children, ctx);
     }
+    if head.len() < res.len() && tight_width(&res, ctx) >= ctx.config.max_line_length {
+        ctx.stats.applied("hug_trailing_block");
+        return with_broken_trailing_blocks(parent, children, ctx);
+    }
     res
 }
 
+/// the arguments formatted tight, the content blocks after the closing
+/// parenthesis hugging it with their content broken.
+fn with_broken_trailing_blocks(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    let mut children = children.to_vec();
+    let trailing = parent
+        .children()
+        .skip_while(|c| c.kind() != RightParen)
+        .filter(|c| c.kind() == ContentBlock);
+    for block in trailing {
+        let s = &children[block.index()];
+        children[block.index()] = markup::break_hugged_block(&block, s, ctx);
+    }
+    format_args_tight(parent, &children, ctx)
+}
+
+/// the width of the arguments formatted tight, once in place.
+///
+/// At least 7 columns are left for what surrounds them: the arguments of
+/// `#let name = f(..)` at the top level would otherwise only be broken once
+/// over the limit, as the `let` is formatted after them.
+pub(crate) fn tight_width(res: &str, ctx: &Ctx) -> usize {
+    ctx.width_in_place(res).max(utils::max_line_length(res) + 7)
+}
+
+/// the arguments `res` formatted tight without the content blocks after the
+/// closing parenthesis, `#figure(..)[..]`: they stay against it and break their
+/// own content, breaking the arguments in parentheses wouldn't shorten them.
+fn without_trailing_blocks<'a>(parent: &LinkedNode, children: &[String], res: &'a str) -> &'a str {
+    if parent.kind() != Args {
+        return res;
+    }
+    let trailing: usize = children
+        .iter()
+        .zip(parent.children())
+        .skip_while(|(_, c)| c.kind() != RightParen)
+        .skip(1)
+        .filter(|(_, c)| c.kind() == ContentBlock)
+        .map(|(s, _)| s.len())
+        .sum();
+    res.get(..res.len().saturating_sub(trailing)).unwrap_or(res)
+}
+
 pub(crate) fn format_args_tight(
     parent: &LinkedNode<'_>,
     children: &[String],
     ctx: &mut Ctx,
 ) -> String {
     let mut res = String::new();
-    let is_destruct_and_one_arg = typst_syntax::ast::Destructuring::from_untyped(parent)
-        .is_some_and(|x| x.bindings().count() == 1);
+    // `(a, _)` and `(a, ..)` bind one name but have two items.
+    let is_destruct_and_one_arg = parent.kind() == Destructuring
+        && parent
+            .children()
+            .filter(|c| {
+                !matches!(
+                    c.kind(),
+                    LeftParen | RightParen | Comma | Space | LineComment | BlockComment
+                )
+            })
+            .count()
+            == 1;
     let mut missing_trailing = is_destruct_and_one_arg;
+    let spaced = ctx.config.space_inside_parens
+        && utils::block_type(parent) != Btype::Math
+        && parent
+            .children()
+            .find(|c| c.kind() == LeftParen)
+            .is_some_and(|paren| !next_is_ignoring(&paren, RightParen, &[Space]));
 
     for (s, node) in children.iter().zip(parent.children()) {
         let is_last =
@@ -77,6 +204,14 @@ pub(crate) fn format_args_tight(
         match node.kind() {
             _ if ctx.off => res.push_str(node.text()),
             Space => {}
+            LeftParen if spaced => {
+                ctx.push_raw_in(s, &mut res);
+                ctx.push_raw_in(" ", &mut res);
+            }
+            RightParen if spaced => {
+                ctx.push_raw_in(" ", &mut res);
+                ctx.push_raw_in(s, &mut res);
+            }
             Comma => {
                 let is_last_comma = utils::find_next(&node, &|x| x.kind() == Comma).is_none();
                 let is_trailing =
@@ -122,6 +257,10 @@ pub(crate) fn format_args_breaking(
     children: &[String],
     ctx: &mut Ctx,
 ) -> String {
+    if aligns_named_values(parent, ctx) {
+        return align::format_aligned(parent, children, None, ctx);
+    }
+    ctx.stats.applied("args_breaking");
     let mut res = String::new();
     let mut is_trailing_block = TrailingBlockDetect::default();
     let is_block_math = utils::block_type(parent) == Btype::Math;
@@ -129,6 +268,10 @@ pub(crate) fn format_args_breaking(
     let mut missing_trailing_comma = !(is_parenthesized || is_block_math);
     // only used with experimental flag in config for now
     let mut consecutive_items = 0;
+    let one_per_line = one_per_line(parent, ctx);
+    let long_string = long_string_arg(parent, children, ctx);
+    let is_long_string =
+        |node: Option<LinkedNode>| node.is_some_and(|n| Some(n.index()) == long_string);
 
     for (s, node) in children.iter().zip(parent.children()) {
         let is_last =
@@ -149,6 +292,10 @@ pub(crate) fn format_args_breaking(
                 }
                 ctx.push_raw_in(s, &mut res);
             }
+            BlockComment if utils::comment_precedes_item(&node) => {
+                ctx.push_raw_in(s, &mut res);
+                ctx.push_raw_in(" ", &mut res);
+            }
             LineComment | BlockComment => {
                 consecutive_items = 0;
                 if utils::prev_is_ignoring(&node, LineComment, &[Space])
@@ -168,6 +315,10 @@ pub(crate) fn format_args_breaking(
                             ctx.push_raw_in(&ctx.get_indent(), &mut res);
                             ctx.push_raw_in(s, &mut res);
                         }
+                        _ if node.kind() == LineComment => {
+                            ctx.push_raw_in(&ctx.comment_gap(), &mut res);
+                            ctx.push_raw_in(s, &mut res);
+                        }
                         _ => {
                             ctx.push_raw_in(" ", &mut res);
                             ctx.push_raw_in(s, &mut res);
@@ -195,6 +346,14 @@ pub(crate) fn format_args_breaking(
                     // no indent
                     ctx.push_raw_in(s, &mut res);
                     ctx.push_raw_in("\n", &mut res);
+                } else if one_per_line
+                    || is_long_string(utils::get_prev_ignoring(&node, &[Space]))
+                    || is_long_string(get_next_ignoring(&node, &[Space]))
+                {
+                    ctx.push_raw_in(s, &mut res);
+                    ctx.push_raw_in("\n", &mut res);
+                    ctx.push_raw_in(&ctx.get_indent(), &mut res);
+                    consecutive_items = 0;
                 } else if !ctx.config.experimental_args_breaking_consecutive {
                     ctx.push_raw_in(s, &mut res);
                     ctx.push_raw_in(" ", &mut res);
@@ -229,3 +388,136 @@ pub(crate) fn format_args_breaking(
     }
     res
 }
+
+/// true if `align_named_values` applies to the broken collection: a dictionary
+/// or arguments with several named entries, out of math.
+fn aligns_named_values(parent: &LinkedNode, ctx: &Ctx) -> bool {
+    ctx.config.align_named_values
+        && !ctx.off
+        && matches!(parent.kind(), Args | Dict)
+        && utils::block_type(parent) != Btype::Math
+        && parent
+            .children()
+            .filter(|c| matches!(c.kind(), Named | Keyed))
+            .count()
+            >= 2
+}
+
+/// formats `..args` and the `..` sinks of destructuring patterns, no space after
+/// the dots.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_spread(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    if parent
+        .children()
+        .any(|c| matches!(c.kind(), LineComment | BlockComment))
+    {
+        return format_default(parent, children, ctx);
+    }
+    let mut res = String::new();
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            Space => {}
+            _ => ctx.push_raw_in(s, &mut res),
+        }
+    }
+    res
+}
+
+/// true if `parent` has more elements than it may have on one line:
+/// `max_args_inline` for the arguments of calls out of math,
+/// `max_dict_entries_inline` and `max_array_items_inline`.
+fn too_many_elements(parent: &LinkedNode, ctx: &Ctx) -> bool {
+    let max = match parent.kind() {
+        Args if utils::block_type(parent) != Btype::Math => ctx.config.max_args_inline,
+        Dict => ctx.config.max_dict_entries_inline,
+        Array => ctx.config.max_array_items_inline,
+        _ => 0,
+    };
+    max > 0 && elements_in_parens(parent) > max
+}
+
+/// the number of elements in the parentheses of `parent`, the trailing content
+/// blocks of a call aren't counted.
+fn elements_in_parens(parent: &LinkedNode) -> usize {
+    parent
+        .children()
+        .skip_while(|c| c.kind() != LeftParen)
+        .take_while(|c| c.kind() != RightParen)
+        .filter(|c| {
+            !matches!(
+                c.kind(),
+                Comma | Space | LeftParen | LineComment | BlockComment
+            )
+        })
+        .count()
+}
+
+/// the name of the function called with the arguments `parent`, `cetz.canvas`
+/// for a function of a module.
+fn callee(parent: &LinkedNode) -> Option<String> {
+    if parent.kind() != Args {
+        return None;
+    }
+    let call = parent.parent().filter(|p| p.kind() == FuncCall)?;
+    let name = call.children().next()?.get().clone().into_text();
+    Some(name.chars().filter(|c| !c.is_whitespace()).collect())
+}
+
+/// true if the collection has one element per line when broken: the parameters
+/// of closures, the arguments of `always_break_calls`, and with
+/// `magic_trailing_comma` the other collections but parenthesized expressions and
+/// arguments in math, and the collections with too many elements to stay inline.
+fn one_per_line(parent: &LinkedNode, ctx: &Ctx) -> bool {
+    parent.kind() == Params
+        || callee(parent).is_some_and(|name| ctx.config.always_break_calls.contains(&name))
+        || too_many_elements(parent, ctx)
+        || (ctx.config.magic_trailing_comma
+            && matches!(parent.kind(), Args | Dict | Array)
+            && utils::block_type(parent) != Btype::Math)
+}
+
+/// true if the source has a comma after the last element. The one of `(a,)` is
+/// required to make an array, it doesn't count.
+fn has_magic_trailing_comma(parent: &LinkedNode) -> bool {
+    let Some(comma) = parent.children().filter(|c| c.kind() == Comma).last() else {
+        return false;
+    };
+    let elements = parent
+        .children()
+        .filter(|c| {
+            !matches!(
+                c.kind(),
+                Comma | Space | LeftParen | RightParen | LineComment | BlockComment
+            )
+        })
+        .count();
+    utils::next_is_ignoring(&comma, RightParen, &[Space, LineComment, BlockComment])
+        && !(parent.kind() == Array && elements == 1)
+}
+
+/// the index of the string literal argument making the arguments too long to fit
+/// on one line, if any.
+///
+/// Strings are never split, instead this string is put alone on its line.
+fn long_string_arg(parent: &LinkedNode, children: &[String], ctx: &Ctx) -> Option<usize> {
+    let width = children
+        .iter()
+        .zip(parent.children())
+        .map(|(s, node)| match node.kind() {
+            Space | LeftParen | RightParen => 0,
+            Comma => 2,
+            _ => utils::max_line_length(s),
+        })
+        .sum::<usize>()
+        + ctx.config.indent_space;
+    if width < ctx.config.max_line_length {
+        return None;
+    }
+    children
+        .iter()
+        .zip(parent.children())
+        .filter(|(_, node)| node.kind() == Str)
+        .max_by_key(|(s, _)| utils::max_line_length(s))
+        .filter(|(s, _)| width - utils::max_line_length(s) < ctx.config.max_line_length)
+        .map(|(_, node)| node.index())
+}
@@ -0,0 +1,171 @@
+use std::ops::Range;
+
+use super::*;
+
+/// the deepest leaf containing the byte right before `offset`.
+pub(crate) fn leaf_before<'a>(node: &LinkedNode<'a>, offset: usize) -> Option<LinkedNode<'a>> {
+    if node.children().len() == 0 {
+        return Some(node.clone());
+    }
+    node.children()
+        .find(|c| c.offset() < offset && offset <= c.range().end)
+        .and_then(|c| leaf_before(&c, offset))
+}
+
+/// the smallest node containing all of `range`.
+pub(crate) fn enclosing<'a>(node: &LinkedNode<'a>, range: &Range<usize>) -> Option<LinkedNode<'a>> {
+    if range.start < node.offset() || node.range().end < range.end {
+        return None;
+    }
+    node.children()
+        .find_map(|c| enclosing(&c, range))
+        .or_else(|| Some(node.clone()))
+}
+
+/// true for the nodes that make sense to format on their own: statements,
+/// calls, blocks, collections...
+pub(crate) fn is_formattable(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        Markup
+            | Code
+            | CodeBlock
+            | ContentBlock
+            | FuncCall
+            | Args
+            | Array
+            | Dict
+            | Parenthesized
+            | Binary
+            | Closure
+            | LetBinding
+            | SetRule
+            | ShowRule
+            | Conditional
+            | WhileLoop
+            | ForLoop
+            | ModuleImport
+            | ModuleInclude
+            | FuncReturn
+            | Equation
+            | Heading
+            | ListItem
+            | EnumItem
+            | TermItem
+    )
+}
+
+/// the smallest formattable node containing all of `range`.
+pub(crate) fn formattable_enclosing<'a>(
+    root: &LinkedNode<'a>,
+    range: &Range<usize>,
+) -> Option<LinkedNode<'a>> {
+    let mut node = enclosing(root, range)?;
+    while !is_formattable(node.kind()) {
+        node = node.parent()?.clone();
+    }
+    Some(node)
+}
+
+/// formats the smallest formattable nodes enclosing each range, edits of nodes
+/// inside an other one already formatted are dropped.
+pub(crate) fn format_ranges(s: &str, ranges: &[Range<usize>], config: Config) -> Vec<TextEdit> {
+    let tree = parse(s);
+    let root = LinkedNode::new(&tree);
+    let mut nodes: Vec<LinkedNode> = ranges
+        .iter()
+        .filter(|range| range.start <= range.end && range.end <= s.len())
+        .filter_map(|range| formattable_enclosing(&root, range))
+        .collect();
+    // outer nodes first.
+    nodes.sort_by_key(|node| (node.offset(), std::cmp::Reverse(node.range().end)));
+    let mut end = 0;
+    let mut edits = vec![];
+    for node in nodes {
+        if node.offset() < end {
+            // inside the previous one.
+            continue;
+        }
+        end = node.range().end;
+        edits.extend(format_node(s, &root, &node, config));
+    }
+    edits
+}
+
+/// true if the last directive before `offset` disabled formatting.
+pub(crate) fn is_off_at(root: &LinkedNode, s: &str, offset: usize) -> bool {
+    utils::comment_ranges(root)
+        .into_iter()
+        .filter(|range| range.end <= offset)
+        .map(|range| &s[range])
+        .filter_map(utils::comment_directive)
+        .filter(|d| matches!(d, Directive::Off | Directive::On))
+        .last()
+        == Some(Directive::Off)
+}
+
+/// formats `node` alone, the lines after the first are indented like the line
+/// the node starts on.
+///
+/// Returns `None` if the node can't be formatted on its own (syntax errors, tabs,
+/// `\r\n`, formatting disabled) or if it is already formatted.
+pub(crate) fn format_node(
+    s: &str,
+    root: &LinkedNode,
+    node: &LinkedNode,
+    config: Config,
+) -> Option<TextEdit> {
+    let original = &s[node.range()];
+    // tabs and line endings are only replaced when formatting the whole document.
+    if node.erroneous() || original.contains(['\t', '\r']) || is_off_at(root, s, node.offset()) {
+        return None;
+    }
+    let line_start = s[..node.offset()].rfind('\n').map_or(0, |i| i + 1);
+    let indent: String = s[line_start..].chars().take_while(|c| *c == ' ').collect();
+    let config = Config {
+        max_line_length: config.max_line_length.saturating_sub(utils::width(&indent)),
+        ..config
+    };
+    let mut ctx = Ctx::from_config(config);
+    let formatted = utils::trim_line_ends(&visit(node, &mut ctx));
+    if ctx.off {
+        return None;
+    }
+
+    let mut replacement = String::new();
+    for (i, line) in formatted.split_inclusive('\n').enumerate() {
+        if i > 0 && !line.trim().is_empty() {
+            replacement.push_str(&indent);
+        }
+        replacement.push_str(line);
+    }
+    if replacement == original {
+        return None;
+    }
+    Some(TextEdit {
+        range: node.range(),
+        replacement,
+    })
+}
+
+/// the construct to format after the character right before `offset` was typed.
+pub(crate) fn typed_construct<'a>(root: &LinkedNode<'a>, offset: usize) -> Option<LinkedNode<'a>> {
+    let leaf = leaf_before(root, offset)?;
+    match leaf.kind() {
+        RightBrace | RightBracket if leaf.range().end == offset => leaf.parent().cloned(),
+        Space | Parbreak
+            if leaf
+                .text()
+                .get(..offset - leaf.offset())
+                .is_some_and(|typed| typed.ends_with('\n')) =>
+        {
+            // the line that was just finished.
+            let prev = leaf.prev_sibling()?;
+            match prev.kind() {
+                Text | Space | Parbreak | Linebreak | SmartQuote | Escape | Shorthand => None,
+                _ => Some(prev),
+            }
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,77 @@
+use super::*;
+
+/// Formats the code of the raw blocks tagged with a language, ```` ```rust ````
+/// for instance, see [crate::format_output_with_embedded].
+pub trait EmbeddedFormatter {
+    /// the formatted `code` of a raw block tagged `lang`, `None` to keep it as
+    /// written.
+    fn format(&self, lang: &str, code: &str) -> Option<String>;
+}
+
+/// puts back the raw blocks of `input`, at `originals`, in `formatted` byte for
+/// byte, replacing the tabs, the line endings and the indentation the formatting
+/// changed. The code of the blocks the `formatters` know the language of is
+/// formatted.
+pub(crate) fn restore(
+    input: &str,
+    originals: Vec<std::ops::Range<usize>>,
+    formatted: &str,
+    formatters: &[&dyn EmbeddedFormatter],
+) -> String {
+    // without raw blocks there is nothing to put back, nor to parse.
+    if originals.is_empty() {
+        return formatted.to_string();
+    }
+    let ranges = raw_ranges(&LinkedNode::new(&parse(formatted)));
+    if originals.len() != ranges.len() {
+        return formatted.to_string();
+    }
+    let mut res = String::new();
+    let mut last = 0;
+    for (original, range) in originals.into_iter().zip(ranges) {
+        let original = &input[original];
+        res.push_str(&formatted[last..range.start]);
+        match formatters
+            .iter()
+            .find_map(|f| format_embedded(original, *f))
+        {
+            Some(raw) => res.push_str(&raw),
+            None => res.push_str(original),
+        }
+        last = range.end;
+    }
+    res.push_str(&formatted[last..]);
+    res
+}
+
+/// the raw block with its code formatted, `None` if it has no language or no
+/// line of code.
+fn format_embedded(raw: &str, formatter: &dyn EmbeddedFormatter) -> Option<String> {
+    let fence = &raw[..raw.len() - raw.trim_start_matches('`').len()];
+    if fence.len() < 3 || raw.len() < 2 * fence.len() || !raw.ends_with(fence) {
+        return None;
+    }
+    let (lang, code) = raw[fence.len()..raw.len() - fence.len()].split_once('\n')?;
+    let newline = if lang.ends_with('\r') { "\r\n" } else { "\n" };
+    let lang = lang.trim_end_matches('\r');
+    if lang.trim().is_empty() {
+        return None;
+    }
+    // the indentation of the closing fence.
+    let code_end = code.trim_end_matches([' ', '\t']);
+    let closing_indent = &code[code_end.len()..];
+    let code = code_end.strip_suffix(newline).unwrap_or(code_end);
+    let code = formatter.format(lang.trim(), code)?;
+    let code = code.trim_end_matches('\n').replace('\n', newline);
+    Some(format!(
+        "{fence}{lang}{newline}{code}{newline}{closing_indent}{fence}"
+    ))
+}
+
+/// the ranges of the raw blocks in `node`.
+pub(crate) fn raw_ranges(node: &LinkedNode) -> Vec<std::ops::Range<usize>> {
+    match node.kind() {
+        Raw => vec![node.range()],
+        _ => node.children().flat_map(|c| raw_ranges(&c)).collect(),
+    }
+}
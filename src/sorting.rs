@@ -0,0 +1,184 @@
+use super::*;
+use crate::doc::{self, Doc};
+
+/// An item of a collection with the comments attached to it.
+pub(crate) struct Entry {
+    pub(crate) kind: SyntaxKind,
+    /// the name of a named argument or the key of a dictionary entry.
+    pub(crate) key: Option<String>,
+    /// the key as written, quotes included.
+    pub(crate) key_text: Option<String>,
+    pub(crate) text: String,
+    /// comments on their own line before the entry, or before it on its line.
+    pub(crate) leading: Vec<String>,
+    /// comment on the line of the entry.
+    pub(crate) trailing: Option<String>,
+}
+
+/// formats a collection with its entries sorted by key, see [sort].
+///
+/// The entries are put on one line like [params::format_args_tight] if they fit
+/// and there are no comments, otherwise one entry per line.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_sorted(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    ctx.stats.applied("sorting");
+    let (mut entries, dangling, after) = entries(parent, children);
+    sort(&mut entries);
+
+    let has_comments = !dangling.is_empty()
+        || entries
+            .iter()
+            .any(|e| !e.leading.is_empty() || e.trailing.is_some());
+
+    if !has_comments {
+        let children = in_sorted_order(parent, children, &entries);
+        let res = params::format_args_tight(parent, &children, ctx);
+        if params::tight_width(&res, ctx) < ctx.config.max_line_length {
+            return res;
+        }
+    }
+
+    let mut items = vec![Doc::SoftLine];
+    for (i, entry) in entries.iter().enumerate() {
+        for comment in &entry.leading {
+            items.push(Doc::text(comment));
+            items.push(Doc::HardLine);
+        }
+        items.push(Doc::text(&entry.text));
+        let last = i + 1 == entries.len();
+        items.push(if last {
+            Doc::IfBreak(",".to_string())
+        } else {
+            Doc::text(",")
+        });
+        if let Some(comment) = &entry.trailing {
+            let gap = if comment.starts_with("//") {
+                ctx.comment_gap()
+            } else {
+                " ".to_string()
+            };
+            items.push(Doc::text(gap + comment));
+        }
+        if !last {
+            items.push(Doc::Line);
+        }
+    }
+    for comment in &dangling {
+        items.push(Doc::HardLine);
+        items.push(Doc::text(comment));
+    }
+    let docs = vec![
+        Doc::text("("),
+        Doc::Nest(items),
+        Doc::SoftLine,
+        Doc::text(")"),
+    ];
+    // see params::format_args for the plus 7.
+    let width = ctx.config.max_line_length.saturating_sub(8);
+    let mut res = String::new();
+    ctx.push_raw_in(
+        &doc::render(&Doc::broken(docs), width, &ctx.get_indent()),
+        &mut res,
+    );
+    for s in &after {
+        ctx.push_raw_in(s, &mut res);
+    }
+    res
+}
+
+/// the formatted children of a collection without comments, its entries replaced
+/// by the sorted `entries`.
+fn in_sorted_order(parent: &LinkedNode, children: &[String], entries: &[Entry]) -> Vec<String> {
+    let mut sorted = entries.iter().map(|e| &e.text);
+    let mut closed = false;
+    children
+        .iter()
+        .zip(parent.children())
+        .map(|(s, node)| match node.kind() {
+            _ if closed => s.clone(),
+            RightParen => {
+                closed = true;
+                s.clone()
+            }
+            LeftParen | Space | Comma | Colon => s.clone(),
+            _ => sorted.next().unwrap_or(s).clone(),
+        })
+        .collect()
+}
+
+/// true if the collection can be sorted, it must have entries (`(:)` is left
+/// alone) and must not contain directives.
+pub(crate) fn can_sort(parent: &LinkedNode) -> bool {
+    parent
+        .children()
+        .any(|c| matches!(c.kind(), Named | Keyed | Spread))
+        && !parent.children().any(|c| utils::directive(&c).is_some())
+}
+
+/// sorts the entries by key, entries without a key (positional arguments) come
+/// first in their original order.
+///
+/// Spreads are kept in place since what follows them overrides them.
+fn sort(entries: &mut [Entry]) {
+    for segment in entries.split_mut(|e| e.kind == Spread) {
+        segment.sort_by(|a, b| a.key.cmp(&b.key));
+    }
+}
+
+/// splits the children of a collection into entries, a comment on the line of
+/// an entry belongs to it, a comment on its own line or before an entry on its
+/// line, see [utils::comment_precedes_item], belongs to the next entry.
+///
+/// returns the entries, the comments following the last entry and what
+/// follows the closing parenthesis (trailing content blocks).
+pub(crate) fn entries(
+    parent: &LinkedNode,
+    children: &[String],
+) -> (Vec<Entry>, Vec<String>, Vec<String>) {
+    let mut entries: Vec<Entry> = vec![];
+    let mut pending = vec![];
+    let mut after = vec![];
+    let mut closed = false;
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            _ if closed => after.push(s.clone()),
+            RightParen => closed = true,
+            LeftParen | Space | Comma | Colon => {}
+            LineComment | BlockComment => {
+                let same_line = utils::prev_sibling_or_trivia(&node).is_some_and(|prev| {
+                    prev.kind() != LeftParen
+                        && !(prev.kind() == Space && prev.text().contains('\n'))
+                }) && !utils::comment_precedes_item(&node);
+                match entries.last_mut() {
+                    Some(last) if same_line && pending.is_empty() && last.trailing.is_none() => {
+                        last.trailing = Some(s.clone())
+                    }
+                    _ => pending.push(s.clone()),
+                }
+            }
+            kind => entries.push(Entry {
+                kind,
+                key: key(&node),
+                key_text: key_text(&node),
+                text: s.clone(),
+                leading: std::mem::take(&mut pending),
+                trailing: None,
+            }),
+        }
+    }
+    (entries, pending, after)
+}
+
+fn key(node: &LinkedNode) -> Option<String> {
+    key_text(node).map(|key| key.trim_matches('"').to_string())
+}
+
+fn key_text(node: &LinkedNode) -> Option<String> {
+    match node.kind() {
+        Named | Keyed => node
+            .children()
+            .next()
+            .map(|key| key.get().clone().into_text().to_string()),
+        _ => None,
+    }
+}
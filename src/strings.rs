@@ -0,0 +1,64 @@
+use super::*;
+use unicode_width::UnicodeWidthChar;
+
+/// with `normalize_string_escapes`, writes the `\u{..}` escapes of visible
+/// characters as the characters themselves: `"caf\u{e9}"` is `"café"`.
+///
+/// The quote, the backslash and the line feed, carriage return and tab get their
+/// short escapes, `\"`, `\\`, `\n`, `\r` and `\t`. The other whitespace and the
+/// characters without width, like zero width spaces or combining accents, stay
+/// escaped in lowercase hexadecimal, `\u{200b}`.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_str(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    if !ctx.config.normalize_string_escapes || ctx.off {
+        return no_format(parent, children, ctx);
+    }
+    let text = parent.text();
+    let mut res = String::new();
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find('\\') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match unicode_escape(rest) {
+            Some((c, len)) => {
+                let escape = normalized_escape(c);
+                if escape != rest[..len] {
+                    ctx.stats.applied("normalize_string_escapes");
+                }
+                res.push_str(&escape);
+                rest = &rest[len..];
+            }
+            None => {
+                // copies the backslash and the character it escapes.
+                let len = rest[1..].chars().next().map_or(1, |c| 1 + c.len_utf8());
+                res.push_str(&rest[..len]);
+                rest = &rest[len..];
+            }
+        }
+    }
+    res.push_str(rest);
+    ctx.lost_context();
+    res
+}
+
+/// the character of the `\u{..}` escape starting `s` and the length of the
+/// escape.
+fn unicode_escape(s: &str) -> Option<(char, usize)> {
+    let hex = s.strip_prefix("\\u{")?;
+    let end = hex.find('}')?;
+    let c = char::from_u32(u32::from_str_radix(&hex[..end], 16).ok()?)?;
+    Some((c, "\\u{".len() + end + 1))
+}
+
+/// how the character `c` of a `\u{..}` escape is written, see [format_str].
+fn normalized_escape(c: char) -> String {
+    match c {
+        '"' => "\\\"".to_string(),
+        '\\' => "\\\\".to_string(),
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        _ if !c.is_whitespace() && c.width().is_some_and(|width| width > 0) => c.to_string(),
+        _ => format!("\\u{{{:x}}}", u32::from(c)),
+    }
+}
@@ -0,0 +1,60 @@
+use super::*;
+
+/// formats `set target(args) if condition`: one space after `set` and around `if`,
+/// the arguments are formatted like the ones of a call.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_set_rule(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    if has_comment(parent) {
+        return format_default(parent, children, ctx);
+    }
+    let mut res = String::new();
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            Space => {}
+            Set => {
+                ctx.push_raw_in(s, &mut res);
+                ctx.push_raw_in(" ", &mut res);
+            }
+            If => {
+                ctx.push_raw_in(" ", &mut res);
+                ctx.push_raw_in(s, &mut res);
+                ctx.push_raw_in(" ", &mut res);
+            }
+            _ => ctx.push_raw_in(s, &mut res),
+        }
+    }
+    res
+}
+
+/// formats `show selector: transform`, `show: transform` without a selector. The
+/// blocks of the transform are already indented.
+#[instrument(skip_all, ret)]
+pub(crate) fn format_show_rule(parent: &LinkedNode, children: &[String], ctx: &mut Ctx) -> String {
+    if has_comment(parent) {
+        return format_default(parent, children, ctx);
+    }
+    let mut res = String::new();
+    for (s, node) in children.iter().zip(parent.children()) {
+        match node.kind() {
+            Space => {}
+            Show => ctx.push_raw_in(s, &mut res),
+            Colon => {
+                ctx.push_raw_in(s, &mut res);
+                ctx.push_raw_in(" ", &mut res);
+            }
+            _ if node.prev_sibling_kind() == Some(Show) => {
+                ctx.push_raw_in(" ", &mut res);
+                ctx.push_raw_in(s, &mut res);
+            }
+            _ => ctx.push_raw_in(s, &mut res),
+        }
+    }
+    res
+}
+
+/// comments need the line breaks around them, they are left as they are.
+fn has_comment(parent: &LinkedNode) -> bool {
+    parent
+        .children()
+        .any(|c| matches!(c.kind(), LineComment | BlockComment))
+}
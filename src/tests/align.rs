@@ -0,0 +1,90 @@
+use super::*;
+
+test_eq!(
+    align_dict,
+    "#let d = ( // fmt: align\n  a:   1,\n  bbb: 2,\n)"
+);
+test_eq!(
+    align_table,
+    "#table( // fmt: align\n  columns: 2,\n  [a],  [bbb],\n  [cc], [d],\n)"
+);
+test_eq!(
+    align_let_run,
+    "#{\n  let a   = 1 // fmt: align\n  let bbb = 2\n  f(a)\n}"
+);
+test_eq!(
+    align_let_run_on_the_binding_eq,
+    "#{\n  let f(x: \"a = b\") = x // fmt: align\n  let g             = \"c = d\"\n}"
+);
+test_eq!(
+    align_let_run_in_markup,
+    "#let a   = 1 // fmt: align\n#let bbb = 2\n"
+);
+
+#[test]
+fn align_marker_moves_after_parenthesis() {
+    init();
+    similar_asserts::assert_eq!(
+        format(
+            "#table(columns: 2, // fmt: align\n  [a], [bbb],\n  [cc], [d],\n)",
+            Config::default()
+        ),
+        "#table( // fmt: align\n  columns: 2,\n  [a],  [bbb],\n  [cc], [d],\n)"
+    );
+}
+
+#[test]
+fn align_table_cells() {
+    init();
+    let config = Config {
+        align_table_cells: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#table(columns: 2, [a], [bbb], [cc], [d])", config),
+        "#table(\n  columns: 2,\n  [a],  [bbb],\n  [cc], [d],\n)"
+    );
+    similar_asserts::assert_eq!(format("#table([a], [bbb])", config), "#table([a], [bbb])");
+}
+
+#[test]
+fn align_named_values() {
+    init();
+    let config = Config {
+        align_named_values: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format(
+            "#let style = (fill: rgb(\"#e0e0e0\"), stroke-width: 1pt, inset: 8pt, radius: 4pt, x: 1)",
+            config
+        ),
+        "#let style = (\n  fill:         rgb(\"#e0e0e0\"),\n  stroke-width: 1pt,\n  inset:        8pt,\n  radius:       4pt,\n  x:            1,\n)"
+    );
+    similar_asserts::assert_eq!(format("#f(a: 1, bb: 2)", config), "#f(a: 1, bb: 2)");
+}
+
+#[test]
+fn preserve_alignment() {
+    init();
+    let config = Config {
+        preserve_alignment: true,
+        ..Default::default()
+    };
+    let src = "#let m = (\n  1,   0, 10,\n  0,  10,  1,\n)\n$ mat(\n  1,  0;\n  10, 1;\n) $\n";
+    similar_asserts::assert_eq!(format(src, config), src);
+    let nested = "#{\n  let m = (\n    1,   0,\n    0,  10,\n  )\n}";
+    similar_asserts::assert_eq!(format(nested, config), nested);
+    let urls = "#let urls = (\n  \"https://a.b\", 1, // first\n  \"c\",           2,\n)";
+    similar_asserts::assert_eq!(format(urls, config), urls);
+    let realigned = "#let m = (\n  f(1), 0,\n  10,   1,\n)";
+    similar_asserts::assert_eq!(
+        format("#let m = (\n  f( 1 ),  0,\n  10,      1,\n)", config),
+        realigned
+    );
+    similar_asserts::assert_eq!(format(realigned, config), realigned);
+    similar_asserts::assert_eq!(
+        format("#let m = (\n  1,   0,\n  0,  10,\n)", Config::default()),
+        "#let m = (1, 0, 0, 10)"
+    );
+}
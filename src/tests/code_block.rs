@@ -22,3 +22,164 @@ make_test!(breaks_good_comments, "#{\n//some comment\n}");
 make_test!(no_space_after_block, "#{//\n}    \ncontent");
 make_test!(no_space_after_block2, "#{//\n}   \n\ncontent");
 make_test!(breakline_after_block, "#{//\n}\n\ncontent");
+test_eq!(semicolon_then_newline, "#{\n  a;\n  b\n}");
+test_eq!(one_statement_stays_inline, "#{ a }");
+test_eq!(semicolon_before_comment, "#{\n  a; // comment\n  b\n}");
+
+#[test]
+fn trailing_semicolon_removed() {
+    init();
+    similar_asserts::assert_eq!(format("#{ a;b; }", Config::default()), "#{\n  a;\n  b\n}");
+}
+test_eq!(
+    short_statements_inline,
+    "#{ let x = 1; x + 1 }",
+    Config {
+        max_inline_code_block_width: 30,
+        ..Default::default()
+    }
+);
+make_test!(
+    long_statements_not_inline,
+    "#{ let x = 1; x + 1 }",
+    Config {
+        max_inline_code_block_width: 10,
+        ..Default::default()
+    }
+);
+test_eq!(
+    return_broken,
+    "#{\n  return (\n    aaaa + bbbb\n  )\n}",
+    Config {
+        max_line_length: 10,
+        ..Default::default()
+    }
+);
+
+#[test]
+fn long_return_is_wrapped() {
+    init();
+    let config = Config {
+        max_line_length: 10,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#{\n  return aaaa + bbbb\n}", config),
+        "#{\n  return (\n    aaaa + bbbb\n  )\n}"
+    );
+}
+
+#[test]
+fn no_space_before_semicolon() {
+    init();
+    let config = Config {
+        max_inline_code_block_width: 30,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format("#{ a ; b }", config), "#{ a; b }");
+}
+
+#[test]
+fn unwrap_embedded_blocks() {
+    init();
+    let config = Config {
+        unwrap_embedded_blocks: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format("a #{emph[x]} b", config), "a #emph[x] b");
+    similar_asserts::assert_eq!(format("#{a.b}, c", config), "#a.b, c");
+    similar_asserts::assert_eq!(format("#{ x }", config), "#x");
+    // what follows would change the meaning.
+    similar_asserts::assert_eq!(format("#{f}(x)", config), "#{ f }(x)");
+    similar_asserts::assert_eq!(format("#{f(x)}[y]", config), "#{ f(x) }[y]");
+    similar_asserts::assert_eq!(format("#{a};", config), "#{ a };");
+    similar_asserts::assert_eq!(format("#{a}b", config), "#{ a }b");
+    // not a single unwrappable expression.
+    similar_asserts::assert_eq!(format("#{1 + 2}", config), "#{ 1 + 2 }");
+    similar_asserts::assert_eq!(format("#f({x})", config), "#f({ x })");
+}
+
+#[test]
+fn trim_block_blank_lines() {
+    init();
+    let config = Config {
+        trim_block_blank_lines: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#{\n\n  let a = 1\n\n  a\n\n}", config),
+        "#{\n  let a = 1\n\n  a\n}"
+    );
+    similar_asserts::assert_eq!(format("#box[\n\n  text\n\n]", config), "#box[\n  text\n]");
+}
+
+test_eq!(
+    loop_spacing_kept,
+    "#for x in (1, 2) {\n  x\n}\n#while x < 2 {\n  x += 1\n}"
+);
+
+#[test]
+fn loop_spacing() {
+    init();
+    similar_asserts::assert_eq!(
+        format("#for  x  in  (1, 2)  {\n  x\n}", Config::default()),
+        "#for x in (1, 2) {\n  x\n}"
+    );
+    let config = Config {
+        max_inline_code_block_width: 40,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#while x < 2 { x += 1 }", config),
+        "#while x < 2 { x += 1 }"
+    );
+}
+
+#[test]
+fn collapse_and_expand_blocks() {
+    init();
+    let conservative = Config {
+        mode: Mode::Conservative,
+        ..Default::default()
+    };
+    let broken = "#{\n  x + 1\n}\n#[\n  some text\n]";
+    similar_asserts::assert_eq!(format(broken, conservative), broken);
+    let collapse = Config {
+        collapse_single_statement_blocks: true,
+        ..conservative
+    };
+    similar_asserts::assert_eq!(format(broken, collapse), "#{ x + 1 }\n#[ some text ]");
+    // a list is always broken, collapsing it wouldn't be stable.
+    let list = "#box[\n  - a\n]";
+    similar_asserts::assert_eq!(format(list, collapse), list);
+    similar_asserts::assert_eq!(format(&format(list, collapse), collapse), list);
+
+    let expand = Config {
+        expand_blocks: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#{ x + 1 }\n#[ some text ]\n#[text]", expand),
+        "#{\n  x + 1\n}\n#[\n  some text\n]\n#[text]"
+    );
+}
+
+#[test]
+fn remove_line_end_semicolons() {
+    init();
+    let config = Config {
+        remove_line_end_semicolons: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#{\n  let a = 1;\n  a;b\n}", config),
+        "#{\n  let a = 1\n  a\n  b\n}"
+    );
+    let kept = "#{\n  a; // comment\n  b\n}";
+    similar_asserts::assert_eq!(format(kept, config), kept);
+    let one_line = Config {
+        max_inline_code_block_width: 20,
+        ..config
+    };
+    similar_asserts::assert_eq!(format("#{a; b}", one_line), "#{ a; b }");
+}
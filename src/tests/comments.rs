@@ -74,3 +74,135 @@ const START_WITH_COMMENT: &str = r#"#table(
   fill: value,
   [*Pros*],)
 "#;
+
+#[test]
+fn space_after_line_comment() {
+    init();
+    let config = Config {
+        space_after_line_comment: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#{\n  //comment\n  a\n}", config),
+        "#{\n  // comment\n  a\n}"
+    );
+    similar_asserts::assert_eq!(format("//typstfmt::off\n", config), "//typstfmt::off\n");
+}
+
+#[test]
+fn block_comments_to_line_comments() {
+    init();
+    let config = Config {
+        block_comments_to_line_comments: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#let a = 1 /* short */\n", config),
+        "#let a = 1 // short\n"
+    );
+    similar_asserts::assert_eq!(
+        format("/*\n * first\n * second\n */\ntext", config),
+        "// first\n// second\ntext"
+    );
+    // the lines are indented with the block.
+    for (src, formatted) in [
+        (
+            "#{\n  a\n  /* x\n     y */\n  b\n}",
+            "#{\n  a\n  // x\n  // y\n  b\n}",
+        ),
+        (
+            "#[\n  a\n  /* x\n     y */\n  b\n]",
+            "#[\n  a\n  // x\n  // y\n  b\n]",
+        ),
+    ] {
+        similar_asserts::assert_eq!(format(src, config), formatted);
+        similar_asserts::assert_eq!(format(formatted, config), formatted);
+    }
+    // the rest of the line would be commented out.
+    similar_asserts::assert_eq!(
+        format("#let a = /* a */ 1\n", config),
+        "#let a = /* a */ 1\n"
+    );
+    // the arguments may be joined on one line, `b` would be commented out.
+    for src in [
+        "#f(a, /* c */\n  b)",
+        "#let (a, /* c */\n  b) = x",
+        "#let f(a, /* c */\n  b) = a",
+    ] {
+        let formatted = format(src, config);
+        assert!(formatted.contains("/* c */"), "{formatted}");
+        assert!(!formatted.contains("//"), "{formatted}");
+        similar_asserts::assert_eq!(format(&formatted, config), formatted);
+    }
+}
+
+test_eq!(
+    comment_interiors_preserved,
+    "// a  table   here   \ntext /*  a\t b  \n  c */\n"
+);
+
+#[test]
+fn trailing_comment_spaces() {
+    init();
+    let config = Config {
+        trailing_comment_spaces: 2,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#{\n  a // first\n  b\n}", config),
+        "#{\n  a  // first\n  b\n}"
+    );
+    similar_asserts::assert_eq!(
+        format("#f(\n  a, // first\n  b,\n)", config),
+        "#f(\n  a,  // first\n  b,\n)"
+    );
+}
+
+#[test]
+fn ignore_directive() {
+    init();
+    let s = "// typstfmt: ignore\n#table(columns: 2,\n  [a],   [b],\n  [cc],  [d])\n#f(a ,b)\n";
+    similar_asserts::assert_eq!(
+        format(s, Config::default()),
+        "// typstfmt: ignore\n#table(columns: 2,\n  [a],   [b],\n  [cc],  [d])\n#f(a, b)\n"
+    );
+    similar_asserts::assert_eq!(
+        format(
+            "#{\n  // typstfmt: ignore\n  let x  =  1\n  let y  =  2\n}",
+            Config::default()
+        ),
+        "#{\n  // typstfmt: ignore\n  let x  =  1\n  let y = 2\n}"
+    );
+}
+
+#[test]
+fn directive_in_a_string() {
+    init();
+    similar_asserts::assert_eq!(
+        format("#let a = \"typstfmt: off\"\n#f(a ,b)\n", Config::default()),
+        "#let a = \"typstfmt: off\"\n#f(a, b)\n"
+    );
+}
+
+test_eq!(
+    off_directive_with_single_colon,
+    "// typstfmt: off\n#f(a ,b)\n// typstfmt: on\n"
+);
+
+test_eq!(
+    block_comment_stays_before_its_argument,
+    "#f(\n  a, // first\n  /* about b */ b,\n)"
+);
+
+#[test]
+fn comment_before_named_value() {
+    init();
+    similar_asserts::assert_eq!(
+        format("#f(a: // the width\n1)", Config::default()),
+        "#f(a: // the width\n  1)"
+    );
+    similar_asserts::assert_eq!(
+        format("#f(a:/* the width */1)", Config::default()),
+        "#f(a: /* the width */ 1)"
+    );
+}
@@ -0,0 +1,254 @@
+use super::*;
+
+#[test]
+fn every_option_is_documented() {
+    for option in Config::OPTIONS {
+        assert!(
+            !option.description.trim().is_empty(),
+            "{} is not documented",
+            option.name
+        );
+        assert!(Config::explain(option.name).is_some());
+    }
+}
+
+#[test]
+fn explain_unknown_key() {
+    assert!(Config::explain("not_a_key").is_none());
+}
+
+#[test]
+fn extends_path() {
+    let dir = temp_dir("typstfmt_extends_path");
+    std::fs::create_dir_all(dir.join("style")).unwrap();
+    std::fs::write(
+        dir.join("style").join("typstfmt.toml"),
+        "indent_space = 4\nmax_line_length = 100\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("typstfmt.toml"),
+        "extends = \"style\"\nmax_line_length = 60\n",
+    )
+    .unwrap();
+    let config = Config::from_file(&dir.join("typstfmt.toml")).unwrap();
+    assert_eq!(config.indent_space, 4);
+    assert_eq!(config.max_line_length, 60);
+}
+
+#[test]
+fn extends_cycle() {
+    let dir = temp_dir("typstfmt_extends_cycle");
+    std::fs::write(dir.join("a.toml"), "extends = \"b.toml\"\n").unwrap();
+    std::fs::write(dir.join("b.toml"), "extends = \"a.toml\"\n").unwrap();
+    assert!(Config::from_file(&dir.join("a.toml")).is_err());
+}
+
+#[test]
+fn fingerprint() {
+    let config = Config::default();
+    assert_eq!(config.fingerprint(), Config::default().fingerprint());
+    assert_eq!(config.fingerprint().len(), 16);
+    let other = Config {
+        indent_space: 4,
+        ..Default::default()
+    };
+    assert_ne!(config.fingerprint(), other.fingerprint());
+    // debug builds verify by default, release builds don't, the headers are the same.
+    let verified = Config {
+        verify_syntax: !config.verify_syntax,
+        ..Default::default()
+    };
+    assert_eq!(config.fingerprint(), verified.fingerprint());
+}
+
+#[test]
+fn required_version() {
+    let config = Config::from_toml("required_version = \"^0.3\"").unwrap();
+    let required = config.required_version.unwrap();
+    assert!(required.matches("0.3.0"));
+    assert!(required.matches("0.3.7"));
+    assert!(!required.matches("0.2.9"));
+    assert!(!required.matches("0.4.0"));
+    assert_eq!(config.version_mismatch, VersionMismatch::Error);
+
+    let exact = VersionReq::try_from("=0.2.9".to_string()).unwrap();
+    assert!(exact.matches("0.2.9") && !exact.matches("0.2.10"));
+    let tilde = VersionReq::try_from("~1.2".to_string()).unwrap();
+    assert!(tilde.matches("1.2.5") && !tilde.matches("1.3.0"));
+    assert_eq!(tilde.to_string(), "~1.2");
+    assert!(Config::from_toml("required_version = \"latest\"").is_err());
+}
+
+#[test]
+fn env_overrides() {
+    let vars = [
+        ("TYPSTFMT_MAX_LINE_LENGTH", "100"),
+        ("TYPSTFMT_MODE", "conservative"),
+        ("TYPSTFMT_VERSION", "not a key"),
+        ("PATH", "/bin"),
+    ]
+    .map(|(k, v)| (k.to_string(), v.to_string()));
+    let config = Config::default().with_overrides(vars.into_iter()).unwrap();
+    assert_eq!(config.max_line_length, 100);
+    assert_eq!(config.mode, Mode::Conservative);
+
+    let vars = [("TYPSTFMT_INDENT_SPACE", "two")].map(|(k, v)| (k.to_string(), v.to_string()));
+    assert!(Config::default().with_overrides(vars.into_iter()).is_err());
+}
+
+#[test]
+fn resolve_for_merges_hierarchy() {
+    let dir = temp_dir("typstfmt_resolve_for");
+    let env = isolated(&dir);
+    std::fs::create_dir_all(dir.join("chapters")).unwrap();
+    std::fs::write(
+        dir.join(".editorconfig"),
+        "root = true\n\n[*.{typ,md}]\nindent_size = 8\nmax_line_length = 100\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("typstfmt.toml"), "indent_space = 4\n").unwrap();
+    std::fs::write(
+        dir.join("chapters").join(".typstfmt.toml"),
+        "max_line_length = 60\n",
+    )
+    .unwrap();
+    let config = Config::resolve_in(&dir.join("chapters").join("one.typ"), &env).unwrap();
+    assert_eq!(config.indent_space, 4);
+    assert_eq!(config.max_line_length, 60);
+
+    let config = Config::resolve_in(&dir.join("main.typ"), &env).unwrap();
+    assert_eq!(config.indent_space, 4);
+    assert_eq!(config.max_line_length, 100);
+}
+
+#[test]
+fn dump_for_annotates_sources() {
+    let dir = temp_dir("typstfmt_dump_for");
+    let mut env = isolated(&dir);
+    std::fs::write(dir.join("typstfmt.toml"), "indent_space = 4\n").unwrap();
+    env.vars = vec![("TYPSTFMT_MAX_LINE_LENGTH".to_string(), "100".to_string())];
+    let dump = Config::dump_in(&dir.join("main.typ"), &env).unwrap();
+    let source = dir.join("typstfmt.toml").display().to_string();
+    assert!(dump.contains(&format!("indent_space = 4 # {source}\n")));
+    assert!(dump.contains("max_line_length = 100 # environment (TYPSTFMT_MAX_LINE_LENGTH)\n"));
+    assert!(dump.contains("line_wrap = true # default\n"));
+    assert!(dump.contains("# required_version is not set # default\n"));
+}
+
+#[test]
+fn set_rule() {
+    let config = Config::default()
+        .set_rule("sort-dict-keys", true)
+        .unwrap()
+        .set_rule("markup-reflow", false)
+        .unwrap();
+    assert!(config.sort_dict_keys);
+    assert_eq!(config.line_wrap, Wrap::Off);
+    let config = Config::from_toml("line_wrap = \"semantic\"").unwrap();
+    assert_eq!(config.line_wrap, Wrap::Semantic);
+    assert!(Config::from_toml("line_wrap = \"never\"").is_err());
+    assert!(Config::rules().contains(&"trim-block-blank-lines".to_string()));
+    assert!(Config::default().set_rule("max-line-length", true).is_err());
+    assert!(Config::default().set_rule("not-a-rule", true).is_err());
+}
+
+#[test]
+fn discover() {
+    let dir = temp_dir("typstfmt_discover");
+    std::fs::create_dir_all(dir.join("a").join("b")).unwrap();
+    std::fs::write(
+        dir.join("typstfmt.toml"),
+        "indent_space = 4\nmax_line_length = 60",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("a").join(".typstfmt.toml"),
+        "max_line_length = 100",
+    )
+    .unwrap();
+    assert_eq!(
+        Config::discover_in(&dir.join("a").join("b").join("main.typ"), &isolated(&dir)),
+        vec![
+            dir.join("typstfmt.toml"),
+            dir.join("a").join(".typstfmt.toml")
+        ]
+    );
+    let config = Config::from_path(&dir.join("a").join("b").join("main.typ")).unwrap();
+    assert_eq!(config.indent_space, 4);
+    assert_eq!(config.max_line_length, 100);
+}
+
+#[test]
+fn indent_with_tabs() {
+    init();
+    let config = Config {
+        indent_style: IndentStyle::Tabs,
+        ..Default::default()
+    };
+    let src = "#{\n\tlet a = 1\n\tif a {\n\t\t[text]\n\t}\n}";
+    similar_asserts::assert_eq!(format(src, config), src);
+    similar_asserts::assert_eq!(format("#{\n  let a = 1\n}", config), "#{\n\tlet a = 1\n}");
+}
+
+#[test]
+fn line_ending() {
+    init();
+    let src = "#let a  = 1\r\n#let b = 2\r\n";
+    similar_asserts::assert_eq!(
+        format(src, Config::default()),
+        "#let a = 1\r\n#let b = 2\r\n"
+    );
+    let config = Config {
+        line_ending: LineEnding::Lf,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format(src, config), "#let a = 1\n#let b = 2\n");
+    let config = Config {
+        line_ending: LineEnding::Crlf,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format("#let a = 1\n", config), "#let a = 1\r\n");
+}
+
+#[test]
+fn json_schema() {
+    let schema: serde_json::Value = serde_json::from_str(&Config::json_schema()).unwrap();
+    let properties = schema["properties"].as_object().unwrap();
+    for option in Config::OPTIONS {
+        assert!(properties.contains_key(option.name), "{}", option.name);
+    }
+    assert!(properties.contains_key("extends"));
+    assert_eq!(properties["indent_space"]["default"], 2);
+    assert_eq!(properties["mode"]["enum"][1], "canonical");
+    assert!(properties["required_version"].get("default").is_none());
+}
+
+#[test]
+fn presets() {
+    let compact = Config::preset("compact").unwrap();
+    assert_eq!(compact.max_line_length, 100);
+    assert!(compact.collapse_single_statement_blocks);
+    assert!(compact.trim_block_blank_lines);
+    assert!(!Config::default().trim_block_blank_lines);
+    assert!(Config::preset("expanded").unwrap().expand_blocks);
+    assert_eq!(
+        Config::preset("default").unwrap().fingerprint(),
+        Config::default().fingerprint()
+    );
+    assert!(Config::preset("huge").is_none());
+
+    let config = Config::from_toml("style = \"compact\"\nmax_line_length = 120").unwrap();
+    assert_eq!(config.max_line_length, 120);
+    assert_eq!(config.max_inline_code_block_width, 40);
+    assert!(Config::from_toml("style = \"huge\"").is_err());
+}
+
+#[test]
+fn names_are_interned_once() {
+    let first = |names: Names| names.iter().next().unwrap().as_ptr();
+    let list = Names::new(&["table", "figure"]);
+    let reloaded = Config::from_toml("verbatim_args_calls = [\"table\", \"figure\"]").unwrap();
+    assert_eq!(first(list), first(reloaded.verbatim_args_calls));
+    assert_eq!(first(list), first(Names::new(&["table"])));
+}
@@ -0,0 +1,60 @@
+use super::*;
+use crate::doc::{render, render_in_place, Doc};
+
+fn call(args: &[&str]) -> Doc {
+    let mut items = vec![Doc::SoftLine];
+    for (i, arg) in args.iter().enumerate() {
+        items.push(Doc::text(*arg));
+        if i + 1 < args.len() {
+            items.push(Doc::text(","));
+            items.push(Doc::Line);
+        } else {
+            items.push(Doc::IfBreak(",".to_string()));
+        }
+    }
+    Doc::group(vec![
+        Doc::text("f("),
+        Doc::Nest(items),
+        Doc::SoftLine,
+        Doc::text(")"),
+    ])
+}
+
+#[test]
+fn group_fits() {
+    init();
+    similar_asserts::assert_eq!(render(&call(&["a", "b"]), 80, "  "), "f(a, b)");
+}
+
+#[test]
+fn group_breaks() {
+    init();
+    similar_asserts::assert_eq!(
+        render(&call(&["aaaa", "bbbb"]), 10, "  "),
+        "f(\n  aaaa,\n  bbbb,\n)"
+    );
+}
+
+#[test]
+fn hard_line_breaks_group() {
+    init();
+    let doc = Doc::group(vec![
+        Doc::text("a"),
+        Doc::Line,
+        Doc::Nest(vec![Doc::HardLine, Doc::text("b\nc")]),
+    ]);
+    similar_asserts::assert_eq!(render(&doc, 80, "  "), "a\n\n  b\n  c");
+}
+
+#[test]
+fn render_in_place_starts_at_the_column_of_the_node() {
+    init();
+    let doc = Doc::group(vec![Doc::text("aaa"), Doc::Line, Doc::text("bb")]);
+    let mut ctx = Ctx::from_config(Config {
+        max_line_length: 10,
+        ..Default::default()
+    });
+    assert_eq!(render_in_place(&doc, &ctx), ("aaa bb".to_string(), false));
+    ctx.start_column = 6;
+    assert_eq!(render_in_place(&doc, &ctx), ("aaa\nbb".to_string(), true));
+}
@@ -0,0 +1,82 @@
+use super::*;
+
+#[test]
+fn erroneous_node_is_left_verbatim() {
+    init();
+    let input = "#f(1,2)\n\n#{ let   = 1 }\n";
+    let formatted = format(input, Config::default());
+    similar_asserts::assert_eq!(formatted, "#f(1, 2)\n\n#{ let   = 1 }\n");
+}
+
+#[test]
+fn erroneous_node_is_reported() {
+    init();
+    let output = format_output("#f(1,2)\n\n#{ let   = 1 }\n", Config::default());
+    assert!(output.changed);
+    assert_eq!(output.skipped_regions, vec![10..23]);
+    assert!(!output.diagnostics.is_empty());
+    assert!(output
+        .diagnostics
+        .iter()
+        .all(|d| d.range.start >= 10 && d.range.end <= 23));
+}
+
+#[test]
+fn off_region_is_reported() {
+    init();
+    let input = "// typstfmt::off\n#{{4}}\n// typstfmt::on\n#{{4}}\n";
+    let output = format_output(input, Config::default());
+    assert!(!output.changed);
+    assert_eq!(output.skipped_regions, vec![0..39]);
+}
+
+#[test]
+fn on_error() {
+    init();
+    let input = "#f(1,2)\n\n#{ let   = 1 }\n";
+    let config = Config {
+        on_error: OnError::Passthrough,
+        ..Default::default()
+    };
+    let output = format_output(input, config);
+    similar_asserts::assert_eq!(output.text, input);
+    assert_eq!(output.skipped_regions, vec![0..input.len()]);
+    assert!(!output.diagnostics.is_empty());
+
+    let config = Config {
+        on_error: OnError::Abort,
+        ..Default::default()
+    };
+    let errors = try_format(input, config).unwrap_err();
+    assert!(errors
+        .0
+        .iter()
+        .all(|d| d.range.start >= 10 && d.range.end <= 23));
+    assert!(try_format("#f(1,2)", config).is_ok());
+    similar_asserts::assert_eq!(format(input, config), input);
+    // the same errors, without formatting.
+    assert_eq!(check_syntax(input, config).unwrap_err().0, errors.0);
+    assert!(check_syntax("#f(1,2)", config).is_ok());
+}
+
+#[test]
+fn newer_typst_version() {
+    init();
+    let input = "#{\n  let it = context text.lang\n}\n";
+    let output = format_output(input, Config::default());
+    similar_asserts::assert_eq!(output.text, input);
+    assert!(!output.diagnostics.is_empty());
+    assert!(output
+        .diagnostics
+        .iter()
+        .all(|d| !d.message.contains("0.11")));
+
+    let config = Config::from_toml("typst_version = \"0.11\"").unwrap();
+    assert_eq!(config.typst_version, TypstVersion::V0_11);
+    let output = format_output(input, config);
+    similar_asserts::assert_eq!(output.text, input);
+    assert!(!output.diagnostics.is_empty());
+    assert!(output.diagnostics.iter().all(|d| d
+        .message
+        .contains("parses the syntax of typst 0.10, not of 0.11")));
+}
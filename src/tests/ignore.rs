@@ -0,0 +1,69 @@
+use super::*;
+
+#[test]
+fn typstfmtignore() {
+    let dir = temp_dir("typstfmt_ignore");
+    std::fs::create_dir_all(dir.join("vendor").join("lib")).unwrap();
+    std::fs::create_dir_all(dir.join("chapters")).unwrap();
+    std::fs::write(dir.join("typstfmt.toml"), "").unwrap();
+    std::fs::write(
+        dir.join(".typstfmtignore"),
+        "# generated\nvendor/\n*.generated.typ\n!keep.generated.typ\n",
+    )
+    .unwrap();
+    let ignore = Ignore::new(Names::new(&[]), &dir);
+    assert!(ignore.is_ignored(&dir.join("vendor").join("lib").join("a.typ")));
+    assert!(ignore.is_ignored(&dir.join("chapters").join("toc.generated.typ")));
+    assert!(!ignore.is_ignored(&dir.join("chapters").join("keep.generated.typ")));
+    assert!(!ignore.is_ignored(&dir.join("chapters").join("one.typ")));
+}
+
+#[test]
+fn exclude_is_relative_to_its_config_file() {
+    let dir = temp_dir("typstfmt_exclude");
+    std::fs::create_dir_all(dir.join("sub").join("drafts")).unwrap();
+    std::fs::create_dir_all(dir.join("drafts")).unwrap();
+    std::fs::write(dir.join("typstfmt.toml"), "exclude = [\"drafts/**\"]").unwrap();
+    let file = dir.join("sub").join("one.typ");
+    std::fs::write(&file, "").unwrap();
+
+    let env = isolated(&dir);
+    let config = Config::resolve_in(&file, &env).unwrap();
+    let base = Config::exclude_dir_in(&file, &env).unwrap();
+    assert_eq!(base, dir);
+    let ignore = Ignore::new(config.exclude, &base);
+    assert!(ignore.is_ignored(&dir.join("drafts").join("old.typ")));
+    assert!(!ignore.is_ignored(&dir.join("sub").join("drafts").join("old.typ")));
+    assert!(!ignore.is_ignored(&file));
+}
+
+#[test]
+fn exclude_of_a_nested_config() {
+    let dir = temp_dir("typstfmt_exclude_nested");
+    std::fs::create_dir_all(dir.join("drafts")).unwrap();
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    std::fs::write(dir.join("typstfmt.toml"), "exclude = [\"drafts/**\"]").unwrap();
+    std::fs::write(
+        dir.join("sub").join("typstfmt.toml"),
+        "exclude = [\"old.typ\"]",
+    )
+    .unwrap();
+
+    let ignore = Ignore::of_configs_in(isolated(&dir));
+    assert!(ignore.is_ignored(&dir.join("drafts").join("a.typ")));
+    assert!(ignore.is_ignored(&dir.join("sub").join("old.typ")));
+    assert!(!ignore.is_ignored(&dir.join("old.typ")));
+    assert!(!ignore.is_ignored(&dir.join("sub").join("new.typ")));
+}
+
+#[test]
+fn typstfmtignore_stops_at_the_project_root() {
+    let dir = temp_dir("typstfmt_ignore_root");
+    let project = dir.join("project");
+    std::fs::create_dir_all(project.join(".git")).unwrap();
+    std::fs::write(dir.join(".typstfmtignore"), "*.typ\n").unwrap();
+    std::fs::write(project.join(".typstfmtignore"), "vendor/\n").unwrap();
+    let ignore = Ignore::new(Names::new(&[]), &project);
+    assert!(!ignore.is_ignored(&project.join("main.typ")));
+    assert!(ignore.is_ignored(&project.join("vendor").join("a.typ")));
+}
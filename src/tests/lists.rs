@@ -6,6 +6,22 @@ make_test!(list2, &TERMS.replace('/', "-"));
 make_test!(enums, &TERMS.replace('/', "+"));
 make_test!(terms, TERMS);
 
+#[test]
+fn continuation_under_text() {
+    init();
+    let config = Config {
+        line_wrap: Wrap::Off,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format(
+            "10. first\n continued\n      - nested\n        more",
+            config
+        ),
+        "10. first\n    continued\n  - nested\n    more"
+    );
+}
+
 const LIST: &str = r#"
 - 000
  some text 
@@ -48,3 +48,177 @@ make_test!(
     children_respect_max_line_length,
     r#"#[ Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et #[ Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris ] dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla ]"#
 );
+
+#[test]
+fn own_line_calls() {
+    init();
+    similar_asserts::assert_eq!(
+        format("Some text. #pagebreak() More text.", Config::default()),
+        "Some text.\n#pagebreak()\nMore text."
+    );
+    similar_asserts::assert_eq!(format("a #v(1em)\nb", Config::default()), "a\n#v(1em)\nb");
+    // other calls and list items are left alone.
+    similar_asserts::assert_eq!(format("a #emph[b] c", Config::default()), "a #emph[b] c");
+    similar_asserts::assert_eq!(format("- a #v(1em)", Config::default()), "- a #v(1em)");
+    let config = Config {
+        own_line_calls: Names::new(&["emph"]),
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format("a #emph[b] c", config), "a\n#emph[b]\nc");
+}
+
+test_eq!(
+    minimal_keeps_line_breaks,
+    "Some text\non several\nlines.",
+    Config {
+        mode: Mode::Minimal,
+        ..Default::default()
+    }
+);
+
+#[test]
+fn wrap_keeps_labels_with_their_text() {
+    init();
+    let config = Config {
+        max_line_length: 20,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("aaaa bbbb cccc dddd <label> eeee", config),
+        "aaaa bbbb cccc dddd <label>\neeee"
+    );
+}
+
+#[test]
+fn wrap_around_inline_items() {
+    init();
+    let config = Config {
+        max_line_length: 20,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("aaaa bbbb cccc `raw` dddd", config),
+        "aaaa bbbb cccc `raw`\ndddd"
+    );
+    similar_asserts::assert_eq!(format("aa\n`b`\n$c$ dd", config), "aa `b` $c$ dd");
+}
+
+#[test]
+fn blank_lines() {
+    init();
+    let src = "#let a = 1\n#set text(red)\nSome text.\n= Title\n\n\n\nMore text.";
+    similar_asserts::assert_eq!(
+        format(src, Config::default()),
+        "#let a = 1\n#set text(red)\nSome text.\n= Title\n\nMore text."
+    );
+    let config = Config {
+        max_blank_lines: 2,
+        blank_line_before_headings: true,
+        blank_line_after_rules: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format(src, config),
+        "#let a = 1\n#set text(red)\n\nSome text.\n\n= Title\n\n\nMore text."
+    );
+    // a blank line would split the paragraph.
+    let paragraph = "Some text\n#let a = 1\nmore text.\n#set text(red)\n- item";
+    similar_asserts::assert_eq!(
+        format(paragraph, config),
+        "Some text\n#let a = 1\nmore text.\n#set text(red)\n\n- item"
+    );
+}
+
+#[test]
+fn heading_spacing() {
+    init();
+    similar_asserts::assert_eq!(
+        format("==   Title  \ntext", Config::default()),
+        "== Title\ntext"
+    );
+    let config = Config {
+        blank_line_before_headings: true,
+        blank_line_after_headings: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("text\n= Title\ntext", config),
+        "text\n\n= Title\n\ntext"
+    );
+}
+
+#[test]
+fn wrap_measures_display_width() {
+    init();
+    let config = Config {
+        max_line_length: 10,
+        ..Default::default()
+    };
+    // each character is two columns wide.
+    similar_asserts::assert_eq!(
+        format("一二三 四五六 七八九", config),
+        "一二三\n四五六\n七八九"
+    );
+    similar_asserts::assert_eq!(format("abc def ghi", config), "abc def\nghi");
+}
+
+#[test]
+fn content_block_breaking() {
+    init();
+    let config = Config {
+        max_inline_content_block_width: 20,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format("#box[- a]", config), "#box[\n  - a\n]");
+    similar_asserts::assert_eq!(
+        format("#block[ some words that are long ]", config),
+        "#block[\n  some words that are long\n]"
+    );
+    let unspaced = "#strong[some words that are long]";
+    similar_asserts::assert_eq!(format(unspaced, config), unspaced);
+    let short = "#block[ short ]";
+    similar_asserts::assert_eq!(format(short, config), short);
+}
+
+#[test]
+fn label_spacing() {
+    init();
+    let conservative = Config {
+        mode: Mode::Conservative,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("Some text\n<intro>", conservative),
+        "Some text <intro>"
+    );
+    let config = Config {
+        max_line_length: 10,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format("aaaa bbbb <lab>.", config), "aaaa bbbb <lab>.");
+    similar_asserts::assert_eq!(
+        format("see @fig[the figure]", config),
+        "see\n@fig[the figure]"
+    );
+}
+
+#[test]
+fn semantic_wrap() {
+    init();
+    let config = Config {
+        line_wrap: Wrap::Semantic,
+        max_line_length: 10,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("First sentence here. Second one! A\nthird? Yes", config),
+        "First sentence here.\nSecond one!\nA third?\nYes"
+    );
+    similar_asserts::assert_eq!(format("Done. _Next_ one.", config), "Done.\n_Next_ one.");
+    let kept = "= A title. Still the title\n`raw. text` and $x. y$ stay.";
+    let config = Config {
+        line_wrap: Wrap::Semantic,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format(kept, config), kept);
+}
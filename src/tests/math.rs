@@ -114,3 +114,46 @@ $
  a    & ≠  b &   ("text")
 $"#
 );
+
+#[test]
+fn attach_and_frac_spacing() {
+    init();
+    similar_asserts::assert_eq!(
+        format("$x ^ 2 + a _(i j)$", Config::default()),
+        "$x^2 + a_(i j)$"
+    );
+    similar_asserts::assert_eq!(format("$a/ b + c/d$", Config::default()), "$a / b + c/d$");
+}
+
+#[test]
+fn inline_math_on_one_line() {
+    init();
+    similar_asserts::assert_eq!(
+        format("Let $a +\n  b$ be", Config::default()),
+        "Let $a + b$ be"
+    );
+}
+
+#[test]
+fn block_math_on_several_lines() {
+    init();
+    similar_asserts::assert_eq!(
+        format("$ a &= b \\\n c &= d $", Config::default()),
+        "$\n  a &= b \\\n  c &= d\n$"
+    );
+}
+
+#[test]
+fn align_math_points() {
+    init();
+    let src = "$\n  a &= 1 \\\n  bbb &= 2\n$";
+    similar_asserts::assert_eq!(
+        format(src, Config::default()),
+        "$\n  a   &= 1 \\\n  bbb &= 2\n$"
+    );
+    let config = Config {
+        align_math_points: false,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format(src, config), src);
+}
@@ -32,6 +32,28 @@ fn init() {
     let _ = tracing::subscriber::set_global_default(subscriber);
 }
 
+/// a new empty directory in the temporary directory, for the test `name`, unique
+/// to the process so tests running at the same time don't share it.
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!("{name}_{}_{nanos}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// an environment without variables nor global config, where no config file is
+/// looked for above `dir`.
+fn isolated(dir: &std::path::Path) -> crate::config::Environment {
+    crate::config::Environment {
+        vars: vec![],
+        global: None,
+        ceiling: Some(dir.to_path_buf()),
+    }
+}
+
 /// This makes :
 /// - A snapshot test where you're prompted to say if you're snippet is nicely formatted.
 /// (see README.md)
@@ -173,11 +195,18 @@ fn comma_gets_ignored_in_comparison() {
     assert!(parses_the_same("#f(1,{g(1,2,3,)},)", "#f(1,{g(1,2,3)})"));
 }
 
+mod align;
 mod code_block;
 mod comments;
 mod conditionals;
+mod config;
+mod doc;
+mod errors;
+mod ignore;
 mod lists;
 mod markup;
 mod math;
 mod params;
+mod partial;
 mod snippets;
+mod verify;
@@ -92,3 +92,333 @@ test_eq!(
     "f[ this loooooooooooooooooooooooooooong text is not supposed to not be indented
 at all ]"
 );
+test_eq!(
+    conservative_keeps_long_line,
+    "#f(aaaa, bbbb, cccc)",
+    Config {
+        max_line_length: 5,
+        mode: Mode::Conservative,
+        ..Default::default()
+    }
+);
+test_eq!(
+    conservative_keeps_broken_args,
+    "#f(\n  a, b,\n)",
+    Config {
+        mode: Mode::Conservative,
+        ..Default::default()
+    }
+);
+test_eq!(
+    minimal_keeps_broken_args,
+    "#f(\n  a, b,\n)",
+    Config {
+        mode: Mode::Minimal,
+        ..Default::default()
+    }
+);
+test_eq!(
+    long_string_own_line,
+    r#"#image(
+  "a/very/long/path/to/an/image/file.png",
+  width: 50%,
+)"#,
+    Config {
+        max_line_length: 40,
+        ..Default::default()
+    }
+);
+
+#[test]
+fn comma_and_colon_spacing() {
+    init();
+    similar_asserts::assert_eq!(
+        format("#f(a ,b,c : d)", Config::default()),
+        "#f(a, b, c: d)"
+    );
+    similar_asserts::assert_eq!(format("#(a :1 ,b:2)", Config::default()), "#(a: 1, b: 2)");
+    similar_asserts::assert_eq!(
+        format("#import \"a.typ\" :a ,b", Config::default()),
+        "#import \"a.typ\": a, b"
+    );
+}
+
+#[test]
+fn sort_dict_keys() {
+    init();
+    let config = Config {
+        sort_dict_keys: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#(b: 1, \"a\": 2, c: 3)", config),
+        "#(\"a\": 2, b: 1, c: 3)"
+    );
+    // spreads stay in place, what follows them overrides them.
+    similar_asserts::assert_eq!(
+        format("#(b: 1, ..base, d: 1, c: 2)", config),
+        "#(b: 1, ..base, c: 2, d: 1)"
+    );
+    // arguments are left alone.
+    similar_asserts::assert_eq!(format("#f(b: 1, a: 2)", config), "#f(b: 1, a: 2)");
+    similar_asserts::assert_eq!(format("#(:)", config), "#(:)");
+    similar_asserts::assert_eq!(
+        format("#(\n  b: 1, // bee\n  // about a\n  a: 2,\n)", config),
+        "#(\n  // about a\n  a: 2,\n  b: 1, // bee\n)"
+    );
+    // keys that are expressions sort by their whole text.
+    similar_asserts::assert_eq!(
+        format("#((\"b\"): 1, (\"a\"): 2)", config),
+        "#((\"a\"): 2, (\"b\"): 1)"
+    );
+    let spaced = Config {
+        space_inside_parens: true,
+        ..config
+    };
+    similar_asserts::assert_eq!(format("#(b: 1, a: 2)", spaced), "#( a: 2, b: 1 )");
+    // the dictionaries in arguments are sorted, not the arguments.
+    similar_asserts::assert_eq!(
+        format("#f(b: 1, a: (d: 1, c: 2))", config),
+        "#f(b: 1, a: (c: 2, d: 1))"
+    );
+    similar_asserts::assert_eq!(
+        format("#(b: 1, /* about a */ a: 2)", config),
+        "#(\n  /* about a */\n  a: 2,\n  b: 1,\n)"
+    );
+}
+test_eq!(
+    sort_dict_keys_comments_stay,
+    "#(\n  // about a\n  a: 2,\n  b: 1, // bee\n)",
+    Config {
+        sort_dict_keys: true,
+        ..Default::default()
+    }
+);
+
+#[test]
+fn sort_named_args() {
+    init();
+    let config = Config {
+        sort_named_args: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#set text(size: 11pt, font: \"Inter\")", config),
+        "#set text(font: \"Inter\", size: 11pt)"
+    );
+    similar_asserts::assert_eq!(
+        format("#f(b: 1, x, a: 2, y)[body]", config),
+        "#f(x, y, a: 2, b: 1)[body]"
+    );
+    // dictionaries are left alone.
+    similar_asserts::assert_eq!(format("#(b: 1, a: 2)", config), "#(b: 1, a: 2)");
+}
+test_eq!(
+    keep_collection_layout_tight,
+    "#f(aaaa, bbbb, cccc)",
+    Config {
+        max_line_length: 5,
+        keep_collection_layout: true,
+        ..Default::default()
+    }
+);
+test_eq!(
+    keep_collection_layout_broken,
+    "#let a = (\n  1, 2,\n)",
+    Config {
+        keep_collection_layout: true,
+        ..Default::default()
+    }
+);
+
+#[test]
+fn minimal_breaks_long_lines() {
+    init();
+    let config = |mode| Config {
+        max_line_length: 10,
+        mode,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#f(aaaa, bbbb, cccc)", config(Mode::Minimal)),
+        format("#f(aaaa, bbbb, cccc)", config(Mode::Canonical))
+    );
+}
+
+#[test]
+fn no_space_before_comma() {
+    init();
+    let config = Config::default();
+    similar_asserts::assert_eq!(format("#f(a , b)", config), "#f(a, b)");
+    similar_asserts::assert_eq!(format("#(a , b)", config), "#(a, b)");
+    similar_asserts::assert_eq!(format("#(k: v , j: w)", config), "#(k: v, j: w)");
+    similar_asserts::assert_eq!(format("#let (a , b) = x", config), "#let (a, b) = x");
+    similar_asserts::assert_eq!(format("#let f(a , b) = a", config), "#let f(a, b) = a");
+    let config = Config {
+        magic_trailing_comma: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format("#f(a , b ,)", config), "#f(\n  a,\n  b,\n)");
+}
+
+#[test]
+fn magic_trailing_comma() {
+    init();
+    let config = Config {
+        magic_trailing_comma: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format("#f(a, b,)", config), "#f(\n  a,\n  b,\n)");
+    similar_asserts::assert_eq!(format("#f(\n  a,\n  b\n)", config), "#f(a, b)");
+    similar_asserts::assert_eq!(format("#(auto,)", config), "#(auto,)");
+}
+
+#[test]
+fn sort_imports() {
+    init();
+    let config = Config {
+        sort_imports: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format(
+            "#import \"b.typ\": y, x\n// the template\n#import \"a.typ\": conf\n#import \"b.typ\": z\n#let a = 1\n#import \"0.typ\": *\n",
+            config
+        ),
+        "// the template\n#import \"a.typ\": conf\n#import \"b.typ\": x, y, z\n#let a = 1\n#import \"0.typ\": *\n"
+    );
+    // the `x` of `a.typ` wins, sorting would change that.
+    let clash = "#import \"b.typ\": x\n#import \"a.typ\": x\n";
+    similar_asserts::assert_eq!(format(clash, config), clash);
+    // `c.typ` may export an `x`, the imports don't move across it.
+    let wildcard = "#import \"b.typ\": x\n#import \"c.typ\": *\n#import \"a.typ\": x\n";
+    similar_asserts::assert_eq!(format(wildcard, config), wildcard);
+    similar_asserts::assert_eq!(
+        format(
+            "#import \"z.typ\": a\n#import \"y.typ\": b\n#import \"m.typ\": *\n#import \"e.typ\": c\n#import \"d.typ\": d\n",
+            config
+        ),
+        "#import \"y.typ\": b\n#import \"z.typ\": a\n#import \"m.typ\": *\n#import \"d.typ\": d\n#import \"e.typ\": c\n"
+    );
+}
+
+#[test]
+fn closure_spacing() {
+    init();
+    similar_asserts::assert_eq!(
+        format("#let f = (x)=>x", Config::default()),
+        "#let f = (x) => x"
+    );
+    similar_asserts::assert_eq!(format("#let g(a)=a", Config::default()), "#let g(a) = a");
+}
+
+#[test]
+fn call_overrides() {
+    init();
+    let config = Config {
+        verbatim_args_calls: Names::new(&["table", "cetz.canvas"]),
+        always_break_calls: Names::new(&["figure"]),
+        never_break_calls: Names::new(&["link"]),
+        ..Default::default()
+    };
+    let verbatim = "#table(columns:2,  [a],[b])\n#cetz.canvas({line((0,0),(1,1))})";
+    similar_asserts::assert_eq!(format(verbatim, config), verbatim);
+    similar_asserts::assert_eq!(
+        format("#figure(image(\"a.png\"), caption: [A cat])", config),
+        "#figure(\n  image(\"a.png\"),\n  caption: [A cat],\n)"
+    );
+    let link = "#link(\"https://example.com/a/very/long/path/that/goes/over/the/maximum/line/length\")[the docs]";
+    similar_asserts::assert_eq!(format(link, config), link);
+}
+
+#[test]
+fn spread_and_destructuring() {
+    init();
+    similar_asserts::assert_eq!(
+        format("#let f(.. args) = g(.. args)", Config::default()),
+        "#let f(..args) = g(..args)"
+    );
+    similar_asserts::assert_eq!(
+        format("#let (a,..) = (1, 2)", Config::default()),
+        "#let (a, ..) = (1, 2)"
+    );
+    let sinks = "#let (a, _) = (1, 2)\n#let (a: x, ..rest) = d\n#let (a,) = (1,)";
+    similar_asserts::assert_eq!(format(sinks, Config::default()), sinks);
+    similar_asserts::assert_eq!(
+        format("#{\n  (a,b)=(b,a)\n}", Config::default()),
+        "#{\n  (a, b) = (b, a)\n}"
+    );
+}
+
+#[test]
+fn nested_width_counts_the_column() {
+    init();
+    let config = Config {
+        magic_trailing_comma: true,
+        ..Default::default()
+    };
+    // `dark` fits in 80 columns alone, not once indented after `dark: `.
+    let src = "#let theme = config(\n  palette: (\n    dark: (background: \"#111111\", foreground: \"#eeeeee\", highlight: \"#ff880080\"),\n  ),\n)";
+    let formatted = "#let theme = config(\n  palette: (\n    dark: (\n      background: \"#111111\",\n      foreground: \"#eeeeee\",\n      highlight: \"#ff880080\",\n    ),\n  ),\n)";
+    similar_asserts::assert_eq!(format(src, config), formatted);
+    // the column is estimated from the source, the broken output must stay as is.
+    similar_asserts::assert_eq!(format(formatted, config), formatted);
+}
+
+#[test]
+fn hug_trailing_content_block() {
+    init();
+    let src =
+        "#figure(caption: [A cat])[ #image(\"pictures/a-cat-sleeping-in-the-sun-on-the-sofa.png\") ]";
+    let hugged = "#figure(caption: [A cat])[\n  #image(\"pictures/a-cat-sleeping-in-the-sun-on-the-sofa.png\")\n]";
+    similar_asserts::assert_eq!(format(src, Config::default()), hugged);
+    similar_asserts::assert_eq!(format(hugged, Config::default()), hugged);
+    similar_asserts::assert_eq!(
+        format(
+            "#box(width: 50%, inset: 8pt, fill: luma(230), stroke: 1pt + black, radius: 4pt)[a]",
+            Config::default()
+        ),
+        "#box(\n  width: 50%, inset: 8pt, fill: luma(230), stroke: 1pt + black, radius: 4pt,\n)[a]"
+    );
+}
+
+#[test]
+fn delimiter_spacing() {
+    init();
+    let config = Config {
+        space_inside_parens: true,
+        space_inside_braces: false,
+        space_before_colon: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#f(a,b, key:1)\n#let x = ()\n#{x}", config),
+        "#f( a, b, key : 1 )\n#let x = ()\n#{x}"
+    );
+    let config = Config {
+        space_after_colon: false,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(format("#(a: 1, \"b\": 2)", config), "#(a:1, \"b\":2)");
+}
+
+#[test]
+fn max_elements_inline() {
+    init();
+    let config = Config {
+        max_args_inline: 2,
+        max_array_items_inline: 3,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format("#f(a, b)[c]\n#f(a, b, c)[d]", config),
+        "#f(a, b)[c]\n#f(\n  a,\n  b,\n  c,\n)[d]"
+    );
+    similar_asserts::assert_eq!(
+        format(
+            "#let a = (1, 2, 3)\n#let b = (1, 2, 3, 4)\n#let c = (a: 1, b: 2, c: 3)",
+            config
+        ),
+        "#let a = (1, 2, 3)\n#let b = (\n  1,\n  2,\n  3,\n  4,\n)\n#let c = (a: 1, b: 2, c: 3)"
+    );
+}
@@ -0,0 +1,171 @@
+use super::*;
+
+#[test]
+fn on_type_closing_brace() {
+    init();
+    let s = "#let a = (1,2)\n#{\nlet b  =  1\n}";
+    similar_asserts::assert_eq!(
+        format_on_type(s, s.len(), Config::default()),
+        Some(TextEdit {
+            range: 16..s.len(),
+            replacement: "{\n  let b = 1\n}".to_string(),
+        })
+    );
+}
+
+#[test]
+fn on_type_newline() {
+    init();
+    let s = "#{\n  let b  =  1\n";
+    similar_asserts::assert_eq!(
+        format_on_type(s, s.len(), Config::default()),
+        Some(TextEdit {
+            range: 5..16,
+            replacement: "let b = 1".to_string(),
+        })
+    );
+}
+
+#[test]
+fn on_type_nothing_to_do() {
+    init();
+    let s = "#{\n  let b = 1\n}";
+    similar_asserts::assert_eq!(format_on_type(s, s.len(), Config::default()), None);
+    let s = "some text\n";
+    similar_asserts::assert_eq!(format_on_type(s, s.len(), Config::default()), None);
+    let s = "// typstfmt::off\n#{\nlet b  =  1\n}";
+    similar_asserts::assert_eq!(format_on_type(s, s.len(), Config::default()), None);
+}
+
+#[test]
+fn several_ranges() {
+    init();
+    let s = "#f(a ,b)\ntext\n#g(c ,d)\n";
+    similar_asserts::assert_eq!(
+        format_ranges(s, &[3..4, 2..8, 17..18], Config::default()),
+        vec![
+            TextEdit {
+                range: 2..8,
+                replacement: "(a, b)".to_string(),
+            },
+            TextEdit {
+                range: 16..22,
+                replacement: "(c, d)".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn enclosing() {
+    init();
+    let s = "#let a = 1\n#{\n  let x  =  (1,2)\n}\n";
+    // in `(1,2)`.
+    similar_asserts::assert_eq!(
+        format_enclosing(s, 27, Config::default()),
+        Some(TextEdit {
+            range: 26..31,
+            replacement: "(1, 2)".to_string(),
+        })
+    );
+    // in `let`.
+    similar_asserts::assert_eq!(
+        format_enclosing(s, 17, Config::default()),
+        Some(TextEdit {
+            range: 16..31,
+            replacement: "let x = (1, 2)".to_string(),
+        })
+    );
+}
+
+#[test]
+fn range() {
+    init();
+    let s = "#f(a ,b)\ntext  here\n";
+    similar_asserts::assert_eq!(
+        format_range(s, 3..6, Config::default()),
+        Some(("#f(a, b)\ntext  here\n".to_string(), 2..8))
+    );
+    similar_asserts::assert_eq!(format_range("#f(a, b)\n", 3..6, Config::default()), None);
+}
+
+#[test]
+fn edits() {
+    init();
+    let s = "#f(a ,b)\n#g()\n";
+    similar_asserts::assert_eq!(
+        format_edits(s, Config::default()),
+        vec![TextEdit {
+            range: 4..6,
+            replacement: ", ".to_string(),
+        }]
+    );
+    let formatted = format(s, Config::default());
+    similar_asserts::assert_eq!(format_edits(&formatted, Config::default()), vec![]);
+}
+
+#[test]
+fn incremental() {
+    init();
+    let mut doc = Incremental::new("#f(a ,b)\n#let x  = 1\n= Title\n", Config::default());
+    similar_asserts::assert_eq!(doc.format().text, "#f(a, b)\n#let x = 1\n= Title\n");
+    doc.edit(4..6, ",  c,");
+    similar_asserts::assert_eq!(doc.text(), "#f(a,  c,b)\n#let x  = 1\n= Title\n");
+    let output = doc.format();
+    similar_asserts::assert_eq!(output.text, format(doc.text(), Config::default()));
+    similar_asserts::assert_eq!(output.text, "#f(a, c, b)\n#let x = 1\n= Title\n");
+}
+
+#[test]
+fn incremental_keeps_let_runs_aligned() {
+    init();
+    let src = "#let a   = 1 // fmt: align\n#let bbb = 2\n";
+    let mut doc = Incremental::new(src, Config::default());
+    similar_asserts::assert_eq!(doc.format().text, src);
+    similar_asserts::assert_eq!(doc.format().text, src);
+    doc.edit(src.len()..src.len(), "text\n");
+    similar_asserts::assert_eq!(doc.format().text, format(doc.text(), Config::default()));
+}
+
+#[test]
+fn incremental_matches_format() {
+    init();
+    // the call moves right after the edit, it no longer fits on its line.
+    let call = "#figure(image(\"diagram.png\", width: 80%), caption: [A long caption])\n";
+    let mut doc = Incremental::new(call, Config::default());
+    similar_asserts::assert_eq!(doc.format().text, format(call, Config::default()));
+    doc.edit(0..0, "Some words before ");
+    similar_asserts::assert_eq!(doc.format().text, format(doc.text(), Config::default()));
+
+    // the regions skipped in a cached construct are still reported.
+    let config = Config {
+        max_format_depth: 3,
+        ..Default::default()
+    };
+    let mut doc = Incremental::new("#f(g(h(i(j(1)))))\n#let x  = 1\n", config);
+    let first = doc.format();
+    assert!(!first.skipped_regions.is_empty());
+    doc.edit(25..25, " ");
+    let output = doc.format();
+    assert_eq!(output.stats.rules.get("incremental_cache"), Some(&1));
+    assert_eq!(
+        output.skipped_regions,
+        format_output(doc.text(), config).skipped_regions
+    );
+    assert_eq!(output.text, format(doc.text(), config));
+}
+
+#[test]
+fn source_map() {
+    init();
+    let s = "#let  a=(1,2)\n#f(a)  ";
+    let (text, map) = format_with_map(s, Config::default());
+    similar_asserts::assert_eq!(text, "#let a = (1, 2)\n#f(a)");
+    // the `a` of the binding, the `2`, the `f` and the end.
+    assert_eq!(map.map_offset(6), 5);
+    assert_eq!(map.map_offset(11), 13);
+    assert_eq!(map.map_offset(15), 17);
+    assert_eq!(map.map_offset(s.len()), text.len());
+    // in the spaces after `let`, one of them was removed.
+    assert_eq!(map.map_offset(5), 4);
+}
@@ -41,7 +41,7 @@ test_eq!(
     line_wrap_off,
     "a very very very very very very very very very very very very very long line",
     Config {
-        line_wrap: false,
+        line_wrap: Wrap::Off,
         max_line_length: 50,
         ..Default::default()
     }
@@ -111,3 +111,216 @@ const ON_OFF: &str = r#"// typstfmt::off
 // typstfmt::on
 #{{4}}
 "#;
+
+#[test]
+fn fragment_is_indented() {
+    init();
+    let formatted = format_fragment("#{\na\n}", Config::default(), "    ");
+    similar_asserts::assert_eq!(formatted, "#{\n      a\n    }");
+    let formatted = format_fragment("```\nc\n```\n#f(\"a\nb\")", Config::default(), "    ");
+    similar_asserts::assert_eq!(formatted, "```\nc\n```\n    #f(\"a\nb\")");
+}
+
+#[test]
+fn stats_are_collected() {
+    init();
+    let stats = format_output(
+        "#f(aaaa, bbbb, cccc)",
+        Config {
+            max_line_length: 10,
+            ..Default::default()
+        },
+    )
+    .stats;
+    assert_eq!(stats.nodes.get("function call"), Some(&1));
+    assert_eq!(stats.rules.get("args_breaking"), Some(&1));
+    assert!(stats.time.is_empty());
+}
+
+#[test]
+fn timings_are_measured_on_request() {
+    init();
+    let src = "#f(aaaa, bbbb, cccc)";
+    let timed = format_output_timed(src, Config::default());
+    assert!(timed.stats.time.contains_key("function call"));
+    // the timings don't make the same formatting compare unequal.
+    assert_eq!(timed, format_output(src, Config::default()));
+}
+
+#[test]
+fn local_imports_are_listed() {
+    init();
+    similar_asserts::assert_eq!(
+        local_imports(
+            "#import \"template.typ\": conf\n#import \"@preview/cetz:0.1.2\"\n#include \"/chapters/one.typ\"\n"
+        ),
+        vec!["template.typ".to_string(), "/chapters/one.typ".to_string()]
+    );
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn format_many_keeps_order() {
+    init();
+    let sources = vec!["#f(a,b)", "#let x=1", "#{\na\n}"];
+    let formatted = format_many(sources.clone(), Config::default());
+    let expected: Vec<String> = sources
+        .iter()
+        .map(|s| format(s, Config::default()))
+        .collect();
+    similar_asserts::assert_eq!(formatted, expected);
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn format_batch_reports() {
+    init();
+    let sources = vec!["#f(a,b)", "#let x = 1", "#f(a,", "#{\na\n  b\n}"];
+    let (outputs, report) = format_batch(sources.clone(), Config::default());
+    similar_asserts::assert_eq!(outputs[0].text, "#f(a, b)");
+    assert_eq!(outputs.len(), 4);
+    assert_eq!(report.files, 4);
+    assert_eq!(report.reformatted, 2);
+    assert_eq!(report.unchanged, 2);
+    assert_eq!(report.parse_failures, 1);
+    assert_eq!(report.lines_changed, 2);
+}
+
+#[test]
+fn custom_width() {
+    init();
+    let input = "#f(aaaa, bbbb)";
+    similar_asserts::assert_eq!(format(input, Config::default()), input);
+    // every character counts ten times, the call doesn't fit on one line anymore.
+    let formatted =
+        format_output_with_width(input, Config::default(), |s| 10 * s.chars().count()).text;
+    assert!(formatted.contains('\n'), "{formatted}");
+    // the measure doesn't leak to the next formatting.
+    similar_asserts::assert_eq!(format(input, Config::default()), input);
+    // nor when the formatting panics.
+    let panicked = std::panic::catch_unwind(|| {
+        format_output_with_width(input, Config::default(), |_| panic!("measured"))
+    });
+    assert!(panicked.is_err());
+    similar_asserts::assert_eq!(format(input, Config::default()), input);
+    // the padding is measured too, a space is two columns here.
+    let aligned = "#let d = ( // fmt: align\n  a:   1,\n  bbb: 2,\n)";
+    let doubled = |s: &str| 2 * s.chars().count();
+    similar_asserts::assert_eq!(
+        format_output_with_width(aligned, Config::default(), doubled).text,
+        aligned
+    );
+}
+
+#[test]
+fn max_format_depth() {
+    init();
+    let config = Config {
+        max_format_depth: 1,
+        ..Default::default()
+    };
+    let output = format_output("#f(a,g( b ,c))", config);
+    similar_asserts::assert_eq!(output.text, "#f(a, g( b ,c))");
+    assert_eq!(output.skipped_regions.len(), 1);
+}
+
+#[test]
+fn long_binary_breaks() {
+    init();
+    let src = "#let total = (first-long-value + second-long-value + third-long-value + fourth-long-value + fifth-long-value)\n";
+    let formatted = format(src, Config::default());
+    similar_asserts::assert_eq!(
+        formatted,
+        "#let total = (first-long-value\n  + second-long-value\n  + third-long-value\n  + fourth-long-value\n  + fifth-long-value)\n"
+    );
+    similar_asserts::assert_eq!(format(&formatted, Config::default()), formatted);
+    // the operands are formatted once, their rules counted once.
+    let output = format_output(src, Config::default());
+    assert_eq!(output.stats.rules.get("binary_breaking"), Some(&1));
+}
+
+#[test]
+fn long_chain_breaks() {
+    init();
+    let src = "#{\n  let names = people.filter(person => person.age > 18).map(person => person.name).join(\", \")\n}";
+    let formatted = format(src, Config::default());
+    similar_asserts::assert_eq!(
+        formatted,
+        "#{\n  let names = people\n    .filter(person => person.age > 18)\n    .map(person => person.name)\n    .join(\", \")\n}"
+    );
+    similar_asserts::assert_eq!(format(&formatted, Config::default()), formatted);
+    let output = format_output(src, Config::default());
+    assert_eq!(output.stats.rules.get("chain_breaking"), Some(&1));
+    similar_asserts::assert_eq!(
+        format("#adult-people.filter(person => person.age > 18).map(person => person.name).join(\", \")", Config::default()),
+        "#(adult-people\n  .filter(person => person.age > 18)\n  .map(person => person.name)\n  .join(\", \"))"
+    );
+}
+
+#[test]
+fn set_and_show_rules() {
+    init();
+    similar_asserts::assert_eq!(
+        format("#set  text(red)  if  x", Config::default()),
+        "#set text(red) if x"
+    );
+    similar_asserts::assert_eq!(
+        format("#show  heading :set text(blue)", Config::default()),
+        "#show heading: set text(blue)"
+    );
+    similar_asserts::assert_eq!(
+        format("#show :template", Config::default()),
+        "#show: template"
+    );
+}
+
+struct Upper;
+
+impl EmbeddedFormatter for Upper {
+    fn format(&self, lang: &str, code: &str) -> Option<String> {
+        (lang == "upper").then(|| code.to_uppercase())
+    }
+}
+
+#[test]
+fn raw_blocks_kept() {
+    init();
+    let src = "#{\n  let a = [\n    ```py\n\tdef  f():  \n\t    pass\n    ```\n  ]\n}";
+    similar_asserts::assert_eq!(format(src, Config::default()), src);
+    let src = "Some text.\n```upper\nshout\n```\n```py\nquiet\n```\n";
+    similar_asserts::assert_eq!(
+        format_output_with_embedded(src, Config::default(), &[&Upper]).text,
+        "Some text.\n```upper\nSHOUT\n```\n```py\nquiet\n```\n"
+    );
+}
+
+#[test]
+fn normalize_string_escapes() {
+    init();
+    let config = Config {
+        normalize_string_escapes: true,
+        ..Default::default()
+    };
+    similar_asserts::assert_eq!(
+        format(r#"#let a = "caf\u{e9} \u{22} \u{200b} \n \\u{e9}""#, config),
+        r#"#let a = "café \" \u{200b} \n \\u{e9}""#
+    );
+    similar_asserts::assert_eq!(
+        format(r#"#let a = "\u{a}\u{9}\u{5c}\u{200B}\u{00a0}""#, config),
+        r#"#let a = "\n\t\\\u{200b}\u{a0}""#
+    );
+    let src = r#"#let a = "caf\u{e9}""#;
+    similar_asserts::assert_eq!(format(src, Config::default()), src);
+}
+
+#[test]
+fn markdown_typst_blocks() {
+    init();
+    let md = "# Title\n\n```typst\n#let a  = 1\n```\n\n- item\n\n  ```typ\n  #f(a,b)\n  ```\n\n```rust\nlet a  = 1;\n```\n\n````markdown\n```typst\n#let a  = 1\n```\n````\n";
+    similar_asserts::assert_eq!(
+        format_markdown(md, Config::default()),
+        "# Title\n\n```typst\n#let a = 1\n```\n\n- item\n\n  ```typ\n  #f(a, b)\n  ```\n\n```rust\nlet a  = 1;\n```\n\n````markdown\n```typst\n#let a  = 1\n```\n````\n"
+    );
+    let unclosed = "```typst\n#let a  = 1\n";
+    similar_asserts::assert_eq!(format_markdown(unclosed, Config::default()), unclosed);
+}
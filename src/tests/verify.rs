@@ -0,0 +1,50 @@
+use super::*;
+
+#[test]
+fn no_token_loss_on_formatting() {
+    init();
+    let src = "#f(a ,b,)\n#{\n  let x  =  1;\n}\n#let y = (x +   1)\n";
+    let out = format(src, Config::default());
+    similar_asserts::assert_eq!(verify::no_token_loss(src, &out), Ok(()));
+    similar_asserts::assert_eq!(verify::no_token_loss("#f(a, b)", "#f(a, b)"), Ok(()));
+    similar_asserts::assert_eq!(
+        verify::no_token_loss("#f(a, b)", "#f(a)"),
+        Err("`,` at 1:5 of the source was not kept, found `)`".to_string())
+    );
+    similar_asserts::assert_eq!(
+        verify::no_token_loss("some text", "some text text"),
+        Err("`text` at 1:11 of the output is not in the source".to_string())
+    );
+}
+
+#[test]
+fn same_syntax() {
+    init();
+    let src = "#f(a ,b,)\n#{\n  let x  =  1;\n}\n#let y = (x +   1)\n";
+    let out = format(src, Config::default());
+    similar_asserts::assert_eq!(verify::same_syntax(src, &out), Ok(()));
+    similar_asserts::assert_eq!(verify::same_syntax("#{emph[x]}", "#emph[x]"), Ok(()));
+    similar_asserts::assert_eq!(
+        verify::same_syntax("#(b: 1, a: 2)", "#(a: 2, b: 1)"),
+        Ok(())
+    );
+    assert!(verify::same_syntax("#f(a, b)", "#f(b, a)").is_err());
+    assert!(verify::same_syntax("a\n- b", "a - b").is_err());
+}
+
+#[test]
+fn check() {
+    init();
+    let src = "= Title\n#f(a ,b,)\n#{\n  let x  =  1;\n}\n$ a &= b + c $\n- item\n";
+    similar_asserts::assert_eq!(verify::check(src, Config::default()), Ok(()));
+    // the guarantees are about documents that parse.
+    similar_asserts::assert_eq!(verify::check("#f(a,", Config::default()), Ok(()));
+}
+
+#[cfg(feature = "testing")]
+proptest::proptest! {
+    #[test]
+    fn check_snippets(src in verify::snippet()) {
+        proptest::prop_assert_eq!(verify::check(&src, Config::default()), Ok(()));
+    }
+}
@@ -1,11 +1,153 @@
 use super::*;
-use unicode_width::UnicodeWidthStr as _;
+use std::cell::Cell;
+use std::thread::LocalKey;
+use unicode_width::UnicodeWidthStr;
+
+thread_local! {
+    /// measures the width of a line, see [crate::format_output_with_width].
+    static WIDTH: Cell<fn(&str) -> usize> = Cell::new(<str as UnicodeWidthStr>::width);
+    /// true if the time spent on each kind of node is measured, see
+    /// [crate::format_output_timed].
+    static TIMED: Cell<bool> = Cell::new(false);
+}
+
+/// Sets a thread local until it is dropped, the previous value is put back even
+/// if the formatting panics.
+struct Restore<T: Copy + 'static> {
+    key: &'static LocalKey<Cell<T>>,
+    previous: T,
+}
+
+impl<T: Copy + 'static> Restore<T> {
+    fn set(key: &'static LocalKey<Cell<T>>, value: T) -> Self {
+        Self {
+            key,
+            previous: key.replace(value),
+        }
+    }
+}
+
+impl<T: Copy + 'static> Drop for Restore<T> {
+    fn drop(&mut self) {
+        self.key.set(self.previous);
+    }
+}
+
+/// runs `f` measuring the time spent on each kind of node.
+pub(crate) fn with_timings<T>(f: impl FnOnce() -> T) -> T {
+    let _timed = Restore::set(&TIMED, true);
+    f()
+}
+
+/// true if the time spent on each kind of node is measured.
+pub(crate) fn timed() -> bool {
+    TIMED.get()
+}
+
+/// runs `f` measuring lines with `width`.
+pub(crate) fn with_width<T>(width: fn(&str) -> usize, f: impl FnOnce() -> T) -> T {
+    let _width = Restore::set(&WIDTH, width);
+    f()
+}
+
+/// the width of a line, in columns of a monospace font unless an embedder chose
+/// another measure.
+pub(crate) fn width(s: &str) -> usize {
+    WIDTH.get()(s)
+}
+
+/// the spaces taking at least `columns` columns as [width] measures them, to pad
+/// by the difference of two widths.
+pub(crate) fn padding(columns: usize) -> String {
+    let space = width(" ").max(1);
+    " ".repeat(columns.div_ceil(space))
+}
+
+/// A comment controlling the formatter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Directive {
+    /// disables the formatting until `on`.
+    Off,
+    On,
+    /// the next node is kept as written.
+    Ignore,
+    /// probably a typo.
+    Unknown,
+}
+
+/// the directive of a comment, written `typstfmt::off` or `typstfmt: off`, `None`
+/// for the other nodes: a string containing `typstfmt:` isn't one.
+pub(crate) fn directive(node: &typst_syntax::SyntaxNode) -> Option<Directive> {
+    if !matches!(node.kind(), LineComment | BlockComment) {
+        return None;
+    }
+    comment_directive(node.text())
+}
+
+/// the directive of the text of a comment.
+pub(crate) fn comment_directive(comment: &str) -> Option<Directive> {
+    let (_, rest) = comment.split_once("typstfmt:")?;
+    let word = rest
+        .trim_start_matches(':')
+        .trim_start()
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default();
+    Some(match word {
+        "off" => Directive::Off,
+        "on" => Directive::On,
+        "ignore" => Directive::Ignore,
+        _ => Directive::Unknown,
+    })
+}
+
+/// true if `node` follows a `typstfmt: ignore` comment, a space and the `#` of
+/// embedded code may be between them.
+pub(crate) fn is_ignored(node: &LinkedNode) -> bool {
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    if node.kind().is_trivia() || node.kind() == Hash {
+        return false;
+    }
+    // on the nodes rather than with `prev_sibling_or_trivia`, this is called for
+    // every node.
+    let siblings = parent.get().children().as_slice();
+    let mut i = node.index();
+    if i > 0 && siblings[i - 1].kind() == Hash {
+        i -= 1;
+    }
+    while i > 0 && siblings[i - 1].kind() == Space {
+        i -= 1;
+    }
+    i > 0 && directive(&siblings[i - 1]) == Some(Directive::Ignore)
+}
 
 /// like next sibling but doesn't skip trivia.
 pub(crate) fn next_sibling_or_trivia<'a>(node: &LinkedNode<'a>) -> Option<LinkedNode<'a>> {
     node.parent()?.children().nth(node.index() + 1)
 }
 
+/// true if `node` is a block comment followed on its line by an item of its
+/// collection, `/* about b */ b`: the comment goes with the item.
+pub(crate) fn comment_precedes_item(node: &LinkedNode) -> bool {
+    if node.kind() != BlockComment {
+        return false;
+    }
+    let next = match next_sibling_or_trivia(node) {
+        Some(space) if space.kind() == Space && !space.text().contains('\n') => {
+            next_sibling_or_trivia(&space)
+        }
+        next => next,
+    };
+    next.is_some_and(|next| {
+        !matches!(
+            next.kind(),
+            Comma | RightParen | Space | LineComment | BlockComment
+        )
+    })
+}
+
 /// like next sibling but doesn't skip trivia.
 pub(crate) fn prev_sibling_or_trivia<'a>(node: &LinkedNode<'a>) -> Option<LinkedNode<'a>> {
     if node.index() == 0 {
@@ -38,6 +180,164 @@ pub(crate) fn find_child<'a>(
     None
 }
 
+/// true if the parent of this node is the root of the tree.
+pub(crate) fn is_top_level(node: &LinkedNode) -> bool {
+    node.parent().is_some_and(|p| p.parent().is_none())
+}
+
+/// true if the author broke this node across lines, only looks at the direct children.
+pub(crate) fn source_is_multiline(node: &LinkedNode) -> bool {
+    node.children()
+        .any(|c| c.kind() == Space && c.text().contains('\n'))
+}
+
+/// the range and message of all the errors inside this node.
+pub(crate) fn errors(node: &LinkedNode) -> Vec<(std::ops::Range<usize>, String)> {
+    if node.kind() == Error {
+        return node
+            .errors()
+            .into_iter()
+            .map(|e| (node.range(), e.message.to_string()))
+            .collect();
+    }
+    node.children()
+        .filter(|c| c.erroneous())
+        .flat_map(|c| errors(&c))
+        .collect()
+}
+
+/// maps an offset in `expanded`, made by [expand_tabs] after removing the `\r` of
+/// the line endings, back to an offset in `s`.
+pub(crate) fn unexpand_offset(s: &str, expanded: &str, tab_width: usize, offset: usize) -> usize {
+    let mut pos = 0;
+    for (i, c) in s.char_indices() {
+        if pos >= offset {
+            return i;
+        }
+        pos += if c == '\t' && !expanded[pos..].starts_with('\t') {
+            tab_width
+        } else if c == '\r' && !expanded[pos..].starts_with('\r') {
+            0
+        } else {
+            c.len_utf8()
+        };
+    }
+    s.len()
+}
+
+/// true if more lines of `s` end with `\r\n` than with `\n` alone.
+pub(crate) fn mostly_crlf(s: &str) -> bool {
+    let crlf = s.matches("\r\n").count();
+    crlf > s.matches('\n').count() - crlf
+}
+
+/// ranges of all the comments in the tree.
+pub(crate) fn comment_ranges(node: &LinkedNode) -> Vec<std::ops::Range<usize>> {
+    if matches!(node.kind(), LineComment | BlockComment) {
+        return vec![node.range()];
+    }
+    node.children().flat_map(|c| comment_ranges(&c)).collect()
+}
+
+/// applies `f` to the parts of the source outside comments, the text of comments
+/// is copied byte for byte.
+pub(crate) fn map_outside_comments(s: &str, f: impl Fn(&str) -> String) -> String {
+    let root = parse(s);
+    let mut res = String::new();
+    let mut last = 0;
+    for range in comment_ranges(&LinkedNode::new(&root)) {
+        res.push_str(&f(&s[last..range.start]));
+        res.push_str(&s[range.clone()]);
+        last = range.end;
+    }
+    res.push_str(&f(&s[last..]));
+    res
+}
+
+/// replaces tabs by `tab_width` spaces, except in comments.
+pub(crate) fn expand_tabs(s: &str, tab_width: usize) -> String {
+    // most documents have no tab, they aren't parsed for their comments.
+    if !s.contains('\t') {
+        return s.to_string();
+    }
+    map_outside_comments(s, |part| part.replace('\t', &" ".repeat(tab_width)))
+}
+
+/// replaces each `tab_width` spaces indenting a line by a tab, the spaces left
+/// over align the line. Only the whitespace of the syntax is changed, not the
+/// lines of strings, raw blocks or comments.
+pub(crate) fn indent_with_tabs(s: &str, tab_width: usize) -> String {
+    fn whitespace(node: &LinkedNode, ranges: &mut Vec<std::ops::Range<usize>>) {
+        match node.kind() {
+            Space | Parbreak if node.text().contains('\n') => ranges.push(node.range()),
+            _ => node.children().for_each(|c| whitespace(&c, ranges)),
+        }
+    }
+    let tab_width = tab_width.max(1);
+    if !s.contains(&format!("\n{}", " ".repeat(tab_width))) {
+        return s.to_string();
+    }
+    let root = parse(s);
+    let mut ranges = vec![];
+    whitespace(&LinkedNode::new(&root), &mut ranges);
+    let mut res = String::new();
+    let mut last = 0;
+    for range in ranges {
+        res.push_str(&s[last..range.start]);
+        let mut lines = s[range.clone()].split('\n');
+        res.push_str(lines.next().unwrap_or_default());
+        for line in lines {
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            res.push('\n');
+            res.push_str(&"\t".repeat(spaces / tab_width));
+            res.push_str(&line[spaces / tab_width * tab_width..]);
+        }
+        last = range.end;
+    }
+    res.push_str(&s[last..]);
+    res
+}
+
+/// removes the spaces ending lines, except in comments.
+pub(crate) fn trim_line_ends(s: &str) -> String {
+    if !s.contains(" \n") {
+        return s.to_string();
+    }
+    let re = regex::Regex::new("( )+\n").unwrap();
+    map_outside_comments(s, |part| re.replace_all(part, "\n").to_string())
+}
+
+/// line and column (starting at 1) where this node starts in the source.
+pub(crate) fn line_col(node: &LinkedNode) -> (usize, usize) {
+    let mut root = node;
+    while let Some(parent) = root.parent() {
+        root = parent;
+    }
+    let text = root.get().clone().into_text();
+    let before = &text[..node.offset()];
+    let line = before.matches('\n').count() + 1;
+    let col = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    (line, col)
+}
+
+/// the source of the line `node` starts on, up to the node.
+pub(crate) fn line_before(node: &LinkedNode) -> String {
+    // walking back the leaves rather than copying the text of the whole document,
+    // that would be quadratic.
+    let mut parts = vec![];
+    let mut leaf = node.prev_leaf();
+    while let Some(prev) = leaf {
+        let text = prev.get().text();
+        if let Some((_, last)) = text.rsplit_once('\n') {
+            parts.push(last);
+            break;
+        }
+        parts.push(text);
+        leaf = prev.prev_leaf();
+    }
+    parts.into_iter().rev().collect()
+}
+
 #[derive(Debug, Default, PartialEq)]
 pub(crate) enum Btype {
     #[default]
@@ -62,6 +362,17 @@ pub(crate) fn block_type(node: &LinkedNode) -> Btype {
     }
 }
 
+/// the ranges of the raw blocks and strings in `node`, their text is kept as is.
+pub(crate) fn verbatim_ranges(node: &LinkedNode, res: &mut Vec<std::ops::Range<usize>>) {
+    for child in node.children() {
+        if matches!(child.kind(), Raw | Str) {
+            res.push(child.range());
+        } else {
+            verbatim_ranges(&child, res);
+        }
+    }
+}
+
 /// find all children recursively that fits predicate
 // pub(crate) fn find_children<'a>(
 //     res: &mut Vec<LinkedNode<'a>>,
@@ -140,14 +451,121 @@ pub(crate) fn prev_is_ignoring(node: &LinkedNode, is: SyntaxKind, ignoring: &[Sy
     n.is_some_and(|n| is == n.kind())
 }
 
+/// removes the blank lines right after the line opening a block and right before
+/// the line closing it, `open` and `close` being the delimiters alone on these lines.
+pub(crate) fn trim_blank_edge_lines(block: &str, open: char, close: char) -> String {
+    let lines = block.split('\n').collect_vec();
+    let last = lines.len().saturating_sub(1);
+    if lines.len() < 3
+        || lines[0].trim_end() != open.to_string()
+        || !lines[last].trim_start().starts_with(close)
+    {
+        return block.to_string();
+    }
+    let blank = |i: &usize| lines[*i].trim().is_empty();
+    let start = (1..last).find(|i| !blank(i)).unwrap_or(last);
+    let end = (start..last)
+        .rev()
+        .find(|i| !blank(i))
+        .map_or(start, |i| i + 1);
+    std::iter::once(lines[0])
+        .chain(lines[start..end].iter().copied())
+        .chain(std::iter::once(lines[last]))
+        .join("\n")
+}
+
+/// how many blocks and collections `node` is in, itself included if it is one.
+pub(crate) fn nesting_depth(node: &LinkedNode) -> usize {
+    let nests = |kind: SyntaxKind| {
+        matches!(
+            kind,
+            CodeBlock | ContentBlock | Args | Params | Array | Dict | Parenthesized
+        )
+    };
+    if !nests(node.kind()) {
+        return 0;
+    }
+    std::iter::successors(Some(node.clone()), |n| n.parent().cloned())
+        .filter(|n| nests(n.kind()))
+        .count()
+}
+
+/// true if formatting was disabled somewhere under `node`, its text is then kept.
+pub(crate) fn contains_directive(node: &LinkedNode) -> bool {
+    find_child(node, &|c| {
+        matches!(c.kind(), LineComment | BlockComment) && c.text().contains("typstfmt:")
+    })
+    .is_some()
+}
+
+/// the column `node` starts at once formatted, on a line indented by
+/// `indent_level` levels of `indent_space` columns.
+///
+/// The text before it on its line in the source is counted, runs of spaces as
+/// one. The enclosing delimited constructs and chains not broken yet are assumed
+/// to be: the text before their `(`, `[`, `{` or `.` isn't counted, nor the text
+/// of markup, that is wrapped.
+pub(crate) fn start_column(node: &LinkedNode, indent_level: usize, indent_space: usize) -> usize {
+    let encloses = |parent: &LinkedNode| parent.range().contains(&node.offset());
+    let mut column = 0;
+    let mut leaf = node.prev_leaf();
+    while let Some(prev) = leaf {
+        let text = prev.text();
+        if let Some((_, last)) = text.rsplit_once('\n') {
+            column += width(last.trim_start());
+            break;
+        }
+        match prev.kind() {
+            LeftParen | LeftBracket | LeftBrace if prev.parent().is_some_and(|p| encloses(&p)) => {
+                // the constructs broken in the source are in `indent_level`.
+                if !prev.parent().is_some_and(|p| indents_children(&p)) {
+                    column += indent_space;
+                }
+                break;
+            }
+            Dot if prev
+                .parent()
+                .and_then(|p| p.parent())
+                .is_some_and(|p| encloses(&p)) =>
+            {
+                column += indent_space + 1;
+                break;
+            }
+            _ => {}
+        }
+        if prev.parent_kind() == Some(Markup) && prev.kind() != Hash {
+            break;
+        }
+        column += if prev.kind() == Space { 1 } else { width(text) };
+        leaf = prev.prev_leaf();
+    }
+    indent_level * indent_space + column
+}
+
+/// true if the children of `node` are indented by one more level, the
+/// delimited constructs the author broke across lines.
+pub(crate) fn indents_children(node: &LinkedNode) -> bool {
+    let broken = |node: &LinkedNode| {
+        node.children()
+            .any(|c| c.kind() == Parbreak || (c.kind() == Space && c.text().contains('\n')))
+    };
+    match node.kind() {
+        Args | Params | Array | Dict | Destructuring | Parenthesized => broken(node),
+        CodeBlock | ContentBlock => node
+            .children()
+            .any(|c| matches!(c.kind(), Code | Markup) && broken(&c)),
+        _ => false,
+    }
+}
+
 pub(crate) fn max_line_length(s: &str) -> usize {
-    s.lines().map(|l| l.width()).max().unwrap_or(0)
+    s.lines().map(width).max().unwrap_or(0)
 }
 
 pub(crate) fn last_line_length(s: &str) -> usize {
-    s.split('\n').last().unwrap_or("").width()
+    width(s.split('\n').last().unwrap_or(""))
 }
 
 pub(crate) fn first_line_length(s: &str) -> usize {
-    s.split('\n').next().unwrap_or("").width()
+    width(s.split('\n').next().unwrap_or(""))
 }
@@ -0,0 +1,217 @@
+//! Checks catching the worst formatter bugs, code being dropped or duplicated.
+
+use super::*;
+
+#[cfg(feature = "testing")]
+mod snippets;
+#[cfg(feature = "testing")]
+pub use snippets::snippet;
+
+/// Checks what formatting `src` with `config` must guarantee when `src` parses:
+/// the output parses, formatting it again changes nothing and it has the syntax
+/// tree of `src`, see [same_syntax]. The inputs with syntax errors pass.
+///
+/// Returns a message describing the first broken guarantee, fuzzers and property
+/// tests can call it on any input.
+pub fn check(src: &str, config: Config) -> Result<(), String> {
+    if parse(src).erroneous() {
+        return Ok(());
+    }
+    let out = format(src, config);
+    let out_root = parse(&out);
+    if let Some((range, message)) = utils::errors(&LinkedNode::new(&out_root)).first() {
+        let (line, col) = line_col(&out, range.start);
+        return Err(format!(
+            "the output doesn't parse, {message} at {line}:{col}"
+        ));
+    }
+    let again = format(&out, config);
+    if let Some((i, (a, b))) = out
+        .lines()
+        .zip(again.lines())
+        .enumerate()
+        .find(|(_, (a, b))| a != b)
+    {
+        return Err(format!(
+            "formatting is not idempotent, line {} `{a}` became `{b}`",
+            i + 1
+        ));
+    }
+    if again != out {
+        return Err("formatting is not idempotent, the line count changed".to_string());
+    }
+    same_syntax(src, &out)
+}
+
+/// Checks that every non whitespace lexeme of `src` appears in `out`, in the same
+/// order and with the same text, and that `out` has no other.
+///
+/// What the formatter is allowed to change is ignored: trailing commas and
+/// semicolons and the parentheses grouping an expression. The opt-in rewrites of
+/// comments (`space_after_line_comment` for instance) are reported.
+///
+/// Returns a message describing the first difference.
+pub fn no_token_loss(src: &str, out: &str) -> Result<(), String> {
+    let expected = lexemes(src);
+    let found = lexemes(out);
+    for i in 0..expected.len().max(found.len()) {
+        match (expected.get(i), found.get(i)) {
+            (Some((_, a)), Some((_, b))) if a == b => {}
+            (Some((offset, a)), b) => {
+                let (line, col) = line_col(src, *offset);
+                let found = b.map_or("the end of the output".to_string(), |(_, b)| {
+                    format!("`{b}`")
+                });
+                return Err(format!(
+                    "`{a}` at {line}:{col} of the source was not kept, found {found}"
+                ));
+            }
+            (None, Some((offset, b))) => {
+                let (line, col) = line_col(out, *offset);
+                return Err(format!(
+                    "`{b}` at {line}:{col} of the output is not in the source"
+                ));
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `out` parses to the same syntax tree as `src`, whitespace and
+/// comments left out.
+///
+/// What the formatter is allowed to change is ignored: commas and semicolons,
+/// the parentheses grouping an expression, a code block around a single
+/// expression in markup removed by `unwrap_embedded_blocks` and the order of
+/// the named arguments and dictionary keys sorted by `sort_named_args` and
+/// `sort_dict_keys`.
+///
+/// Returns a message describing the first difference.
+pub fn same_syntax(src: &str, out: &str) -> Result<(), String> {
+    let (src_root, out_root) = (parse(src), parse(out));
+    let expected = shape(&LinkedNode::new(&src_root));
+    let found = shape(&LinkedNode::new(&out_root));
+    for i in 0..expected.len().max(found.len()) {
+        match (expected.get(i), found.get(i)) {
+            (Some((_, a)), Some((_, b))) if a == b => {}
+            (Some((offset, a)), b) => {
+                let (line, col) = line_col(src, *offset);
+                let found = b.map_or("the end of the output".to_string(), |(_, b)| b.clone());
+                return Err(format!("{a} at {line}:{col} of the source became {found}"));
+            }
+            (None, Some((offset, b))) => {
+                let (line, col) = line_col(out, *offset);
+                return Err(format!(
+                    "{b} at {line}:{col} of the output is not in the source"
+                ));
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// the nodes of the tree in depth first order, with their offset: the kind of
+/// inner nodes and the text of leaves.
+fn shape(node: &LinkedNode) -> Vec<(usize, String)> {
+    let kind = node.kind();
+    if kind.is_trivia() || matches!(kind, Comma | Semicolon) {
+        return vec![];
+    }
+    if node.children().len() == 0 {
+        return vec![(node.offset(), format!("`{}`", node.text()))];
+    }
+    if kind == Parenthesized {
+        return node
+            .children()
+            .filter(|c| !matches!(c.kind(), LeftParen | RightParen))
+            .flat_map(|c| shape(&c))
+            .collect();
+    }
+    if let Some(expr) = single_expr(node) {
+        return shape(&expr);
+    }
+    let mut children: Vec<_> = node.children().map(|c| shape(&c)).collect();
+    if matches!(kind, Args | Dict) {
+        // the named ones can be sorted, positional ones keep their order.
+        let is_named = |c: &Vec<(usize, String)>| {
+            c.first()
+                .is_some_and(|(_, s)| s == Named.name() || s == Keyed.name())
+        };
+        let (mut named, positional): (Vec<_>, Vec<_>) = children.into_iter().partition(is_named);
+        named.sort_by(|a, b| {
+            let text = |c: &Vec<(usize, String)>| c.iter().map(|(_, s)| s.clone()).collect_vec();
+            text(a).cmp(&text(b))
+        });
+        children = positional.into_iter().chain(named).collect();
+    }
+    let mut res = vec![(node.offset(), kind.name().to_string())];
+    res.extend(children.into_iter().flatten());
+    res.push((
+        node.offset() + node.len(),
+        format!("the end of {}", kind.name()),
+    ));
+    res
+}
+
+/// the expression of a code block in markup holding only it, the block can be
+/// removed without changing the meaning.
+fn single_expr<'a>(node: &LinkedNode<'a>) -> Option<LinkedNode<'a>> {
+    if node.kind() != CodeBlock || node.parent_kind() != Some(Markup) {
+        return None;
+    }
+    let code = node.children().find(|c| c.kind() == Code)?;
+    let mut exprs = code
+        .children()
+        .filter(|c| !c.kind().is_trivia() && c.kind() != Semicolon);
+    let expr = exprs.next()?;
+    exprs.next().is_none().then_some(expr)
+}
+
+/// the words of the leaves of `s`, with their offset.
+fn lexemes(s: &str) -> Vec<(usize, String)> {
+    let root = parse(s);
+    let mut res = vec![];
+    push_lexemes(&LinkedNode::new(&root), &mut res);
+    res
+}
+
+fn push_lexemes(node: &LinkedNode, res: &mut Vec<(usize, String)>) {
+    if node.children().len() > 0 {
+        for child in node.children() {
+            push_lexemes(&child, res);
+        }
+        return;
+    }
+    let ignored = match node.kind() {
+        Space | Parbreak => true,
+        LeftParen | RightParen => node.parent_kind() == Some(Parenthesized),
+        Comma | Semicolon => node.next_sibling().map_or(true, |next| {
+            matches!(next.kind(), RightParen | RightBrace | RightBracket)
+        }),
+        _ => false,
+    };
+    if ignored {
+        return;
+    }
+    let text = node.text();
+    for word in text.split_whitespace() {
+        let start = word.as_ptr() as usize - text.as_ptr() as usize;
+        res.push((node.offset() + start, word.to_string()));
+    }
+}
+
+/// line and column (starting at 1) of a byte offset.
+fn line_col(s: &str, offset: usize) -> (usize, usize) {
+    let before = &s[..offset];
+    let line = before.matches('\n').count() + 1;
+    let col = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    (line, col)
+}
@@ -0,0 +1,148 @@
+//! A generator of Typst documents for property tests, see [snippet].
+
+use proptest::prelude::*;
+
+/// Typst documents mixing markup, code and math, nested a few levels; give them
+/// to [super::check]:
+///
+/// ```ignore
+/// proptest! {
+///     #[test]
+///     fn formatting_is_safe(src in typstfmt::verify::snippet()) {
+///         prop_assert_eq!(typstfmt::verify::check(&src, Default::default()), Ok(()));
+///     }
+/// }
+/// ```
+///
+/// Most of them parse, the others exercise the handling of syntax errors.
+pub fn snippet() -> impl Strategy<Value = String> {
+    prop::collection::vec(block(), 1..6).prop_map(|blocks| blocks.join(""))
+}
+
+/// a line or a paragraph of the document.
+fn block() -> impl Strategy<Value = String> {
+    prop_oneof![
+        markup().prop_map(|m| format!("{m}\n")),
+        markup().prop_map(|m| format!("{m}\n\n")),
+        (1..4usize, markup()).prop_map(|(n, m)| format!("{} {m}\n", "=".repeat(n))),
+        markup().prop_map(|m| format!("- {m}\n")),
+        markup().prop_map(|m| format!("+ {m}\n")),
+        (ident(), code()).prop_map(|(i, c)| format!("#let {i} = {c}\n")),
+        (ident(), args()).prop_map(|(i, a)| format!("#set {i}{a}\n")),
+        (ident(), ident()).prop_map(|(a, b)| format!("#show {a}: {b}\n")),
+        statements().prop_map(|s| format!("#{{\n{s}\n}}\n")),
+        math().prop_map(|m| format!("$ {m} $\n")),
+        comment().prop_map(|c| format!("{c}\n")),
+    ]
+}
+
+/// inline markup.
+fn markup() -> impl Strategy<Value = String> {
+    let leaf = prop_oneof![word(), embedded(), math().prop_map(|m| format!("${m}$"))];
+    leaf.prop_recursive(3, 16, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 1..5).prop_map(|words| words.join(" ")),
+            inner.clone().prop_map(|m| format!("*{m}*")),
+            inner.clone().prop_map(|m| format!("_{m}_")),
+            (ident(), inner).prop_map(|(i, m)| format!("#{i}[{m}]")),
+        ]
+    })
+}
+
+/// an expression in code.
+fn code() -> impl Strategy<Value = String> {
+    let leaf = prop_oneof![
+        ident(),
+        (0..1000i32).prop_map(|n| n.to_string()),
+        word().prop_map(|w| format!("{w:?}")),
+        Just("none".to_string()),
+        Just("true".to_string()),
+    ];
+    leaf.prop_recursive(3, 24, 4, |inner| {
+        prop_oneof![
+            (inner.clone(), op(), inner.clone()).prop_map(|(a, op, b)| format!("{a} {op} {b}")),
+            (ident(), prop::collection::vec(inner.clone(), 0..4))
+                .prop_map(|(f, args)| format!("{f}({})", args.join(", "))),
+            prop::collection::vec(inner.clone(), 0..4)
+                .prop_map(|items| format!("({},)", items.join(", "))),
+            prop::collection::vec((ident(), inner.clone()), 1..4).prop_map(|pairs| {
+                let pairs: Vec<_> = pairs.iter().map(|(k, v)| format!("{k}: {v}")).collect();
+                format!("({})", pairs.join(", "))
+            }),
+            (inner.clone(), ident()).prop_map(|(a, f)| format!("{a}.{f}()")),
+            (inner.clone(), inner.clone(), inner)
+                .prop_map(|(c, a, b)| format!("if {c} {{ {a} }} else {{ {b} }}")),
+            // not [markup], an embedded expression is an expression.
+            prop::collection::vec(word(), 1..4).prop_map(|words| format!("[{}]", words.join(" "))),
+        ]
+    })
+}
+
+/// the statements of a code block, one per line.
+fn statements() -> impl Strategy<Value = String> {
+    let statement = prop_oneof![
+        code(),
+        (ident(), code()).prop_map(|(i, c)| format!("let {i} = {c}")),
+        (ident(), code(), code()).prop_map(|(i, c, b)| format!("for {i} in {c} {{ {b} }}")),
+        comment(),
+    ];
+    prop::collection::vec(statement, 1..5).prop_map(|s| s.join("\n"))
+}
+
+/// the content of an equation.
+fn math() -> impl Strategy<Value = String> {
+    let leaf = prop_oneof!["[a-z]", "[0-9]{1,3}", Just("alpha".to_string())];
+    leaf.prop_recursive(2, 12, 3, |inner| {
+        prop_oneof![
+            (
+                inner.clone(),
+                prop_oneof!["\\+", "-", "=", "&="],
+                inner.clone()
+            )
+                .prop_map(|(a, op, b)| format!("{a} {op} {b}")),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| format!("{a}/{b}")),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| format!("{a}^({b})")),
+            prop::collection::vec(inner, 1..4).prop_map(|rows| rows.join(" \\\\ ")),
+        ]
+    })
+}
+
+/// a call with its argument list.
+fn args() -> impl Strategy<Value = String> {
+    prop::collection::vec((ident(), code()), 0..4).prop_map(|named| {
+        let named: Vec<_> = named.iter().map(|(k, v)| format!("{k}: {v}")).collect();
+        format!("({})", named.join(", "))
+    })
+}
+
+/// an embedded expression in markup.
+fn embedded() -> impl Strategy<Value = String> {
+    prop_oneof![
+        ident().prop_map(|i| format!("#{i}")),
+        (ident(), args()).prop_map(|(i, a)| format!("#{i}{a}")),
+        code().prop_map(|c| format!("#({c})")),
+    ]
+}
+
+fn comment() -> impl Strategy<Value = String> {
+    prop_oneof![
+        word().prop_map(|w| format!("// {w}")),
+        word().prop_map(|w| format!("/* {w} */")),
+    ]
+}
+
+fn op() -> impl Strategy<Value = String> {
+    prop_oneof!["\\+", "-", "\\*", "==", "and", "or", "in"]
+}
+
+fn ident() -> impl Strategy<Value = String> {
+    const KEYWORDS: &[&str] = &[
+        "let", "set", "show", "if", "else", "for", "in", "while", "break", "continue", "return",
+        "import", "include", "none", "auto", "true", "false", "and", "or", "not", "as", "context",
+    ];
+    "[a-z][a-z0-9]{0,6}".prop_filter("a keyword", |i| !KEYWORDS.contains(&i.as_str()))
+}
+
+fn word() -> impl Strategy<Value = String> {
+    "[a-zA-Z]{1,10}[.,]?"
+}
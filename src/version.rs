@@ -0,0 +1,117 @@
+use std::fmt::Display;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// A requirement on the version of typstfmt like `^0.3`, with the meaning cargo
+/// gives it: `^` (the default) allows changes that are compatible, `~` patch
+/// changes only, `=` only the given version and `>=` any later version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct VersionReq {
+    op: Op,
+    major: u64,
+    minor: Option<u64>,
+    patch: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Caret,
+    Tilde,
+    Exact,
+    GreaterEq,
+}
+
+impl VersionReq {
+    /// Returns false if `version` (`major.minor.patch`) is invalid.
+    pub fn matches(&self, version: &str) -> bool {
+        let mut parts = version.split('.').map(|p| p.parse::<u64>());
+        let (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch))) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return false;
+        };
+        let version = (major, minor, patch);
+        let min = (self.major, self.minor.unwrap_or(0), self.patch.unwrap_or(0));
+        // first version not allowed anymore.
+        let end = match (self.op, self.minor, self.patch) {
+            (Op::GreaterEq, _, _) => None,
+            (Op::Exact, None, _) | (Op::Tilde, None, _) => Some((self.major + 1, 0, 0)),
+            (Op::Exact, Some(minor), None) | (Op::Tilde, Some(minor), _) => {
+                Some((self.major, minor + 1, 0))
+            }
+            (Op::Exact, Some(minor), Some(patch)) => Some((self.major, minor, patch + 1)),
+            (Op::Caret, _, _) if self.major > 0 => Some((self.major + 1, 0, 0)),
+            (Op::Caret, None, _) => Some((1, 0, 0)),
+            (Op::Caret, Some(minor), _) if minor > 0 => Some((0, minor + 1, 0)),
+            (Op::Caret, Some(_), None) => Some((0, 1, 0)),
+            (Op::Caret, Some(_), Some(patch)) => Some((0, 0, patch + 1)),
+        };
+        min <= version && end.map_or(true, |end| version < end)
+    }
+}
+
+impl TryFrom<String> for VersionReq {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let s = s.trim();
+        let (op, version) = if let Some(v) = s.strip_prefix(">=") {
+            (Op::GreaterEq, v)
+        } else if let Some(v) = s.strip_prefix('^') {
+            (Op::Caret, v)
+        } else if let Some(v) = s.strip_prefix('~') {
+            (Op::Tilde, v)
+        } else if let Some(v) = s.strip_prefix('=') {
+            (Op::Exact, v)
+        } else {
+            (Op::Caret, s)
+        };
+        let invalid =
+            || format!("invalid version requirement {s:?}, expected something like \"^0.3\"");
+        let mut parts = version.trim().split('.');
+        let mut next = || {
+            parts
+                .next()
+                .map(|p| p.parse::<u64>().map_err(|_| invalid()))
+        };
+        let major = next().ok_or_else(invalid)??;
+        let minor = next().transpose()?;
+        let patch = next().transpose()?;
+        if next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            op,
+            major,
+            minor,
+            patch,
+        })
+    }
+}
+
+impl From<VersionReq> for String {
+    fn from(req: VersionReq) -> Self {
+        req.to_string()
+    }
+}
+
+impl Display for VersionReq {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let op = match self.op {
+            Op::Caret => "^",
+            Op::Tilde => "~",
+            Op::Exact => "=",
+            Op::GreaterEq => ">=",
+        };
+        write!(f, "{op}{}", self.major)?;
+        if let Some(minor) = self.minor {
+            write!(f, ".{minor}")?;
+        }
+        if let Some(patch) = self.patch {
+            write!(f, ".{patch}")?;
+        }
+        Ok(())
+    }
+}
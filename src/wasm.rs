@@ -0,0 +1,54 @@
+//! The javascript API. The library is only built as a cdylib for it:
+//!
+//! ```sh
+//! cargo rustc --lib --release --target wasm32-unknown-unknown --crate-type cdylib \
+//!     --no-default-features --features wasm
+//! wasm-bindgen --target web --out-dir pkg target/wasm32-unknown-unknown/release/typstfmt.wasm
+//! ```
+//!
+//! The config is the JSON version of `typstfmt.toml`, `{"indent_space": 4}` for
+//! instance, an empty string being the default config. Offsets are counted in
+//! UTF-16 code units like the indices of javascript strings.
+
+use wasm_bindgen::prelude::*;
+
+use crate::Config;
+
+fn config(json: &str) -> Result<Config, JsError> {
+    if json.trim().is_empty() {
+        return Ok(Config::default());
+    }
+    serde_json::from_str(json).map_err(|err| JsError::new(&format!("invalid config: {err}")))
+}
+
+/// the byte offset in `s` of the UTF-16 offset `offset`.
+fn byte_offset(s: &str, offset: usize) -> usize {
+    let mut utf16 = 0;
+    for (i, c) in s.char_indices() {
+        if utf16 >= offset {
+            return i;
+        }
+        utf16 += c.len_utf16();
+    }
+    s.len()
+}
+
+/// Formats `source`.
+#[wasm_bindgen]
+pub fn format(source: &str, config_json: &str) -> Result<String, JsError> {
+    Ok(crate::format(source, config(config_json)?))
+}
+
+/// Formats only the smallest construct enclosing `start..end`, returns the whole
+/// text, unchanged if there was nothing to format.
+#[wasm_bindgen(js_name = formatRange)]
+pub fn format_range(
+    source: &str,
+    start: usize,
+    end: usize,
+    config_json: &str,
+) -> Result<String, JsError> {
+    let range = byte_offset(source, start)..byte_offset(source, end);
+    Ok(crate::format_range(source, range, config(config_json)?)
+        .map_or_else(|| source.to_string(), |(text, _)| text))
+}